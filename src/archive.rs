@@ -1,39 +1,116 @@
 use std::{
-    collections::BTreeMap,
     io::{self, Read, Seek, SeekFrom, Write},
     num::NonZeroUsize,
+    sync::Arc,
 };
 
-use binrw::{binread, binrw, BinRead, BinWrite, FilePtr64, VecArgs};
+use binrw::{binread, binrw, BinRead, BinWrite, VecArgs};
 use hash40::Hash40;
 use semver::Version;
 
 use crate::{
     engines::{
+        lookup::HashLookup,
         packaged::{
             bucket_map::BucketMap,
-            types::{Info, Patch},
+            types::{Info, Package, Patch, Path},
             PackagedEngine, PackagedWriter, ToMemoryResults,
         },
-        search::{SearchEngine, SearchWriter},
-        stream::{StreamEngine, StreamWriter},
-        table::TableCell,
+        search::{
+            glob::GlobMatch,
+            types::{SearchFolder, SearchPath},
+            SearchEngine, SearchWriter,
+        },
+        stream::{types::StreamPath, StreamEngine, StreamWriter},
+        table::{ResolveError, Table, TableIndex},
     },
-    Hashable,
+    region::LocaleRegionTable,
+    Decompressor, GlobalDecompressor, Hashable,
 };
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Archive {
     pub file_section_offset: usize,
     pub packaged_fs: PackagedEngine,
     pub search_fs: SearchEngine,
     pub stream_fs: StreamEngine,
     pub version: Version,
-    pub region_lookup_table: Vec<(u32, u32, u32)>,
+    pub region_lookup_table: LocaleRegionTable,
+
+    /// How the data of this archive is compressed and decompressed
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub options: ArchiveOptions,
+}
+
+/// Controls how the data of an archive is compressed and decompressed
+///
+/// Every archive carries its own options, so archives in the same process can use
+/// different codecs. The default options use the decompressor set with
+/// [`set_decompressor`](crate::set_decompressor).
+#[derive(Clone)]
+pub struct ArchiveOptions {
+    /// The decompressor for the table sections and file data
+    pub decompressor: Arc<dyn Decompressor>,
+
+    /// The compressor for the table sections, used by [`Archive::write_tables`]
+    #[cfg(feature = "compression")]
+    pub compressor: Arc<dyn crate::Compressor>,
+}
+
+impl ArchiveOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses a different decompressor for the archive
+    pub fn decompressor(mut self, decompressor: Arc<dyn Decompressor>) -> Self {
+        self.decompressor = decompressor;
+        self
+    }
+
+    /// Uses a different compressor for the archive
+    #[cfg(feature = "compression")]
+    pub fn compressor(mut self, compressor: Arc<dyn crate::Compressor>) -> Self {
+        self.compressor = compressor;
+        self
+    }
+}
+
+impl Default for ArchiveOptions {
+    fn default() -> Self {
+        Self {
+            decompressor: Arc::new(GlobalDecompressor),
+            #[cfg(feature = "compression")]
+            compressor: Arc::new(crate::DefaultCompressor),
+        }
+    }
+}
+
+/// A place in the archive where a hash exists
+///
+/// Returned by [`Archive::lookup`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ArchiveEntry {
+    /// A file in the packaged filesystem
+    PackagedFile(TableIndex<Path>),
+
+    /// A package in the packaged filesystem
+    Package(TableIndex<Package>),
+
+    /// A file in the stream filesystem
+    StreamFile(TableIndex<StreamPath>),
+
+    /// A file or folder path in the search filesystem
+    SearchPath(TableIndex<SearchPath>),
+
+    /// A folder in the search filesystem
+    SearchFolder(TableIndex<SearchFolder>),
 }
 
 #[binrw]
 #[brw(magic = 0x10u32)]
-struct ArchiveTablesHeader {
+pub(crate) struct ArchiveTablesHeader {
     #[br(map = |size: u32| size as usize)]
     #[bw(map = |size: &usize| *size as u32)]
     pub decompressed_size: usize,
@@ -49,7 +126,11 @@ struct ArchiveTablesHeader {
 
 impl ArchiveTablesHeader {
     #[allow(clippy::uninit_vec)]
-    pub fn read_table<R: Read + Seek>(&self, reader: &mut R, offset: usize) -> io::Result<Vec<u8>> {
+    pub fn read_compressed<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        offset: usize,
+    ) -> io::Result<Vec<u8>> {
         let mut compressed_data = Vec::with_capacity(self.compressed_size);
 
         unsafe {
@@ -57,23 +138,49 @@ impl ArchiveTablesHeader {
         }
 
         reader.read_exact(&mut compressed_data)?;
-        let time = std::time::Instant::now();
-        let decompressed_data =
-            crate::decompress_data_with_size(&compressed_data, self.decompressed_size);
-        println!("{}", time.elapsed().as_secs_f32());
 
         reader.seek(io::SeekFrom::Start(
             (offset + self.compressed_section_size) as u64,
         ))?;
 
-        Ok(decompressed_data)
+        Ok(compressed_data)
+    }
+
+    pub fn decompress(
+        &self,
+        compressed_data: &[u8],
+        decompressor: &dyn Decompressor,
+    ) -> io::Result<Vec<u8>> {
+        decompressor.decompress_with_size(compressed_data, self.decompressed_size)
+    }
+
+    /// Decompresses the table section into a buffer owned by the caller
+    ///
+    /// ### Arguments
+    /// * `compressed_data` - The compressed table section
+    /// * `decompressor` - The decompressor to use
+    /// * `output` - The buffer to decompress into, which must be [`decompressed_size`](Self::decompressed_size) long
+    pub fn decompress_into(
+        &self,
+        compressed_data: &[u8],
+        decompressor: &dyn Decompressor,
+        output: &mut [u8],
+    ) -> io::Result<()> {
+        if decompressor.decompress_into(compressed_data, output)? != self.decompressed_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The table section is smaller than its header says",
+            ));
+        }
+
+        Ok(())
     }
 }
 
 #[binrw]
 #[bw(import { write_output: ToMemoryResults })]
 #[derive(Debug)]
-struct PackagedFsHeader {
+pub(crate) struct PackagedFsHeader {
     #[br(map = |count: u32| count as usize)]
     #[bw(map = |count: &usize| *count as u32)]
     pub path_count: usize,
@@ -115,14 +222,14 @@ struct PackagedFsHeader {
     #[bw(calc = write_output.group_info_len as u32)]
     group_info_count: u32,
 
-    #[br(map = |count: u8| count as usize)]
-    #[bw(map = |_: &usize| 14u8)]
-    pub locale_count: usize,
+    #[br(temp)]
+    #[bw(calc = locale_region_table.locale_count() as u8)]
+    locale_count: u8,
 
-    #[br(map = |count: u8| count as usize)]
-    #[bw(map = |_: &usize| 5u8)]
+    #[br(temp)]
+    #[bw(calc = locale_region_table.region_count() as u8)]
     #[brw(pad_after = 0x2)]
-    pub region_count: usize,
+    region_count: u8,
 
     #[br(map = |raw: (u8, u8, u16)| Version::new(raw.2 as u64, raw.1 as u64, raw.0 as u64))]
     #[bw(map = |version: &Version| (version.patch as u8, version.minor as u8, version.major as u16))]
@@ -149,8 +256,8 @@ struct PackagedFsHeader {
     #[bw(calc = write_output.version_data_len as u32)]
     version_metadata_count: u32,
 
-    #[br(count = locale_count)]
-    pub locale_region_hash_to_region: Vec<(u32, u32, u32)>,
+    #[br(args(locale_count as usize, region_count as usize))]
+    pub locale_region_table: LocaleRegionTable,
 
     #[br(calc = (metadata_group_count + info_group_count + version_group_count) as usize)]
     #[bw(ignore)]
@@ -170,7 +277,7 @@ struct PackagedFsHeader {
 }
 
 #[binrw]
-struct StreamFsHeader {
+pub(crate) struct StreamFsHeader {
     #[br(map = |count: u32| count as usize)]
     #[bw(map = |count: &usize| *count as u32)]
     pub folder_count: usize,
@@ -188,19 +295,83 @@ struct StreamFsHeader {
     pub metadata_count: usize,
 }
 
-struct ArchiveNonUserTables(PackagedEngine, StreamEngine, Version, Vec<(u32, u32, u32)>);
+/// The parts of an archive which are not stored in any of its tables
+///
+/// Written to `"archive_header.bin"` by [`Archive::to_directory`]
+#[binrw]
+#[brw(little)]
+struct ArchiveDirectoryHeader {
+    #[br(map = |offset: u64| offset as usize)]
+    #[bw(map = |offset: &usize| *offset as u64)]
+    file_section_offset: usize,
+
+    #[br(map = |raw: (u8, u8, u16)| Version::new(raw.2 as u64, raw.1 as u64, raw.0 as u64))]
+    #[bw(map = |version: &Version| (version.patch as u8, version.minor as u8, version.major as u16))]
+    version: Version,
 
-impl BinRead for ArchiveNonUserTables {
-    type Args = ();
+    #[br(temp)]
+    #[bw(calc = region_lookup_table.locale_count() as u32)]
+    locale_count: u32,
 
-    fn read_options<R: std::io::Read + std::io::Seek>(
+    #[br(temp)]
+    #[bw(calc = region_lookup_table.region_count() as u32)]
+    region_count: u32,
+
+    #[br(args(locale_count as usize, region_count as usize))]
+    region_lookup_table: LocaleRegionTable,
+}
+
+/// The header at the very start of the archive
+#[binread]
+#[br(magic = 0xABCDEF9876543210u64)]
+pub(crate) struct ArchiveHeader {
+    pub _stream_data_start: u64,
+    pub file_data_start: u64,
+    pub _shared_file_data_start: u64,
+    pub non_user_tables_start: u64,
+    pub user_tables_start: u64,
+}
+
+/// The compressed table sections of an archive, read but not yet decompressed
+pub(crate) struct CompressedTables {
+    pub non_user_header: ArchiveTablesHeader,
+    pub non_user: Vec<u8>,
+    pub user_header: ArchiveTablesHeader,
+    pub user: Vec<u8>,
+}
+
+impl CompressedTables {
+    pub fn read<R: Read + Seek>(
         reader: &mut R,
         options: &binrw::ReadOptions,
-        _args: Self::Args,
+        header: &ArchiveHeader,
     ) -> binrw::BinResult<Self> {
-        let header = ArchiveTablesHeader::read_options(reader, options, ())?;
+        reader.seek(io::SeekFrom::Start(header.non_user_tables_start))?;
+        let non_user_header = ArchiveTablesHeader::read_options(reader, options, ())?;
+        let offset = reader.stream_position()? as usize;
+        let non_user = non_user_header.read_compressed(reader, offset)?;
+
+        reader.seek(io::SeekFrom::Start(header.user_tables_start))?;
+        let user_header = ArchiveTablesHeader::read_options(reader, options, ())?;
         let offset = reader.stream_position()? as usize;
-        let data = header.read_table(reader, offset)?;
+        let user = user_header.read_compressed(reader, offset)?;
+
+        Ok(Self {
+            non_user_header,
+            non_user,
+            user_header,
+            user,
+        })
+    }
+}
+
+struct ArchiveNonUserTables(PackagedEngine, StreamEngine, Version, LocaleRegionTable);
+
+impl ArchiveNonUserTables {
+    /// Parses the decompressed non-user table section
+    ///
+    /// The lookups are left empty, see [`Archive::build_lookups`]
+    fn parse(data: Vec<u8>, options: &binrw::ReadOptions) -> binrw::BinResult<Self> {
         let mut data = io::Cursor::new(data);
         let _filesystem_size = u32::read_options(&mut data, options, ())?;
         let packaged_header = PackagedFsHeader::read_options(&mut data, options, ())?;
@@ -240,20 +411,14 @@ impl BinRead for ArchiveNonUserTables {
                 .finalize(),
         )?;
 
-        let mut stream_engine = StreamEngine {
-            path_lookup: BTreeMap::new(),
-            folders: stream_folders,
-            paths: stream_paths,
-            links: stream_links,
-            metadatas: stream_metadatas,
+        let stream_engine = StreamEngine {
+            path_lookup: HashLookup::new(),
+            folders: Table::from(stream_folders),
+            paths: Table::from(stream_paths),
+            links: Table::from(stream_links),
+            metadatas: Table::from(stream_metadatas),
         };
 
-        stream_engine.path_lookup = stream_engine
-            .paths
-            .iter()
-            .map(|cell| (cell.get().full_path, cell.clone()))
-            .collect();
-
         let path_lookup_count = u32::read_options(&mut data, options, ())? as usize;
         let path_bucket_count = u32::read_options(&mut data, options, ())? as usize;
 
@@ -334,7 +499,7 @@ impl BinRead for ArchiveNonUserTables {
 
         let num_patches = u32::read_options(&mut data, options, ())?;
 
-        let patches: Vec<TableCell<Patch>> = Vec::read_options(
+        let patches: Vec<Patch> = Vec::read_options(
             &mut data,
             options,
             VecArgs::builder().count(num_patches as usize).finalize(),
@@ -342,7 +507,7 @@ impl BinRead for ArchiveNonUserTables {
 
         let mut versioned_files = Vec::with_capacity(packaged_header.versioned_file_count);
 
-        for patch in patches.iter().map(TableCell::get) {
+        for patch in patches.iter() {
             versioned_files.extend(Vec::read_options(
                 &mut data,
                 options,
@@ -358,47 +523,36 @@ impl BinRead for ArchiveNonUserTables {
             ))?;
         }
 
-        let mut packaged_engine = PackagedEngine {
+        let packaged_engine = PackagedEngine {
             version,
-            package_lookup: BTreeMap::new(),
+            package_lookup: HashLookup::new(),
             file_lookup: BucketMap::new(
                 NonZeroUsize::new(path_bucket_count).expect("Bucket count should be non-zero"),
             ),
-            packages,
-            child_packages,
-            groups,
-            paths,
-            links,
-            infos,
-            descriptors,
-            metadatas,
-            patches,
-            versioned_files,
+            packages: Table::from(packages),
+            child_packages: Table::from(child_packages),
+            groups: Table::from(groups),
+            paths: Table::from(paths),
+            links: Table::from(links),
+            infos: Table::from(infos),
+            descriptors: Table::from(descriptors),
+            metadatas: Table::from(metadatas),
+            patches: Table::from(patches),
+            versioned_files: Table::from(versioned_files),
+            resolve_offsets: None,
         };
 
-        packaged_engine.package_lookup = packaged_engine
-            .packages
-            .iter()
-            .map(|package| (package.get().full_path, package.clone()))
-            .collect();
-
-        for path in packaged_engine.paths.iter() {
-            packaged_engine
-                .file_lookup
-                .insert(path.get().full_path, path.clone());
-        }
-
         Ok(Self(
             packaged_engine,
             stream_engine,
             packaged_header.version,
-            packaged_header.locale_region_hash_to_region,
+            packaged_header.locale_region_table,
         ))
     }
 }
 
 #[binrw]
-struct SearchFsHeader {
+pub(crate) struct SearchFsHeader {
     #[br(map = |count: u32| count as usize)]
     #[bw(map = |count: &usize| *count as u32)]
     pub folder_count: usize,
@@ -414,17 +568,11 @@ struct SearchFsHeader {
 
 struct ArchiveUserTables(SearchEngine);
 
-impl BinRead for ArchiveUserTables {
-    type Args = ();
-
-    fn read_options<R: std::io::Read + std::io::Seek>(
-        reader: &mut R,
-        options: &binrw::ReadOptions,
-        args: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        let header = ArchiveTablesHeader::read_options(reader, options, ())?;
-        let offset = reader.stream_position()? as usize;
-        let data = header.read_table(reader, offset)?;
+impl ArchiveUserTables {
+    /// Parses the decompressed user table section
+    ///
+    /// The lookups are left empty, see [`Archive::build_lookups`]
+    fn parse(data: Vec<u8>, options: &binrw::ReadOptions) -> binrw::BinResult<Self> {
         let mut data = io::Cursor::new(data);
 
         let _filesystem_size = u64::read_options(&mut data, options, ())?;
@@ -455,83 +603,235 @@ impl BinRead for ArchiveUserTables {
                 .finalize(),
         )?;
 
-        let mut search_engine = SearchEngine {
-            folder_lookup: BTreeMap::new(),
-            path_lookup: BTreeMap::new(),
-            folders,
-            paths,
+        let search_engine = SearchEngine {
+            folder_lookup: HashLookup::new(),
+            path_lookup: HashLookup::new(),
+            folders: Table::from(folders),
+            paths: Table::from(paths),
         };
 
-        search_engine.folder_lookup = search_engine
-            .folders
-            .iter()
-            .map(|folder| (folder.get().full_path, folder.clone()))
-            .collect();
-        search_engine.path_lookup = search_engine
-            .paths
-            .iter()
-            .filter_map(|path| {
-                if path.get().full_path == Hash40::new("") {
-                    None
-                } else {
-                    Some((path.get().full_path, path.clone()))
-                }
-            })
-            .collect();
-
         Ok(Self(search_engine))
     }
 }
 
 impl BinRead for Archive {
-    type Args = ();
+    type Args = ArchiveOptions;
 
     fn read_options<R: std::io::Read + std::io::Seek>(
         reader: &mut R,
         options: &binrw::ReadOptions,
-        _args: Self::Args,
+        args: Self::Args,
     ) -> binrw::BinResult<Self> {
-        const MAGIC: u64 = 0xABCDEF9876543210;
-
-        let magic = u64::read_options(reader, options, ())?;
-        if magic != MAGIC {
-            return Err(binrw::Error::BadMagic {
-                pos: options.offset(),
-                found: Box::new(format!("{:#x}", magic)),
-            });
-        }
+        let header = ArchiveHeader::read_options(reader, options, ())?;
+        let tables = CompressedTables::read(reader, options, &header)?;
+        let decompressor = args.decompressor.as_ref();
+        let non_user = tables
+            .non_user_header
+            .decompress(&tables.non_user, decompressor)?;
+        let user = tables.user_header.decompress(&tables.user, decompressor)?;
+
+        let mut archive = Self::from_decompressed(&header, non_user, user, options, args)?;
+        archive.build_lookups();
+        Ok(archive)
+    }
+}
 
-        let _stream_data_start = u64::read_options(reader, options, ())?;
-        let file_data_start = u64::read_options(reader, options, ())?;
-        let _shared_file_data_start = u64::read_options(reader, options, ())?;
+impl Archive {
+    pub fn open(path: impl AsRef<std::path::Path>) -> binrw::BinResult<Self> {
+        Self::open_with(path, ArchiveOptions::default())
+    }
+
+    /// Opens an archive which uses its own compression options
+    ///
+    /// ### Arguments
+    /// * `path` - The path to the archive
+    /// * `options` - The options to decompress the tables with, which are kept by the archive
+    ///   for reading file data later
+    pub fn open_with(
+        path: impl AsRef<std::path::Path>,
+        options: ArchiveOptions,
+    ) -> binrw::BinResult<Self> {
+        let mut reader = std::io::BufReader::with_capacity(0x0010_0000, std::fs::File::open(path)?);
+        Self::read_args(&mut reader, options)
+    }
 
+    /// Builds an archive from its decompressed table sections, without building any lookups
+    pub(crate) fn from_decompressed(
+        header: &ArchiveHeader,
+        non_user: Vec<u8>,
+        user: Vec<u8>,
+        options: &binrw::ReadOptions,
+        archive_options: ArchiveOptions,
+    ) -> binrw::BinResult<Self> {
         let ArchiveNonUserTables(packaged_fs, stream_fs, version, region_lookup_table) =
-            FilePtr64::parse(reader, options, ())?;
-        let ArchiveUserTables(search_fs) = FilePtr64::parse(reader, options, ())?;
+            ArchiveNonUserTables::parse(non_user, options)?;
+        let ArchiveUserTables(search_fs) = ArchiveUserTables::parse(user, options)?;
 
         Ok(Self {
-            file_section_offset: file_data_start as usize,
+            file_section_offset: header.file_data_start as usize,
             packaged_fs,
             search_fs,
             stream_fs,
             version,
             region_lookup_table,
+            options: archive_options,
         })
     }
-}
 
-impl Archive {
-    pub fn open(path: impl AsRef<std::path::Path>) -> binrw::BinResult<Self> {
-        let mut reader = std::io::BufReader::with_capacity(0x0010_0000, std::fs::File::open(path)?);
-        Self::read(&mut reader)
+    /// Builds the hash lookups of every engine from their tables
+    pub(crate) fn build_lookups(&mut self) {
+        self.packaged_fs.package_lookup = self
+            .packaged_fs
+            .packages
+            .iter_indexed()
+            .map(|(index, package)| (package.full_path, index))
+            .collect();
+
+        for (index, path) in self.packaged_fs.paths.iter_indexed() {
+            self.packaged_fs.file_lookup.insert(path.full_path, index);
+        }
+
+        self.stream_fs.path_lookup = self
+            .stream_fs
+            .paths
+            .iter_indexed()
+            .map(|(index, path)| (path.full_path, index))
+            .collect();
+
+        self.search_fs.folder_lookup = self
+            .search_fs
+            .folders
+            .iter_indexed()
+            .map(|(index, folder)| (folder.full_path, index))
+            .collect();
+
+        self.search_fs.path_lookup = self
+            .search_fs
+            .paths
+            .iter_indexed()
+            .filter_map(|(index, path)| {
+                if path.full_path == Hash40::new("") {
+                    None
+                } else {
+                    Some((path.full_path, index))
+                }
+            })
+            .collect();
+    }
+
+    /// Reads an archive from a directory of tables, as written by [`to_directory`](Self::to_directory)
+    ///
+    /// ### Arguments
+    /// * `path` - The path of the directory to read from
+    ///
+    /// ### Returns
+    /// * `Ok(Self)` - The unresolved archive, with all of its lookups generated
+    /// * `Err(_)` - There was an error reading/parsing the tables
+    ///
+    /// ### Notes
+    /// * The version, region lookup table and file section offset are read from `"archive_header.bin"`
+    /// * The archive uses the default [`ArchiveOptions`]
+    /// * See [`PackagedEngine::from_directory`], [`SearchEngine::from_directory`] and
+    ///   [`StreamEngine::from_directory`] for the rest of the files
+    pub fn from_directory(path: impl AsRef<std::path::Path>) -> binrw::BinResult<Self> {
+        let path = path.as_ref();
+
+        let mut header = io::Cursor::new(std::fs::read(path.join("archive_header.bin"))?);
+        let ArchiveDirectoryHeader {
+            file_section_offset,
+            version,
+            region_lookup_table,
+        } = ArchiveDirectoryHeader::read(&mut header)?;
+
+        Ok(Self {
+            file_section_offset,
+            packaged_fs: PackagedEngine::from_directory(path)?,
+            search_fs: SearchEngine::from_directory(path)?,
+            stream_fs: StreamEngine::from_directory(path)?,
+            version,
+            region_lookup_table,
+            options: ArchiveOptions::default(),
+        })
     }
 
-    pub fn resolve(&self) {
+    /// Writes every table of the archive to a directory, consuming the archive
+    ///
+    /// ### Arguments
+    /// * `path` - The path of the directory to write to, which is created if it doesn't exist
+    ///
+    /// ### Returns
+    /// * `Ok(())` - There were no errors while writing
+    /// * `Err(_)` - There were errors while serializing tables
+    ///
+    /// ### Panicking
+    /// Panics if the archive has not been [resolved](Self::resolve)
+    ///
+    /// ### Notes
+    /// * The tables are reorganized the same way as [`write_tables`](Self::write_tables), so
+    ///   reading them back with [`from_directory`](Self::from_directory) writes the same tables
+    /// * The version, region lookup table and file section offset are written to `"archive_header.bin"`
+    /// * See [`PackagedWriter::to_directory`], [`SearchWriter::to_directory`] and
+    ///   [`StreamWriter::to_directory`] for the rest of the files
+    pub fn to_directory(self, path: impl AsRef<std::path::Path>) -> binrw::BinResult<()> {
+        let path = path.as_ref();
+        std::fs::create_dir_all(path)?;
+
+        let Self {
+            file_section_offset,
+            packaged_fs,
+            search_fs,
+            stream_fs,
+            version,
+            region_lookup_table,
+            ..
+        } = self;
+
+        let mut header = io::Cursor::new(vec![]);
+        ArchiveDirectoryHeader {
+            file_section_offset,
+            version,
+            region_lookup_table,
+        }
+        .write_to(&mut header)?;
+        std::fs::write(path.join("archive_header.bin"), header.into_inner())?;
+
+        PackagedWriter::from_engine(packaged_fs).to_directory(path)?;
+        SearchWriter::from_engine(search_fs).to_directory(path)?;
+        StreamWriter::from_engine(stream_fs).to_directory(path)
+    }
+
+    pub fn resolve(&mut self) {
         self.packaged_fs.resolve();
         self.search_fs.resolve();
         self.stream_fs.resolve();
     }
 
+    /// Resolves every filesystem in the archive, without panicking on unexpected data
+    ///
+    /// ### Returns
+    /// * `Ok(())` - Every filesystem was resolved
+    /// * `Err(_)` - A table refers to something that doesn't exist. The filesystems are
+    ///   left partially resolved, and the archive should not be used further.
+    pub fn try_resolve(&mut self) -> Result<(), ResolveError> {
+        self.packaged_fs.try_resolve()?;
+        self.search_fs.try_resolve()?;
+        self.stream_fs.try_resolve()
+    }
+
+    /// Gets a package by hash, resolving only that package if it has not been resolved yet
+    ///
+    /// See [`PackagedEngine::resolve_package`] for what is resolved
+    pub fn resolve_package(&mut self, hash: impl Hashable) -> Option<&Package> {
+        self.packaged_fs.resolve_package(hash)
+    }
+
+    /// Gets a packaged file by hash, resolving only that file if it has not been resolved yet
+    ///
+    /// See [`PackagedEngine::resolve_file`] for what is resolved
+    pub fn resolve_file(&mut self, hash: impl Hashable) -> Option<&Path> {
+        self.packaged_fs.resolve_file(hash)
+    }
+
     pub fn reorganize(self) -> Self {
         let Self {
             file_section_offset,
@@ -540,6 +840,7 @@ impl Archive {
             stream_fs,
             version,
             region_lookup_table,
+            options,
         } = self;
         Self {
             file_section_offset,
@@ -548,10 +849,59 @@ impl Archive {
             stream_fs: stream_fs.reorganize(),
             version,
             region_lookup_table,
+            options,
         }
     }
 
-    pub fn add_file(&mut self, file: impl AsRef<str>, package: impl AsRef<str>) -> TableCell<Info> {
+    /// Finds every path in the search filesystem which matches a glob pattern
+    ///
+    /// See [`SearchEngine::glob`] for the supported patterns, and for which of them can
+    /// match names that have no label
+    pub fn glob(&self, pattern: impl AsRef<str>) -> Vec<GlobMatch> {
+        self.search_fs.glob(pattern)
+    }
+
+    /// Finds every place in the archive where a hash exists
+    ///
+    /// ### Arguments
+    /// * `hash` - The hash of a full path, such as a file, package or folder
+    ///
+    /// ### Returns
+    /// An entry for every table that the hash is in, in the order of the variants of
+    /// [`ArchiveEntry`]. If the hash is not in the archive, this is empty.
+    pub fn lookup(&self, hash: impl Hashable) -> Vec<ArchiveEntry> {
+        let hash = hash.to_hash();
+
+        let mut entries = vec![];
+
+        if let Some(path) = self.packaged_fs.file_lookup.get(hash) {
+            entries.push(ArchiveEntry::PackagedFile(*path));
+        }
+
+        if let Some(package) = self.packaged_fs.package_lookup.get(&hash) {
+            entries.push(ArchiveEntry::Package(*package));
+        }
+
+        if let Some(path) = self.stream_fs.path_lookup.get(&hash) {
+            entries.push(ArchiveEntry::StreamFile(*path));
+        }
+
+        if let Some(path) = self.search_fs.path_lookup.get(&hash) {
+            entries.push(ArchiveEntry::SearchPath(*path));
+        }
+
+        if let Some(folder) = self.search_fs.folder_lookup.get(&hash) {
+            entries.push(ArchiveEntry::SearchFolder(*folder));
+        }
+
+        entries
+    }
+
+    pub fn add_file(
+        &mut self,
+        file: impl AsRef<str>,
+        package: impl AsRef<str>,
+    ) -> TableIndex<Info> {
         let file = file.as_ref();
         let package = package.as_ref();
 
@@ -567,6 +917,7 @@ impl Archive {
             stream_fs,
             version,
             region_lookup_table,
+            options,
             ..
         } = self;
 
@@ -600,11 +951,9 @@ impl Archive {
             link_count: links,
             package_count: packages,
             child_package_count: child_packages,
-            locale_count: 0,
-            region_count: 0,
             version,
             versioned_file_count: versioned_files,
-            locale_region_hash_to_region: region_lookup_table,
+            locale_region_table: region_lookup_table,
             group_count: 0,
             info_count: 0,
             descriptor_count: 0,
@@ -628,7 +977,7 @@ impl Archive {
 
         let data = data.into_inner();
         let decompressed_non_user_len = data.len();
-        let compressed_non_user_data = crate::compress_data(data);
+        let compressed_non_user_data = options.compressor.compress(&data)?;
 
         let search_folders = search_writer.folders.len();
         let search_paths = search_writer.paths.len();
@@ -651,7 +1000,7 @@ impl Archive {
 
         let data = data.into_inner();
         let decompressed_user_data_len = data.len();
-        let compressed_user_data = crate::compress_data(data);
+        let compressed_user_data = options.compressor.compress(&data)?;
 
         let mut compressed_non_user_section_size = compressed_non_user_data.len();
         if compressed_non_user_section_size % 8 != 0 {
@@ -685,3 +1034,191 @@ impl Archive {
         Ok((non_user_fs_start as usize, user_fs_start as usize))
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(feature = "compression")]
+    use std::{
+        io,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use semver::Version;
+
+    use super::{Archive, ArchiveEntry, ArchiveOptions};
+    use crate::{
+        engines::{
+            packaged::PackagedEngine, search::SearchEngine, stream::StreamEngine, VariantKind,
+        },
+        region::LocaleRegionTable,
+        Hashable,
+    };
+    #[cfg(feature = "compression")]
+    use crate::{Decompressor, DefaultDecompressor};
+
+    /// Builds a small archive with a package, two files and a stream file
+    pub(crate) fn build_archive() -> Archive {
+        let version = Version::new(13, 0, 1);
+        let mut archive = Archive {
+            file_section_offset: 0,
+            packaged_fs: PackagedEngine::new(version.clone()),
+            search_fs: SearchEngine::new(),
+            stream_fs: StreamEngine::new(),
+            version,
+            region_lookup_table: LocaleRegionTable::retail(),
+            options: ArchiveOptions::default(),
+        };
+
+        archive
+            .packaged_fs
+            .add_package("fighter/mario/c00", "fighter", VariantKind::None);
+        archive.add_file(
+            "fighter/mario/model/body/c00/model.numdlb",
+            "fighter/mario/c00",
+        );
+        archive.add_file(
+            "fighter/mario/motion/body/c00/a00wait1.nuanmb",
+            "fighter/mario/c00",
+        );
+        archive
+            .stream_fs
+            .add_file("stream:/sound/bgm/bgm_crs01.nus3audio", VariantKind::None);
+        archive
+    }
+
+    #[cfg(feature = "compression")]
+    pub(crate) fn write_tables(archive: Archive) -> Vec<u8> {
+        let mut writer = std::io::Cursor::new(vec![]);
+        archive.write_tables(&mut writer).unwrap();
+        writer.into_inner()
+    }
+
+    /// Writes a whole archive file, with the tables right after the header and no file data
+    #[cfg(feature = "compression")]
+    pub(crate) fn write_archive(archive: Archive) -> Vec<u8> {
+        use std::io::{Seek, SeekFrom, Write};
+
+        let mut writer = std::io::Cursor::new(vec![0u8; 0x30]);
+        writer.seek(SeekFrom::End(0)).unwrap();
+        let (non_user, user) = archive.write_tables(&mut writer).unwrap();
+
+        writer.seek(SeekFrom::Start(0)).unwrap();
+        for value in [0xABCDEF9876543210, 0, 0, 0, non_user as u64, user as u64] {
+            writer.write_all(&u64::to_le_bytes(value)).unwrap();
+        }
+        writer.into_inner()
+    }
+
+    #[test]
+    fn lookup_finds_every_table() {
+        let archive = build_archive();
+        let model = "fighter/mario/model/body/c00/model.numdlb".to_hash();
+
+        assert_eq!(
+            archive.lookup(model),
+            [
+                ArchiveEntry::PackagedFile(*archive.packaged_fs.file_lookup.get(model).unwrap()),
+                ArchiveEntry::SearchPath(*archive.search_fs.path_lookup.get(&model).unwrap()),
+            ]
+        );
+
+        let package = archive.lookup("fighter/mario/c00");
+        assert!(matches!(package[..], [ArchiveEntry::Package(_)]));
+
+        let folder = archive.lookup("fighter/mario");
+        assert!(matches!(
+            folder[..],
+            [ArchiveEntry::SearchPath(_), ArchiveEntry::SearchFolder(_)]
+        ));
+
+        let stream = archive.lookup("stream:/sound/bgm/bgm_crs01.nus3audio");
+        assert!(matches!(stream[..], [ArchiveEntry::StreamFile(_)]));
+
+        assert!(archive.lookup("fighter/luigi/c00").is_empty());
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn directory_round_trip_writes_the_same_tables() {
+        let directory = std::env::temp_dir().join(format!("snarc-tables-{}", std::process::id()));
+        build_archive().to_directory(&directory).unwrap();
+
+        let read = Archive::from_directory(&directory);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let mut read = read.unwrap();
+        read.resolve();
+
+        assert_eq!(write_tables(build_archive()), write_tables(read));
+    }
+
+    /// Decompresses with the default decompressor, counting every call
+    #[cfg(feature = "compression")]
+    #[derive(Default)]
+    struct CountingDecompressor(AtomicUsize);
+
+    #[cfg(feature = "compression")]
+    impl Decompressor for CountingDecompressor {
+        fn decompress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            DefaultDecompressor.decompress(data)
+        }
+
+        fn decompress_with_size(&self, data: &[u8], size: usize) -> io::Result<Vec<u8>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            DefaultDecompressor.decompress_with_size(data, size)
+        }
+    }
+
+    /// Fails to decompress anything
+    #[cfg(feature = "compression")]
+    struct FailingDecompressor;
+
+    #[cfg(feature = "compression")]
+    impl Decompressor for FailingDecompressor {
+        fn decompress(&self, _data: &[u8]) -> io::Result<Vec<u8>> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+
+        fn decompress_with_size(&self, _data: &[u8], _size: usize) -> io::Result<Vec<u8>> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn archives_use_their_own_decompressor() {
+        use binrw::BinRead;
+        use std::{io::Cursor, sync::Arc};
+
+        let data = write_archive(build_archive());
+
+        let counting = Arc::new(CountingDecompressor::default());
+        let options = ArchiveOptions::new().decompressor(counting.clone());
+        let archive = Archive::read_args(&mut Cursor::new(&data), options).unwrap();
+        assert_eq!(counting.0.load(Ordering::SeqCst), 2);
+
+        let other = Archive::read_args(&mut Cursor::new(&data), ArchiveOptions::default()).unwrap();
+        assert_eq!(counting.0.load(Ordering::SeqCst), 2);
+
+        let options = ArchiveOptions::new().decompressor(Arc::new(FailingDecompressor));
+        assert!(Archive::read_args(&mut Cursor::new(&data), options).is_err());
+
+        let compressed = zstd::bulk::compress(b"archive data", 0).unwrap();
+        let mut output = [0u8; 12];
+        archive
+            .options
+            .decompressor
+            .decompress_into(&compressed, &mut output)
+            .unwrap();
+        assert_eq!(&output, b"archive data");
+        assert_eq!(counting.0.load(Ordering::SeqCst), 3);
+
+        other
+            .options
+            .decompressor
+            .decompress_into(&compressed, &mut output)
+            .unwrap();
+        assert_eq!(counting.0.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,482 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use clap::{Parser, Subcommand};
+use hash40::Hash40;
+use snarc::{
+    archive::Archive,
+    engines::{search::dir::SortOrder, Locale},
+    region::LOCALE_COUNT,
+    stats::SizeTotals,
+};
+
+type CliResult<T = ()> = Result<T, Box<dyn Error>>;
+
+/// Inspect, extract and rebuild Smash Ultimate `data.arc` archives
+#[derive(Parser)]
+#[command(name = "snarc", version)]
+struct Cli {
+    /// A label file to resolve hashes with, such as `Labels.txt`
+    #[arg(short, long, global = true)]
+    labels: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the version of an archive and the size of each of its tables
+    Info { archive: PathBuf },
+
+    /// List the contents of a folder
+    Ls {
+        archive: PathBuf,
+
+        /// The folder to list, with no trailing `/`
+        #[arg(default_value = "/")]
+        folder: String,
+
+        /// List the contents of every subfolder as well
+        #[arg(short, long)]
+        recursive: bool,
+    },
+
+    /// Print the folder hierarchy under a folder
+    Tree {
+        archive: PathBuf,
+
+        /// The folder to print, with no trailing `/`
+        #[arg(default_value = "/")]
+        folder: String,
+
+        /// How many levels of folders to print
+        #[arg(short, long)]
+        depth: Option<usize>,
+    },
+
+    /// Write the decompressed data of a file to stdout
+    Cat {
+        archive: PathBuf,
+
+        /// The full path of the file
+        file: String,
+
+        /// The locale to pick localized and regional data with, such as `us_en`
+        #[arg(long)]
+        locale: Option<String>,
+    },
+
+    /// Extract every file which matches a glob pattern
+    Extract {
+        archive: PathBuf,
+
+        /// The directory to extract to
+        output: PathBuf,
+
+        /// The files to extract, relative to the root of the archive
+        #[arg(short, long, default_value = "**")]
+        pattern: String,
+
+        /// The locale to pick localized and regional data with, such as `us_en`
+        #[arg(long)]
+        locale: Option<String>,
+    },
+
+    /// Print size and composition statistics
+    Stats { archive: PathBuf },
+
+    /// Print the differences between two archives as JSON
+    Diff {
+        archive: PathBuf,
+        other: PathBuf,
+
+        /// Also compare the contents of files which are in both archives
+        #[arg(long)]
+        data: bool,
+    },
+
+    /// Check that an archive's tables resolve and that its file data is in bounds
+    Validate { archive: PathBuf },
+
+    /// Write every table of an archive to a directory
+    DumpTables {
+        archive: PathBuf,
+
+        /// The directory to write the tables to
+        output: PathBuf,
+    },
+
+    /// Rebuild the compressed table sections of an archive
+    Repack {
+        archive: PathBuf,
+
+        /// The file to write the table sections to
+        output: PathBuf,
+    },
+}
+
+/// Opens and resolves all of an archive, for the commands which read every table
+///
+/// Commands which only read a few tables open the archive with [`Archive::open`] and resolve
+/// just those, since resolving the packaged filesystem of a full archive is slow.
+fn open(path: &Path) -> CliResult<Archive> {
+    let mut archive = Archive::open(path)?;
+    archive.try_resolve()?;
+    Ok(archive)
+}
+
+/// Parses a locale by its short name, such as `us_en`
+fn parse_locale(locale: Option<&str>) -> CliResult<Locale> {
+    let name = match locale {
+        Some(name) => name,
+        None => return Ok(Locale::Japanese),
+    };
+
+    (0..LOCALE_COUNT as i32)
+        .map(Locale::from)
+        .find(|locale| locale.as_str() == name)
+        .ok_or_else(|| format!("Unknown locale {}", name).into())
+}
+
+/// Reads the data of a packaged or stream file into `buffer`, returning whether it has any
+///
+/// The stream filesystem must already be resolved. A packaged file is resolved on its own.
+/// `scratch` holds compressed data, and is only kept to reuse its allocation.
+fn read_file<R: Read + Seek>(
+    archive: &mut Archive,
+    reader: &mut R,
+    file: Hash40,
+    locale: Locale,
+    buffer: &mut Vec<u8>,
+    scratch: &mut Vec<u8>,
+) -> CliResult<bool> {
+    if archive.stream_fs.get_path(file).is_some() {
+        let resolution = match archive.stream_fs.resolve_variant(file, locale) {
+            Some(resolution) => resolution,
+            None => return Ok(false),
+        };
+        let metadata = &archive.stream_fs.metadatas[resolution.metadata];
+
+        reader.seek(SeekFrom::Start(metadata.offset as u64))?;
+        buffer.clear();
+        buffer.resize(metadata.size, 0);
+        reader.read_exact(buffer)?;
+        return Ok(true);
+    }
+
+    archive.packaged_fs.try_resolve_file(file)?;
+    let view = archive
+        .at_version(archive.version.clone())
+        .expect("The archive's own version should always be viewable");
+    Ok(view.read_file_into(reader, file, locale, buffer, scratch)?)
+}
+
+/// Finds where to extract a file to, from its full path
+///
+/// ### Returns
+/// `None` if the path could point outside of `output`, such as a label with `..` in it
+fn extract_destination(output: &Path, name: &str) -> Option<PathBuf> {
+    let relative = PathBuf::from(name.replace(':', ";"));
+    relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        .then(|| output.join(relative))
+}
+
+fn print_totals(name: &str, totals: &SizeTotals) {
+    println!(
+        "{:<24} {:>8} files {:>14} bytes {:>14} bytes decompressed",
+        name, totals.file_count, totals.compressed_size, totals.decompressed_size
+    );
+}
+
+fn info(path: &Path) -> CliResult {
+    let archive = Archive::open(path)?;
+    let packaged = &archive.packaged_fs;
+
+    println!("version:              {}", archive.version);
+    println!("file section offset:  {:#x}", archive.file_section_offset);
+    println!("packages:             {}", packaged.packages.len());
+    println!("child packages:       {}", packaged.child_packages.len());
+    println!("groups:               {}", packaged.groups.len());
+    println!("paths:                {}", packaged.paths.len());
+    println!("links:                {}", packaged.links.len());
+    println!("infos:                {}", packaged.infos.len());
+    println!("descriptors:          {}", packaged.descriptors.len());
+    println!("metadatas:            {}", packaged.metadatas.len());
+    println!("patches:              {}", packaged.patches.len());
+    println!("versioned files:      {}", packaged.versioned_files.len());
+    println!("stream folders:       {}", archive.stream_fs.folders.len());
+    println!("stream paths:         {}", archive.stream_fs.paths.len());
+    println!("stream metadatas:     {}", archive.stream_fs.metadatas.len());
+    println!("search folders:       {}", archive.search_fs.folders.len());
+    println!("search paths:         {}", archive.search_fs.paths.len());
+    Ok(())
+}
+
+fn ls(path: &Path, folder: &str, recursive: bool) -> CliResult {
+    let mut archive = Archive::open(path)?;
+    archive.search_fs.try_resolve()?;
+    let walk = archive
+        .search_fs
+        .walk(folder)
+        .sort(SortOrder::Label)
+        .folders_first(true);
+    let walk = if recursive { walk } else { walk.max_depth(1) };
+
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    for entry in walk {
+        let suffix = if entry.is_folder() { "/" } else { "" };
+        writeln!(stdout, "{}{}", entry.full_path().to_label(), suffix)?;
+    }
+
+    Ok(())
+}
+
+fn tree(path: &Path, folder: &str, depth: Option<usize>) -> CliResult {
+    let mut archive = Archive::open(path)?;
+    archive.search_fs.try_resolve()?;
+    let walk = archive
+        .search_fs
+        .walk(folder)
+        .sort(SortOrder::Label)
+        .folders_first(true)
+        .max_depth(depth.unwrap_or(usize::MAX));
+
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    writeln!(stdout, "{}", folder)?;
+    for entry in walk {
+        let suffix = if entry.is_folder() { "/" } else { "" };
+        let indent = "    ".repeat(entry.depth() - 1);
+        writeln!(stdout, "{}{}{}", indent, entry.name().to_label(), suffix)?;
+    }
+
+    Ok(())
+}
+
+fn cat(path: &Path, file: &str, locale: Option<&str>) -> CliResult {
+    let mut archive = Archive::open(path)?;
+    archive.stream_fs.try_resolve()?;
+    let locale = parse_locale(locale)?;
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let (mut data, mut scratch) = (Vec::new(), Vec::new());
+    let hash = Hash40::new(file);
+    if !read_file(&mut archive, &mut reader, hash, locale, &mut data, &mut scratch)? {
+        return Err(format!("{} is not a file with data", file).into());
+    }
+    io::stdout().lock().write_all(&data)?;
+    Ok(())
+}
+
+fn extract(path: &Path, output: &Path, pattern: &str, locale: Option<&str>) -> CliResult {
+    let mut archive = Archive::open(path)?;
+    archive.search_fs.try_resolve()?;
+    archive.stream_fs.try_resolve()?;
+    let locale = parse_locale(locale)?;
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut count = 0;
+    let (mut data, mut scratch) = (Vec::new(), Vec::new());
+    for entry in archive.glob(pattern) {
+        if archive.search_fs.get_folder(entry.full_path).is_some() {
+            continue;
+        }
+
+        let name = entry
+            .label
+            .unwrap_or_else(|| entry.full_path.to_label());
+
+        let destination = match extract_destination(output, &name) {
+            Some(destination) => destination,
+            None => {
+                eprintln!("Skipping {}, which is not a path inside of the output", name);
+                continue;
+            }
+        };
+
+        let hash = entry.full_path;
+        if !read_file(&mut archive, &mut reader, hash, locale, &mut data, &mut scratch)? {
+            eprintln!("Skipping {}, which has no data", name);
+            continue;
+        }
+
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(destination, &data)?;
+        count += 1;
+    }
+
+    eprintln!("Extracted {} files", count);
+    Ok(())
+}
+
+fn stats(path: &Path) -> CliResult {
+    let archive = open(path)?;
+    let stats = archive.stats();
+
+    print_totals("packaged", &stats.packaged);
+    print_totals("stream", &stats.stream);
+    print_totals("groups", &stats.group_totals);
+    print_totals("dedup savings", &stats.dedup_savings);
+    println!();
+    println!("files:                  {}", stats.file_count);
+    println!("shared files:           {}", stats.shared_file_count);
+    println!("localized files:        {}", stats.localized_file_count);
+    println!("regional files:         {}", stats.regional_file_count);
+    println!("graphics archive files: {}", stats.graphics_archive_file_count);
+    println!();
+
+    let mut folders: Vec<_> = stats.by_top_level_folder.iter().collect();
+    folders.sort_by_key(|(_, totals)| std::cmp::Reverse(totals.compressed_size));
+    for (folder, totals) in folders {
+        print_totals(&folder.to_label(), totals);
+    }
+
+    Ok(())
+}
+
+fn diff(path: &Path, other_path: &Path, data: bool) -> CliResult {
+    let archive = open(path)?;
+    let other = open(other_path)?;
+
+    let diff = if data {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut other_reader = BufReader::new(File::open(other_path)?);
+        archive.diff_with_data(&mut reader, &other, &mut other_reader)?
+    } else {
+        archive.diff(&other)
+    };
+
+    diff.write_json(&mut BufWriter::new(io::stdout().lock()))?;
+    Ok(())
+}
+
+fn validate(path: &Path) -> CliResult {
+    let mut archive = Archive::open(path)?;
+    archive
+        .try_resolve()
+        .map_err(|error| format!("The archive's tables could not be resolved: {}", error))?;
+
+    let archive_size = std::fs::metadata(path)?.len() as usize;
+    let mut problems = vec![];
+
+    let packaged = &archive.packaged_fs;
+    let view = archive
+        .at_version(archive.version.clone())
+        .expect("The archive's own version should always be viewable");
+
+    for file in packaged.paths.iter() {
+        let name = file.full_path.to_label();
+
+        if archive.search_fs.get_path(file.full_path).is_none() {
+            problems.push(format!("{} is not in the search filesystem", name));
+        }
+
+        if let Some(location) = view.file_location(file.full_path, Locale::Japanese) {
+            if location.offset + location.compressed_size > archive_size {
+                problems.push(format!("The data of {} is past the end of the archive", name));
+            }
+        }
+    }
+
+    for file in archive.stream_fs.paths.iter() {
+        for link in file.links.iter() {
+            let metadata = &archive.stream_fs.metadatas[archive.stream_fs.links[link].metadata()];
+            if metadata.offset + metadata.size > archive_size {
+                problems.push(format!(
+                    "The data of {} is past the end of the archive",
+                    file.full_path.to_label()
+                ));
+            }
+        }
+    }
+
+    let graph = packaged.package_graph();
+    for cycle in graph.cycles() {
+        let names: Vec<_> = cycle
+            .iter()
+            .map(|package| graph.name(*package).to_label())
+            .collect();
+        problems.push(format!("Packages depend on each other: {}", names.join(" -> ")));
+    }
+
+    for problem in problems.iter() {
+        println!("{}", problem);
+    }
+
+    if problems.is_empty() {
+        println!("No problems found");
+        Ok(())
+    } else {
+        Err(format!("Found {} problems", problems.len()).into())
+    }
+}
+
+fn dump_tables(path: &Path, output: &Path) -> CliResult {
+    let archive = open(path)?;
+    std::fs::create_dir_all(output)?;
+    archive.to_directory(output)?;
+    Ok(())
+}
+
+fn repack(path: &Path, output: &Path) -> CliResult {
+    let archive = open(path)?;
+    let mut writer = BufWriter::new(File::create(output)?);
+
+    let (non_user, user) = archive.write_tables(&mut writer)?;
+    writer.flush()?;
+
+    println!("filesystem tables at {:#x}", non_user);
+    println!("search tables at     {:#x}", user);
+    Ok(())
+}
+
+fn main() -> CliResult {
+    let cli = Cli::parse();
+
+    if let Some(labels) = &cli.labels {
+        snarc::try_load_labels(labels)
+            .map_err(|error| format!("The labels could not be read: {}", error))?;
+    }
+
+    match cli.command {
+        Command::Info { archive } => info(&archive),
+        Command::Ls {
+            archive,
+            folder,
+            recursive,
+        } => ls(&archive, &folder, recursive),
+        Command::Tree {
+            archive,
+            folder,
+            depth,
+        } => tree(&archive, &folder, depth),
+        Command::Cat {
+            archive,
+            file,
+            locale,
+        } => cat(&archive, &file, locale.as_deref()),
+        Command::Extract {
+            archive,
+            output,
+            pattern,
+            locale,
+        } => extract(&archive, &output, &pattern, locale.as_deref()),
+        Command::Stats { archive } => stats(&archive),
+        Command::Diff {
+            archive,
+            other,
+            data,
+        } => diff(&archive, &other, data),
+        Command::Validate { archive } => validate(&archive),
+        Command::DumpTables { archive, output } => dump_tables(&archive, &output),
+        Command::Repack { archive, output } => repack(&archive, &output),
+    }
+}
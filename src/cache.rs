@@ -0,0 +1,439 @@
+//! An on-disk cache of the parsed archive tables
+//!
+//! Opening an archive requires decompressing both table sections, parsing every
+//! table and rebuilding every hash lookup, which dominates the time it takes to open
+//! an archive. The [`TableCache`] stores the decompressed sections and the prebuilt
+//! lookups on disk, and keeps every archive it has opened parsed in memory. Both are
+//! keyed by the size, modification time and header of the archive file, so that
+//! reopening an unchanged archive skips that work without reading its tables.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    time::UNIX_EPOCH,
+};
+
+use binrw::{binrw, BinRead, BinWrite};
+use hash40::Hash40;
+
+use crate::{
+    archive::{Archive, ArchiveHeader, ArchiveOptions, CompressedTables},
+    engines::table::TableIndex,
+};
+
+/// The version of the cache file format, bumped whenever the layout changes
+/// so that old cache files are rebuilt instead of misread
+const FORMAT_VERSION: u32 = 2;
+
+/// A directory of cached archive tables
+///
+/// Each archive gets a single cache file, named after the path it was opened from.
+/// When the archive file changes, the cache file no longer matches and is rebuilt the
+/// next time the archive is opened.
+///
+/// The cache also keeps a parsed copy of every archive that it has opened, so opening
+/// the same archive again through the same cache only clones the tables.
+pub struct TableCache {
+    directory: PathBuf,
+    parsed: Mutex<HashMap<PathBuf, (u64, Archive)>>,
+}
+
+impl TableCache {
+    /// Creates a cache which stores its files in the specified directory
+    ///
+    /// ### Arguments
+    /// * `directory` - The directory to store the cache files in, created if it doesn't exist
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            parsed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Gets the directory which the cache files are stored in
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Drops the parsed copies of every archive which this cache has opened
+    ///
+    /// The cache files on disk are kept.
+    pub fn clear_parsed(&self) {
+        self.parsed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Gets the path of the cache file for an archive
+    fn entry_path(&self, archive: &Path) -> PathBuf {
+        let mut hash = FNV_OFFSET_BASIS;
+        fnv1a(&mut hash, archive.to_string_lossy().as_bytes());
+        self.directory.join(format!("{:016x}.tables", hash))
+    }
+
+    /// Gets a clone of the parsed archive, if it was parsed from the same archive file
+    fn load_parsed(&self, archive: &Path, key: u64) -> Option<Archive> {
+        let parsed = self.parsed.lock().unwrap_or_else(PoisonError::into_inner);
+        match parsed.get(archive) {
+            Some((parsed_key, parsed)) if *parsed_key == key => Some(parsed.clone()),
+            _ => None,
+        }
+    }
+
+    /// Keeps a parsed copy of an archive, replacing any older copy
+    fn store_parsed(&self, archive: &Path, key: u64, parsed: &Archive) {
+        self.parsed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(archive.to_path_buf(), (key, parsed.clone()));
+    }
+
+    /// Loads the cache entry for an archive
+    ///
+    /// ### Returns
+    /// * `Some(_)` - The cache entry, if it exists and was made from the same archive file
+    /// * `None` - The entry is missing, stale, or could not be read
+    fn load(&self, archive: &Path, key: u64) -> Option<CacheEntry> {
+        let file = std::fs::File::open(self.entry_path(archive)).ok()?;
+        let entry = CacheEntry::read(&mut BufReader::new(file)).ok()?;
+        (entry.format_version == FORMAT_VERSION && entry.key == key).then_some(entry)
+    }
+
+    /// Stores the cache entry for an archive, replacing any existing entry
+    fn store(&self, archive: &Path, entry: &CacheEntry) -> binrw::BinResult<()> {
+        std::fs::create_dir_all(&self.directory)?;
+
+        // Write to a temporary file first so that a concurrent reader never sees a partial entry
+        let path = self.entry_path(archive);
+        let temp = path.with_extension("tables.tmp");
+        let mut writer = BufWriter::new(std::fs::File::create(&temp)?);
+        entry.write_to(&mut writer)?;
+        writer.into_inner().map_err(io::Error::from)?;
+        std::fs::rename(temp, path)?;
+        Ok(())
+    }
+}
+
+pub(crate) const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+/// Hashes bytes into an existing FNV-1a hash
+pub(crate) fn fnv1a(hash: &mut u64, bytes: &[u8]) {
+    for byte in bytes {
+        *hash ^= *byte as u64;
+        *hash = hash.wrapping_mul(FNV_PRIME);
+    }
+}
+
+/// Computes the key of an archive file from its size, modification time and header
+///
+/// This only needs the file's metadata and the header, which has already been read,
+/// so the table sections never have to be read for an archive which is in the cache.
+fn cache_key(metadata: &std::fs::Metadata, header: &ArchiveHeader) -> u64 {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_nanos());
+
+    let mut key = FNV_OFFSET_BASIS;
+    fnv1a(&mut key, &metadata.len().to_le_bytes());
+    fnv1a(&mut key, &modified.to_le_bytes());
+    fnv1a(&mut key, &header.file_data_start.to_le_bytes());
+    fnv1a(&mut key, &header.non_user_tables_start.to_le_bytes());
+    fnv1a(&mut key, &header.user_tables_start.to_le_bytes());
+    key
+}
+
+/// A hash lookup, stored as `(hash, index)` pairs
+#[binrw]
+#[brw(little)]
+struct CachedLookup {
+    #[bw(calc = entries.len() as u32)]
+    count: u32,
+
+    #[br(count = count)]
+    entries: Vec<(u64, u32)>,
+}
+
+impl CachedLookup {
+    fn new<'a, T: 'a>(lookup: impl Iterator<Item = (&'a Hash40, &'a TableIndex<T>)>) -> Self {
+        Self {
+            entries: lookup
+                .map(|(hash, index)| (hash.0, index.index() as u32))
+                .collect(),
+        }
+    }
+
+    /// Iterates over the lookup, if every index is within a table of `len` entries
+    ///
+    /// ### Returns
+    /// * `Some(_)` - The hash and index of every entry
+    /// * `None` - An index is out of bounds, so the cache entry is corrupt
+    fn checked_iter<T>(
+        &self,
+        len: usize,
+    ) -> Option<impl Iterator<Item = (Hash40, TableIndex<T>)> + '_> {
+        if self.entries.iter().any(|(_, index)| *index as usize >= len) {
+            return None;
+        }
+
+        Some(
+            self.entries
+                .iter()
+                .map(|(hash, index)| (Hash40(*hash), TableIndex::new(*index as usize))),
+        )
+    }
+}
+
+/// A single archive's cached tables
+#[binrw]
+#[brw(little, magic = b"SNARCTBL")]
+struct CacheEntry {
+    format_version: u32,
+    key: u64,
+
+    #[bw(calc = non_user.len() as u64)]
+    non_user_len: u64,
+
+    #[br(count = non_user_len)]
+    non_user: Vec<u8>,
+
+    #[bw(calc = user.len() as u64)]
+    user_len: u64,
+
+    #[br(count = user_len)]
+    user: Vec<u8>,
+
+    package_lookup: CachedLookup,
+    file_lookup: CachedLookup,
+    stream_path_lookup: CachedLookup,
+    search_folder_lookup: CachedLookup,
+    search_path_lookup: CachedLookup,
+}
+
+impl Archive {
+    /// Opens an archive, using the cache to skip decompressing, parsing and indexing the tables
+    ///
+    /// ### Arguments
+    /// * `path` - The path to the archive
+    /// * `cache` - The cache to load the tables from
+    ///
+    /// ### Returns
+    /// The opened archive, or an error if the archive could not be read
+    ///
+    /// ### Notes
+    /// * Only the archive's header and file metadata are read to check the cache. If the
+    ///   archive has been opened through `cache` before, its tables are cloned from memory.
+    ///   Otherwise they are loaded from the cache file, and when that doesn't match, the
+    ///   archive is opened normally and the cache file is replaced.
+    /// * The cache is best-effort. A cache file which can't be parsed or refers outside of
+    ///   its tables is treated as a miss, and failing to write the cache file is ignored.
+    pub fn open_cached(
+        path: impl AsRef<std::path::Path>,
+        cache: &TableCache,
+    ) -> binrw::BinResult<Self> {
+        Self::open_cached_with(path, cache, ArchiveOptions::default())
+    }
+
+    /// Opens an archive which uses its own compression options, using the cache to skip
+    /// decompressing, parsing and indexing the tables
+    ///
+    /// See [`open_cached`](Self::open_cached) and [`open_with`](Self::open_with)
+    pub fn open_cached_with(
+        path: impl AsRef<std::path::Path>,
+        cache: &TableCache,
+        archive_options: ArchiveOptions,
+    ) -> binrw::BinResult<Self> {
+        let path = path.as_ref();
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+        let file = std::fs::File::open(&path)?;
+        let metadata = file.metadata()?;
+        let mut reader = BufReader::with_capacity(0x0010_0000, file);
+        let options = binrw::ReadOptions::new(binrw::Endian::Little);
+
+        let header = ArchiveHeader::read_options(&mut reader, &options, ())?;
+        let key = cache_key(&metadata, &header);
+
+        if let Some(mut archive) = cache.load_parsed(&path, key) {
+            archive.options = archive_options;
+            return Ok(archive);
+        }
+
+        let cached = cache.load(&path, key).and_then(|entry| {
+            Self::from_cache_entry(&header, entry, &options, archive_options.clone())
+        });
+
+        if let Some(archive) = cached {
+            cache.store_parsed(&path, key, &archive);
+            return Ok(archive);
+        }
+
+        let tables = CompressedTables::read(&mut reader, &options, &header)?;
+        let decompressor = archive_options.decompressor.as_ref();
+        let non_user = tables
+            .non_user_header
+            .decompress(&tables.non_user, decompressor)?;
+        let user = tables.user_header.decompress(&tables.user, decompressor)?;
+
+        let mut archive = Self::from_decompressed(
+            &header,
+            non_user.clone(),
+            user.clone(),
+            &options,
+            archive_options,
+        )?;
+        archive.build_lookups();
+
+        let entry = CacheEntry {
+            format_version: FORMAT_VERSION,
+            key,
+            non_user,
+            user,
+            package_lookup: CachedLookup::new(archive.packaged_fs.package_lookup.iter()),
+            file_lookup: CachedLookup::new(archive.packaged_fs.file_lookup.iter()),
+            stream_path_lookup: CachedLookup::new(archive.stream_fs.path_lookup.iter()),
+            search_folder_lookup: CachedLookup::new(archive.search_fs.folder_lookup.iter()),
+            search_path_lookup: CachedLookup::new(archive.search_fs.path_lookup.iter()),
+        };
+
+        // The archive itself was read fine, so a cache directory which can't be written
+        // only costs the next open its speed
+        let _ = cache.store(&path, &entry);
+        cache.store_parsed(&path, key, &archive);
+        Ok(archive)
+    }
+
+    /// Builds an archive from a cache entry, checking every cached lookup against its table
+    ///
+    /// ### Returns
+    /// * `Some(_)` - The archive, with its lookups filled in from the entry
+    /// * `None` - The tables could not be parsed, or a lookup refers outside of its table
+    fn from_cache_entry(
+        header: &ArchiveHeader,
+        entry: CacheEntry,
+        options: &binrw::ReadOptions,
+        archive_options: ArchiveOptions,
+    ) -> Option<Self> {
+        let mut archive =
+            Self::from_decompressed(header, entry.non_user, entry.user, options, archive_options)
+                .ok()?;
+
+        let packaged = &mut archive.packaged_fs;
+        packaged.package_lookup = entry
+            .package_lookup
+            .checked_iter(packaged.packages.len())?
+            .collect();
+        for (hash, index) in entry.file_lookup.checked_iter(packaged.paths.len())? {
+            packaged.file_lookup.insert(hash, index);
+        }
+
+        let stream = &mut archive.stream_fs;
+        stream.path_lookup = entry
+            .stream_path_lookup
+            .checked_iter(stream.paths.len())?
+            .collect();
+
+        let search = &mut archive.search_fs;
+        search.folder_lookup = entry
+            .search_folder_lookup
+            .checked_iter(search.folders.len())?
+            .collect();
+        search.path_lookup = entry
+            .search_path_lookup
+            .checked_iter(search.paths.len())?
+            .collect();
+
+        Some(archive)
+    }
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::archive::tests::{build_archive, write_archive};
+
+    const MODEL: &str = "fighter/mario/model/body/c00/model.numdlb";
+
+    /// Writes the test archive into a new directory, returning the directory and the archive
+    fn archive_file(name: &str) -> (PathBuf, PathBuf) {
+        let directory = std::env::temp_dir().join(format!("snarc-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let path = directory.join("data.arc");
+        std::fs::write(&path, write_archive(build_archive())).unwrap();
+        (directory, path.canonicalize().unwrap())
+    }
+
+    #[test]
+    fn reopened_archives_hit_the_cache() {
+        let (directory, path) = archive_file("cache-hit");
+        let cache = TableCache::new(directory.join("cache"));
+
+        let archive = Archive::open_cached(&path, &cache).unwrap();
+        assert!(archive.packaged_fs.has_file(MODEL));
+        assert!(cache.entry_path(&path).exists());
+        assert!(cache.load_parsed(&path, u64::MAX).is_none());
+
+        let reopened = Archive::open_cached(&path, &cache).unwrap();
+        assert!(reopened.packaged_fs.has_file(MODEL));
+
+        cache.clear_parsed();
+        let from_disk = Archive::open_cached(&path, &cache).unwrap();
+        assert!(from_disk.packaged_fs.has_file(MODEL));
+        assert!(from_disk
+            .packaged_fs
+            .get_package("fighter/mario/c00")
+            .is_some());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn corrupt_entries_are_a_miss() {
+        let (directory, path) = archive_file("cache-corrupt");
+        let cache = TableCache::new(directory.join("cache"));
+        Archive::open_cached(&path, &cache).unwrap();
+        cache.clear_parsed();
+
+        // Point a cached lookup past the end of the package table
+        let entry_path = cache.entry_path(&path);
+        let mut entry = CacheEntry::read(&mut std::fs::File::open(&entry_path).unwrap()).unwrap();
+        entry.package_lookup.entries[0].1 = u32::MAX;
+        cache.store(&path, &entry).unwrap();
+
+        let archive = Archive::open_cached(&path, &cache).unwrap();
+        assert!(archive.packaged_fs.has_file(MODEL));
+        assert!(archive
+            .packaged_fs
+            .get_package("fighter/mario/c00")
+            .is_some());
+
+        // The miss rewrote the entry
+        let entry = CacheEntry::read(&mut std::fs::File::open(&entry_path).unwrap()).unwrap();
+        assert!(entry.package_lookup.entries[0].1 != u32::MAX);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn unwritable_caches_still_open() {
+        let (directory, path) = archive_file("cache-unwritable");
+
+        // A file where the cache directory should be
+        let cache_path = directory.join("cache");
+        std::fs::write(&cache_path, b"").unwrap();
+        let cache = TableCache::new(cache_path);
+
+        let archive = Archive::open_cached(&path, &cache).unwrap();
+        assert!(archive.packaged_fs.has_file(MODEL));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,644 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, Read, Seek, SeekFrom, Write},
+};
+
+use hash40::Hash40;
+
+use crate::{
+    archive::Archive,
+    cache::{fnv1a, FNV_OFFSET_BASIS},
+    engines::packaged::{types::Package, PackagedEngine},
+    json::JsonWriter,
+};
+
+/// The filesystem of the archive that a changed file belongs to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Filesystem {
+    Packaged,
+    Stream,
+    Search,
+}
+
+impl Filesystem {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Packaged => "packaged",
+            Self::Stream => "stream",
+            Self::Search => "search",
+        }
+    }
+}
+
+/// How a file or package differs between two archives
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChangeKind {
+    /// Only the other archive has it
+    Added,
+
+    /// Only this archive has it
+    Removed,
+
+    /// Both archives have it, but it is different
+    Modified,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Modified => "modified",
+        }
+    }
+}
+
+/// What changed about a file that exists in both archives
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FileModification {
+    /// The compressed or decompressed size of the file data, or the number of
+    /// localized/regional variants, changed
+    pub size: bool,
+
+    /// The compression flags of the file data changed
+    pub compression: bool,
+
+    /// The file data changed.
+    ///
+    /// This is only checked by [`Archive::diff_with_data`], and only when the size and
+    /// compression flags are the same.
+    pub data: bool,
+}
+
+impl FileModification {
+    fn is_modified(&self) -> bool {
+        self.size || self.compression || self.data
+    }
+}
+
+/// A file which differs between two archives
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FileChange {
+    pub filesystem: Filesystem,
+
+    /// The full path of the file
+    pub path: Hash40,
+
+    pub kind: ChangeKind,
+
+    /// What changed, when the kind is [`ChangeKind::Modified`]
+    pub modification: FileModification,
+}
+
+/// A package which differs between two archives
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PackageChange {
+    /// The full path of the package
+    pub package: Hash40,
+
+    pub kind: ChangeKind,
+
+    /// The files which only the other archive's package contains
+    pub added_files: Vec<Hash40>,
+
+    /// The files which only this archive's package contains
+    pub removed_files: Vec<Hash40>,
+}
+
+/// The differences between two archives
+///
+/// Created by [`Archive::diff`] and [`Archive::diff_with_data`]. Changes are sorted by
+/// filesystem and then by hash.
+#[derive(Debug, Default, Clone)]
+pub struct ArchiveDiff {
+    pub files: Vec<FileChange>,
+    pub packages: Vec<PackageChange>,
+}
+
+impl ArchiveDiff {
+    /// Checks if there are no differences
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.packages.is_empty()
+    }
+
+    /// Writes the differences as JSON
+    ///
+    /// Paths are written with their labels from the [`Hash40`] label map
+    pub fn write_json<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        fn hash_list(json: &mut JsonWriter, hashes: &[Hash40]) {
+            json.begin_array();
+            for hash in hashes.iter() {
+                json.string(&hash.to_label());
+            }
+            json.end_array();
+        }
+
+        let mut json = JsonWriter::new();
+        json.begin_object();
+
+        json.key("files");
+        json.begin_array();
+        for file in self.files.iter() {
+            json.begin_object();
+            json.key("filesystem");
+            json.string(file.filesystem.as_str());
+            json.key("path");
+            json.string(&file.path.to_label());
+            json.key("change");
+            json.string(file.kind.as_str());
+            json.key("size");
+            json.value(file.modification.size);
+            json.key("compression");
+            json.value(file.modification.compression);
+            json.key("data");
+            json.value(file.modification.data);
+            json.end_object();
+        }
+        json.end_array();
+
+        json.key("packages");
+        json.begin_array();
+        for package in self.packages.iter() {
+            json.begin_object();
+            json.key("package");
+            json.string(&package.package.to_label());
+            json.key("change");
+            json.string(package.kind.as_str());
+            json.key("added_files");
+            hash_list(&mut json, &package.added_files);
+            json.key("removed_files");
+            hash_list(&mut json, &package.removed_files);
+            json.end_object();
+        }
+        json.end_array();
+
+        json.end_object();
+        json.finish(writer)
+    }
+}
+
+/// The location and format of one variant of a file's data
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct DataVariant {
+    /// The absolute offset of the data in the archive
+    offset: usize,
+    compressed_size: usize,
+    decompressed_size: usize,
+    is_compressed: bool,
+    is_standard_zstd: bool,
+}
+
+/// Gets the data of every localized/regional variant of a packaged file
+fn packaged_variants(archive: &Archive, hash: Hash40) -> Option<Vec<Option<DataVariant>>> {
+    let engine = &archive.packaged_fs;
+    let path = engine.get_file(hash)?;
+    let info = &engine.infos[engine.links[path.link()].info()];
+
+    let variants = info
+        .descriptors
+        .iter()
+        .enumerate()
+        .map(|(position, descriptor)| {
+            let (descriptor, metadata) = engine.descriptor_data(descriptor, position)?;
+            let group = &engine.groups[engine.descriptors[descriptor].group()];
+            let metadata = &engine.metadatas[metadata];
+            Some(DataVariant {
+                offset: archive.file_section_offset + group.archive_offset + metadata.group_offset,
+                compressed_size: metadata.compressed_size,
+                decompressed_size: metadata.decompressed_size,
+                is_compressed: metadata.is_compressed,
+                is_standard_zstd: metadata.is_standard_zstd,
+            })
+        })
+        .collect();
+
+    Some(variants)
+}
+
+/// Gets the data of every localized/regional variant of a stream file
+fn stream_variants(archive: &Archive, hash: Hash40) -> Option<Vec<Option<DataVariant>>> {
+    let engine = &archive.stream_fs;
+    let path = engine.get_path(hash)?;
+
+    let variants = path
+        .links
+        .iter()
+        .map(|link| {
+            let metadata = &engine.metadatas[engine.links[link].metadata()];
+            Some(DataVariant {
+                offset: metadata.offset,
+                compressed_size: metadata.size,
+                decompressed_size: metadata.size,
+                is_compressed: false,
+                is_standard_zstd: false,
+            })
+        })
+        .collect();
+
+    Some(variants)
+}
+
+/// Gets the full path of every file that a package loads through its infos
+fn package_files(engine: &PackagedEngine, package: &Package) -> BTreeSet<Hash40> {
+    package
+        .infos
+        .iter()
+        .map(|info| engine.paths[engine.infos[info].path()].full_path)
+        .collect()
+}
+
+/// Hashes a range of data from a reader
+fn hash_data<R: Read + Seek>(reader: &mut R, offset: usize, size: usize) -> io::Result<u64> {
+    reader.seek(SeekFrom::Start(offset as u64))?;
+
+    let mut hash = FNV_OFFSET_BASIS;
+    let mut buffer = vec![0u8; 0x10000];
+    let mut remaining = size;
+    while remaining != 0 {
+        let count = remaining.min(buffer.len());
+        reader.read_exact(&mut buffer[..count])?;
+        fnv1a(&mut hash, &buffer[..count]);
+        remaining -= count;
+    }
+
+    Ok(hash)
+}
+
+/// Compares the data of the same file in two archives
+type DataComparer<'a> = dyn FnMut(&DataVariant, &DataVariant) -> io::Result<bool> + 'a;
+
+struct Differ<'a, 'b> {
+    archive: &'a Archive,
+    other: &'a Archive,
+    data_differs: &'b mut DataComparer<'b>,
+    diff: ArchiveDiff,
+}
+
+impl<'a, 'b> Differ<'a, 'b> {
+    fn compare_variants(
+        &mut self,
+        variants: &[Option<DataVariant>],
+        other_variants: &[Option<DataVariant>],
+    ) -> io::Result<FileModification> {
+        let mut modification = FileModification::default();
+
+        if variants.len() != other_variants.len() {
+            modification.size = true;
+            return Ok(modification);
+        }
+
+        for (variant, other_variant) in variants.iter().zip(other_variants.iter()) {
+            let (variant, other_variant) = match (variant, other_variant) {
+                (Some(variant), Some(other_variant)) => (variant, other_variant),
+                (None, None) => continue,
+                _ => {
+                    modification.size = true;
+                    continue;
+                }
+            };
+
+            let size_changed = variant.compressed_size != other_variant.compressed_size
+                || variant.decompressed_size != other_variant.decompressed_size;
+            let compression_changed = variant.is_compressed != other_variant.is_compressed
+                || variant.is_standard_zstd != other_variant.is_standard_zstd;
+
+            modification.size |= size_changed;
+            modification.compression |= compression_changed;
+
+            if !size_changed && !compression_changed && !modification.data {
+                modification.data = (self.data_differs)(variant, other_variant)?;
+            }
+        }
+
+        Ok(modification)
+    }
+
+    fn diff_files(
+        &mut self,
+        filesystem: Filesystem,
+        hashes: impl Iterator<Item = Hash40>,
+        other_hashes: impl Iterator<Item = Hash40>,
+        variants: fn(&Archive, Hash40) -> Option<Vec<Option<DataVariant>>>,
+    ) -> io::Result<()> {
+        let mut changes = vec![];
+
+        for hash in hashes {
+            let change = match (variants(self.archive, hash), variants(self.other, hash)) {
+                (Some(variants), Some(other_variants)) => {
+                    let modification = self.compare_variants(&variants, &other_variants)?;
+                    if !modification.is_modified() {
+                        continue;
+                    }
+                    (ChangeKind::Modified, modification)
+                }
+                _ => (ChangeKind::Removed, FileModification::default()),
+            };
+
+            changes.push(FileChange {
+                filesystem,
+                path: hash,
+                kind: change.0,
+                modification: change.1,
+            });
+        }
+
+        for hash in other_hashes {
+            if variants(self.archive, hash).is_none() {
+                changes.push(FileChange {
+                    filesystem,
+                    path: hash,
+                    kind: ChangeKind::Added,
+                    modification: FileModification::default(),
+                });
+            }
+        }
+
+        changes.sort_by_key(|change| change.path);
+        self.diff.files.extend(changes);
+        Ok(())
+    }
+
+    fn diff_search(&mut self) {
+        let search = &self.archive.search_fs;
+        let other_search = &self.other.search_fs;
+
+        let mut changes: Vec<_> = search
+            .paths
+            .iter()
+            .filter(|path| other_search.get_path(path.full_path).is_none())
+            .map(|path| (path.full_path, ChangeKind::Removed))
+            .chain(
+                other_search
+                    .paths
+                    .iter()
+                    .filter(|path| search.get_path(path.full_path).is_none())
+                    .map(|path| (path.full_path, ChangeKind::Added)),
+            )
+            .map(|(path, kind)| FileChange {
+                filesystem: Filesystem::Search,
+                path,
+                kind,
+                modification: FileModification::default(),
+            })
+            .collect();
+
+        changes.sort_by_key(|change| change.path);
+        self.diff.files.extend(changes);
+    }
+
+    fn diff_packages(&mut self) {
+        let engine = &self.archive.packaged_fs;
+        let other_engine = &self.other.packaged_fs;
+
+        let mut changes = vec![];
+
+        for package in engine.packages.iter() {
+            let files = package_files(engine, package);
+            let change = match other_engine.get_package(package.full_path) {
+                Some(other_package) => {
+                    let other_files = package_files(other_engine, other_package);
+                    let added_files: Vec<_> = other_files.difference(&files).copied().collect();
+                    let removed_files: Vec<_> = files.difference(&other_files).copied().collect();
+                    if added_files.is_empty() && removed_files.is_empty() {
+                        continue;
+                    }
+
+                    PackageChange {
+                        package: package.full_path,
+                        kind: ChangeKind::Modified,
+                        added_files,
+                        removed_files,
+                    }
+                }
+                None => PackageChange {
+                    package: package.full_path,
+                    kind: ChangeKind::Removed,
+                    added_files: vec![],
+                    removed_files: files.into_iter().collect(),
+                },
+            };
+
+            changes.push(change);
+        }
+
+        for package in other_engine.packages.iter() {
+            if !engine.has_package(package.full_path) {
+                changes.push(PackageChange {
+                    package: package.full_path,
+                    kind: ChangeKind::Added,
+                    added_files: package_files(other_engine, package).into_iter().collect(),
+                    removed_files: vec![],
+                });
+            }
+        }
+
+        changes.sort_by_key(|change| change.package);
+        self.diff.packages = changes;
+    }
+
+    fn run(mut self) -> io::Result<ArchiveDiff> {
+        let (archive, other) = (self.archive, self.other);
+
+        self.diff_files(
+            Filesystem::Packaged,
+            archive.packaged_fs.paths.iter().map(|path| path.full_path),
+            other.packaged_fs.paths.iter().map(|path| path.full_path),
+            packaged_variants,
+        )?;
+
+        self.diff_files(
+            Filesystem::Stream,
+            archive.stream_fs.paths.iter().map(|path| path.full_path),
+            other.stream_fs.paths.iter().map(|path| path.full_path),
+            stream_variants,
+        )?;
+
+        self.diff_search();
+        self.diff_packages();
+
+        Ok(self.diff)
+    }
+}
+
+impl Archive {
+    /// Finds the differences between this archive and another
+    ///
+    /// Files are compared by the size and compression flags of their data. Use
+    /// [`diff_with_data`](Self::diff_with_data) to also compare the data itself.
+    ///
+    /// ### Arguments
+    /// * `other` - The archive to compare against, such as the one from a newer game update
+    ///
+    /// ### Returns
+    /// The changes needed to go from this archive to `other`
+    ///
+    /// ### Panicking
+    /// Panics if either archive has not been [resolved](Self::resolve)
+    ///
+    /// ### Notes
+    /// * Search paths can only be added or removed, as they do not have any data
+    /// * Package changes are based on the files that each package's infos refer to
+    pub fn diff(&self, other: &Archive) -> ArchiveDiff {
+        let mut data_differs = |_: &DataVariant, _: &DataVariant| Ok(false);
+        Differ {
+            archive: self,
+            other,
+            data_differs: &mut data_differs,
+            diff: ArchiveDiff::default(),
+        }
+        .run()
+        .expect("Comparing without data should not fail")
+    }
+
+    /// Finds the differences between this archive and another, including file data
+    ///
+    /// This is the same as [`diff`](Self::diff), but files whose sizes and compression flags are
+    /// unchanged also have their data hashed and compared.
+    ///
+    /// ### Arguments
+    /// * `reader` - The reader for the archive file that this was read from
+    /// * `other` - The archive to compare against
+    /// * `other_reader` - The reader for the archive file that `other` was read from
+    ///
+    /// ### Panicking
+    /// Panics if either archive has not been [resolved](Self::resolve)
+    pub fn diff_with_data<A: Read + Seek, B: Read + Seek>(
+        &self,
+        reader: &mut A,
+        other: &Archive,
+        other_reader: &mut B,
+    ) -> io::Result<ArchiveDiff> {
+        let mut data_differs = |variant: &DataVariant, other_variant: &DataVariant| {
+            let hash = hash_data(reader, variant.offset, variant.compressed_size)?;
+            let other_hash = hash_data(
+                other_reader,
+                other_variant.offset,
+                other_variant.compressed_size,
+            )?;
+            Ok(hash != other_hash)
+        };
+
+        Differ {
+            archive: self,
+            other,
+            data_differs: &mut data_differs,
+            diff: ArchiveDiff::default(),
+        }
+        .run()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{archive::tests::build_archive, engines::VariantKind, Hashable};
+
+    const MODEL: &str = "fighter/mario/model/body/c00/model.numdlb";
+    const MOTION: &str = "fighter/mario/motion/body/c00/a00wait1.nuanmb";
+    const TEXTURE: &str = "fighter/mario/model/body/c00/def_mario_001_col.nutexb";
+
+    /// Places a file's data in its group and sets its sizes
+    fn set_data(archive: &mut Archive, file: &str, offset: usize, size: usize) {
+        let engine = &mut archive.packaged_fs;
+        let info = engine.links[engine.get_file(file).unwrap().link()].info();
+        let descriptor = engine.infos[info].descriptors.get(0);
+        let metadata = engine.descriptors[descriptor].metadata();
+
+        let metadata = &mut engine.metadatas[metadata];
+        metadata.group_offset = offset;
+        metadata.compressed_size = size;
+        metadata.decompressed_size = size;
+    }
+
+    fn build_with_data() -> Archive {
+        let mut archive = build_archive();
+        set_data(&mut archive, MODEL, 0, 4);
+        set_data(&mut archive, MOTION, 4, 4);
+        archive
+    }
+
+    #[test]
+    fn identical_archives_have_no_changes() {
+        let archive = build_with_data();
+        assert!(archive.diff(&build_with_data()).is_empty());
+    }
+
+    #[test]
+    fn changes_are_found_in_every_filesystem() {
+        let archive = build_with_data();
+
+        let mut other = build_with_data();
+        set_data(&mut other, MODEL, 0, 8);
+        other.add_file(TEXTURE, "fighter/mario/c00");
+        other
+            .packaged_fs
+            .add_package("fighter/luigi/c00", "fighter", VariantKind::None);
+
+        let diff = archive.diff(&other);
+        let files: Vec<_> = diff
+            .files
+            .iter()
+            .map(|change| (change.filesystem, change.path, change.kind))
+            .collect();
+        let mut expected = vec![
+            (Filesystem::Packaged, MODEL.to_hash(), ChangeKind::Modified),
+            (Filesystem::Packaged, TEXTURE.to_hash(), ChangeKind::Added),
+            (Filesystem::Search, TEXTURE.to_hash(), ChangeKind::Added),
+        ];
+        expected.sort_by_key(|(filesystem, path, _)| (*filesystem, *path));
+        assert_eq!(files, expected);
+
+        let model = diff
+            .files
+            .iter()
+            .find(|change| change.path == MODEL.to_hash())
+            .unwrap();
+        assert!(model.modification.size);
+        assert!(!model.modification.data);
+
+        let packages: Vec<_> = diff
+            .packages
+            .iter()
+            .map(|change| (change.package, change.kind, change.added_files.clone()))
+            .collect();
+        let mut expected = vec![
+            (
+                "fighter/mario/c00".to_hash(),
+                ChangeKind::Modified,
+                vec![TEXTURE.to_hash()],
+            ),
+            ("fighter/luigi/c00".to_hash(), ChangeKind::Added, vec![]),
+        ];
+        expected.sort_by_key(|(package, ..)| *package);
+        assert_eq!(packages, expected);
+    }
+
+    #[test]
+    fn data_is_compared_when_sizes_match() {
+        let archive = build_with_data();
+        let other = build_with_data();
+
+        let diff = archive
+            .diff_with_data(
+                &mut Cursor::new(b"AAAABBBB".to_vec()),
+                &other,
+                &mut Cursor::new(b"AAAACCCC".to_vec()),
+            )
+            .unwrap();
+
+        assert_eq!(diff.files.len(), 1);
+        assert_eq!(diff.files[0].path, MOTION.to_hash());
+        assert_eq!(diff.files[0].kind, ChangeKind::Modified);
+        assert_eq!(
+            diff.files[0].modification,
+            FileModification {
+                size: false,
+                compression: false,
+                data: true
+            }
+        );
+        assert!(archive.diff(&other).is_empty());
+    }
+}
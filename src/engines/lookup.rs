@@ -0,0 +1,167 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasherDefault, Hasher},
+};
+
+use hash40::Hash40;
+
+/// A hasher for [`Hash40`] keys
+///
+/// Hash40s are already CRC32 hashes, so there is no need to hash them again.
+/// The value is only multiplied by a large odd constant to spread the length
+/// byte across the whole hash.
+#[derive(Default)]
+pub struct Hash40Hasher(u64);
+
+impl Hasher for Hash40Hasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0.rotate_left(8) ^ *byte as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.0 = value.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    }
+}
+
+/// A hash map from [`Hash40`] to a value, for fast lookups by hash
+///
+/// Iteration through [`iter`](Self::iter) is in an arbitrary order. When the entries have to be
+/// written in order, such as for the lookup tables in the archive, use [`iter_sorted`](Self::iter_sorted).
+#[derive(Clone)]
+pub struct HashLookup<V>(HashMap<Hash40, V, BuildHasherDefault<Hash40Hasher>>);
+
+impl<V> HashLookup<V> {
+    pub fn new() -> Self {
+        Self(HashMap::default())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self(HashMap::with_capacity_and_hasher(
+            capacity,
+            Default::default(),
+        ))
+    }
+
+    pub fn get(&self, hash: &Hash40) -> Option<&V> {
+        self.0.get(hash)
+    }
+
+    pub fn get_mut(&mut self, hash: &Hash40) -> Option<&mut V> {
+        self.0.get_mut(hash)
+    }
+
+    pub fn insert(&mut self, hash: Hash40, value: V) -> Option<V> {
+        self.0.insert(hash, value)
+    }
+
+    pub fn remove(&mut self, hash: &Hash40) -> Option<V> {
+        self.0.remove(hash)
+    }
+
+    pub fn contains_key(&self, hash: &Hash40) -> bool {
+        self.0.contains_key(hash)
+    }
+
+    pub fn retain(&mut self, f: impl FnMut(&Hash40, &mut V) -> bool) {
+        self.0.retain(f)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterates over the entries in an arbitrary order
+    pub fn iter(&self) -> impl Iterator<Item = (&Hash40, &V)> {
+        self.0.iter()
+    }
+
+    /// Iterates over the entries in order of their hashes
+    ///
+    /// ### Notes
+    /// This has to sort the entries first, so prefer [`iter`](Self::iter) when the order doesn't matter
+    pub fn iter_sorted(&self) -> impl Iterator<Item = (&Hash40, &V)> {
+        let mut entries: Vec<_> = self.0.iter().collect();
+        entries.sort_unstable_by_key(|(hash, _)| **hash);
+        entries.into_iter()
+    }
+}
+
+impl<V> Default for HashLookup<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> FromIterator<(Hash40, V)> for HashLookup<V> {
+    fn from_iter<T: IntoIterator<Item = (Hash40, V)>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<V> IntoIterator for HashLookup<V> {
+    type Item = (Hash40, V);
+    type IntoIter = std::collections::hash_map::IntoIter<Hash40, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::Hash;
+
+    use super::*;
+
+    fn hash_of(hash: Hash40) -> u64 {
+        let mut hasher = Hash40Hasher::default();
+        hash.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn entries_are_found_by_hash() {
+        let mut lookup: HashLookup<usize> = ["ui/message", "fighter/mario/c00", "sound/bgm"]
+            .into_iter()
+            .enumerate()
+            .map(|(index, path)| (Hash40::new(path), index))
+            .collect();
+
+        assert_eq!(lookup.len(), 3);
+        assert_eq!(lookup.get(&Hash40::new("fighter/mario/c00")), Some(&1));
+        assert_eq!(lookup.insert(Hash40::new("sound/bgm"), 3), Some(2));
+        assert_eq!(lookup.remove(&Hash40::new("ui/message")), Some(0));
+        assert!(!lookup.contains_key(&Hash40::new("ui/message")));
+        assert_eq!(lookup.get(&Hash40::new("fighter/mario/c01")), None);
+    }
+
+    #[test]
+    fn sorted_iteration_is_in_hash_order() {
+        let lookup: HashLookup<()> = (0..64)
+            .map(|index| (Hash40::new(&format!("fighter/mario/c{:02}", index)), ()))
+            .collect();
+
+        let hashes: Vec<_> = lookup.iter_sorted().map(|(hash, _)| *hash).collect();
+        assert_eq!(hashes.len(), 64);
+        assert!(hashes.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn length_byte_changes_the_hash() {
+        let crc = Hash40::new("a").0 & 0xFFFF_FFFF;
+        assert_ne!(
+            hash_of(Hash40(crc | 1 << 32)),
+            hash_of(Hash40(crc | 2 << 32))
+        );
+    }
+}
@@ -2,10 +2,16 @@ use std::{convert::Infallible, fmt, path::Path, str::FromStr};
 
 use hash40::Hash40;
 
-use crate::HASH_MASK;
+use crate::{
+    region::{LOCALE_COUNT, REGION_COUNT},
+    HASH_MASK,
+};
 
+pub mod lookup;
 pub mod table;
 
+use table::Table;
+
 pub mod packaged;
 pub mod search;
 pub mod stream;
@@ -43,7 +49,7 @@ impl fmt::Display for HashKey {
     }
 }
 
-pub(crate) fn read_table<T: BinRead>(path: &Path, item_size: usize) -> binrw::BinResult<Vec<T>>
+pub(crate) fn read_table<T: BinRead>(path: &Path, item_size: usize) -> binrw::BinResult<Table<T>>
 where
     <T as BinRead>::Args: Default,
 {
@@ -61,6 +67,7 @@ where
         &mut std::io::Cursor::new(bytes),
         VecArgs::builder().count(count).finalize(),
     )
+    .map(Table::from)
 }
 
 #[repr(i32)]
@@ -175,6 +182,7 @@ impl From<i32> for Locale {
 
 #[repr(i32)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Region {
     Invalid = -1,
     Japan = 0,
@@ -256,3 +264,41 @@ impl Region {
         }
     }
 }
+
+/// Whether something has a variant for every locale, for every region, or just one version
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VariantKind {
+    /// There is only a single version
+    #[default]
+    None,
+
+    /// There is a variant for every [`Locale`]
+    Localized,
+
+    /// There is a variant for every [`Region`]
+    Regional,
+}
+
+impl VariantKind {
+    /// The number of locales or regions which have a variant
+    pub fn variant_count(self) -> usize {
+        match self {
+            Self::None => 0,
+            Self::Localized => LOCALE_COUNT,
+            Self::Regional => REGION_COUNT,
+        }
+    }
+
+    /// Gets the name of the locale or region of a variant, as used in paths
+    ///
+    /// ### Arguments
+    /// * `variant` - The locale or region index of the variant
+    pub fn variant_name(self, variant: usize) -> &'static str {
+        match self {
+            Self::None => "",
+            Self::Localized => Locale::from(variant as i32).as_str(),
+            Self::Regional => Region::from(variant as i32).as_str(),
+        }
+    }
+}
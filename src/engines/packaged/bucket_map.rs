@@ -1,8 +1,11 @@
-use std::{collections::BTreeMap, num::NonZeroUsize};
+use std::num::NonZeroUsize;
 
 use hash40::Hash40;
 
-pub struct BucketMap<V>(Vec<BTreeMap<Hash40, V>>);
+use crate::engines::lookup::HashLookup;
+
+#[derive(Clone)]
+pub struct BucketMap<V>(Vec<HashLookup<V>>);
 
 impl<V> BucketMap<V> {
     fn bucket_for_hash(&self, hash: Hash40) -> usize {
@@ -13,7 +16,7 @@ impl<V> BucketMap<V> {
         let bucket_count = bucket_count.get();
         let mut buckets = Vec::with_capacity(bucket_count);
         for _ in 0..bucket_count {
-            buckets.push(BTreeMap::new());
+            buckets.push(HashLookup::new());
         }
         Self(buckets)
     }
@@ -41,7 +44,13 @@ impl<V> BucketMap<V> {
         self.get(hash).is_some()
     }
 
-    pub fn into_inner(self) -> Vec<BTreeMap<Hash40, V>> {
+    pub fn retain(&mut self, mut f: impl FnMut(&Hash40, &mut V) -> bool) {
+        for bucket in self.0.iter_mut() {
+            bucket.retain(|hash, value| f(hash, value));
+        }
+    }
+
+    pub fn into_inner(self) -> Vec<HashLookup<V>> {
         self.0
     }
 
@@ -53,7 +62,14 @@ impl<V> BucketMap<V> {
         self.0.iter().flat_map(|map| map.iter())
     }
 
-    pub fn buckets(&self) -> impl Iterator<Item = &BTreeMap<Hash40, V>> {
+    /// Iterates over the entries bucket by bucket, in order of their hashes within each bucket
+    ///
+    /// This is the order that the lookup is stored in the archive
+    pub fn iter_sorted(&self) -> impl Iterator<Item = (&Hash40, &V)> {
+        self.0.iter().flat_map(|map| map.iter_sorted())
+    }
+
+    pub fn buckets(&self) -> impl Iterator<Item = &HashLookup<V>> {
         self.0.iter()
     }
 
@@ -0,0 +1,205 @@
+use crate::{engines::table::TableIndex, Hashable};
+
+use super::{
+    types::{DescriptorLoadArguments, Group, Info, Package, Path},
+    PackagedEngine,
+};
+
+/// The package or group which loads a file
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FileLoader {
+    Package(TableIndex<Package>),
+    Group(TableIndex<Group>),
+}
+
+/// Why a [`FileLoader`] loads a file
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LoadReason {
+    /// One of the loader's infos refers directly to the file's path
+    Info,
+
+    /// The loader is the owner of the file's [`Link`](super::types::Link)
+    LinkOwner,
+
+    /// One of the loader's infos has an [`Unowned`](DescriptorLoadArguments::Unowned)
+    /// descriptor which links to the file
+    Unowned,
+
+    /// One of the loader's infos has a [`SharedButOwned`](DescriptorLoadArguments::SharedButOwned)
+    /// descriptor which links to the file
+    SharedButOwned,
+
+    /// One of the loader's infos has a [`PackageSkip`](DescriptorLoadArguments::PackageSkip)
+    /// descriptor which points to an info for the file
+    PackageSkip,
+}
+
+/// A single reason that a package or group loads a file
+///
+/// Returned by [`PackagedEngine::packages_loading`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FileDependent {
+    /// The package or group which loads the file
+    pub loader: FileLoader,
+
+    /// The info of the loader which causes it to load the file, if there is one.
+    ///
+    /// This is `None` for [`LoadReason::LinkOwner`], which isn't caused by an info.
+    pub info: Option<TableIndex<Info>>,
+
+    /// Why the file is loaded
+    pub reason: LoadReason,
+}
+
+impl PackagedEngine {
+    /// Gets the reason an info causes a file to be loaded, if it does
+    fn info_load_reason(
+        &self,
+        info: TableIndex<Info>,
+        path: TableIndex<Path>,
+    ) -> Option<LoadReason> {
+        let info = &self.infos[info];
+        if info.path() == path {
+            return Some(LoadReason::Info);
+        }
+
+        info.descriptors.iter().find_map(|descriptor| {
+            match &self.descriptors[descriptor].load_args {
+                DescriptorLoadArguments::Unowned { link } => {
+                    let info = self.links[link.index()].info();
+                    (self.infos[info].path() == path).then_some(LoadReason::Unowned)
+                }
+                DescriptorLoadArguments::SharedButOwned { link } => {
+                    let info = self.links[link.index()].info();
+                    (self.infos[info].path() == path).then_some(LoadReason::SharedButOwned)
+                }
+                DescriptorLoadArguments::PackageSkip { info } => {
+                    (self.infos[info.index()].path() == path).then_some(LoadReason::PackageSkip)
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Finds every package and group which loads a file
+    ///
+    /// ### Arguments
+    /// * `hash` - The hash of the file
+    ///
+    /// ### Returns
+    /// Every package and group which loads the file, along with why. A loader can appear more than
+    /// once if it loads the file for more than one reason. If the file does not exist, this is empty.
+    ///
+    /// ### Panicking
+    /// Panics if the engine has not been [resolved](Self::resolve)
+    ///
+    /// ### Notes
+    /// This checks every info in every package and group, so it is not cheap.
+    /// Cache the results if many files need to be queried.
+    pub fn packages_loading(&self, hash: impl Hashable) -> Vec<FileDependent> {
+        let path = match self.file_lookup.get(hash.to_hash()) {
+            Some(path) => *path,
+            None => return vec![],
+        };
+
+        let mut dependents = vec![];
+
+        let link = &self.links[self.paths[path].link()];
+        let owner = if link.is_owner_package() {
+            FileLoader::Package(link.package())
+        } else {
+            FileLoader::Group(link.group())
+        };
+
+        dependents.push(FileDependent {
+            loader: owner,
+            info: None,
+            reason: LoadReason::LinkOwner,
+        });
+
+        for (index, package) in self.packages.iter_indexed() {
+            for info in package.infos.iter() {
+                if let Some(reason) = self.info_load_reason(info, path) {
+                    dependents.push(FileDependent {
+                        loader: FileLoader::Package(index),
+                        info: Some(info),
+                        reason,
+                    });
+                }
+            }
+        }
+
+        for (index, group) in self.groups.iter_indexed() {
+            if !group.is_info_group() {
+                continue;
+            }
+
+            for info in group.infos().iter() {
+                if let Some(reason) = self.info_load_reason(info, path) {
+                    dependents.push(FileDependent {
+                        loader: FileLoader::Group(index),
+                        info: Some(info),
+                        reason,
+                    });
+                }
+            }
+        }
+
+        dependents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engines::packaged::tests::build_engine;
+
+    #[test]
+    fn owned_files_are_loaded_by_their_package() {
+        let engine = build_engine();
+        let package = *engine
+            .package_lookup
+            .get(&hash40::Hash40::new("fighter/mario/c00"))
+            .unwrap();
+
+        let dependents = engine.packages_loading("fighter/mario/model/body/c00/model.numdlb");
+        assert_eq!(dependents.len(), 2);
+        assert!(dependents
+            .iter()
+            .all(|dependent| dependent.loader == FileLoader::Package(package)));
+        assert_eq!(dependents[0].reason, LoadReason::LinkOwner);
+        assert_eq!(dependents[0].info, None);
+        assert_eq!(dependents[1].reason, LoadReason::Info);
+
+        assert!(engine
+            .packages_loading("fighter/luigi/model/body/c00/model.numdlb")
+            .is_empty());
+    }
+
+    #[test]
+    fn shared_files_are_loaded_by_every_package_sharing_them() {
+        let engine = build_engine();
+        let mario = *engine
+            .package_lookup
+            .get(&hash40::Hash40::new("fighter/mario/c00"))
+            .unwrap();
+        let message = *engine
+            .package_lookup
+            .get(&hash40::Hash40::new("ui/message"))
+            .unwrap();
+
+        let loaders: Vec<_> = engine
+            .packages_loading("ui/message/msg_name.msbt")
+            .into_iter()
+            .map(|dependent| (dependent.loader, dependent.reason))
+            .collect();
+        assert_eq!(
+            loaders,
+            [
+                (FileLoader::Package(message), LoadReason::LinkOwner),
+                (FileLoader::Package(mario), LoadReason::Info),
+                (FileLoader::Package(message), LoadReason::Info),
+            ]
+        );
+    }
+}
@@ -0,0 +1,428 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, Write},
+};
+
+use hash40::Hash40;
+
+use crate::{
+    engines::table::TableIndex,
+    json::{escape, JsonWriter},
+    Hashable,
+};
+
+use super::{
+    types::{DescriptorLoadArguments, Package},
+    PackagedEngine,
+};
+
+/// Why one package depends on another in a [`PackageGraph`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PackageEdgeKind {
+    /// The target is one of the source's [child packages](super::types::ChildPackage)
+    Child,
+
+    /// One of the source's groups has the target as its sub package
+    SubPackage,
+
+    /// Same as [`SubPackage`](Self::SubPackage), but the source is a sym link package
+    SymLink,
+
+    /// One of the source's infos loads a file that is owned by the target
+    SharedFile,
+}
+
+impl PackageEdgeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Child => "child",
+            Self::SubPackage => "sub_package",
+            Self::SymLink => "sym_link",
+            Self::SharedFile => "shared_file",
+        }
+    }
+}
+
+/// A dependency from one package to another
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PackageEdge {
+    pub from: TableIndex<Package>,
+    pub to: TableIndex<Package>,
+    pub kind: PackageEdgeKind,
+}
+
+/// The graph of dependencies between every package in the packaged filesystem
+///
+/// Created by [`PackagedEngine::package_graph`]. The nodes are the indices of the packages
+/// in the package table, and the edges point from a package to the packages it depends on.
+pub struct PackageGraph {
+    names: Vec<Hash40>,
+    edges: Vec<PackageEdge>,
+    outgoing: Vec<Vec<usize>>,
+    incoming: Vec<Vec<usize>>,
+}
+
+impl PackageGraph {
+    fn new(names: Vec<Hash40>, edges: BTreeSet<PackageEdge>) -> Self {
+        let edges: Vec<_> = edges.into_iter().collect();
+        let mut outgoing = vec![vec![]; names.len()];
+        let mut incoming = vec![vec![]; names.len()];
+
+        for (index, edge) in edges.iter().enumerate() {
+            outgoing[edge.from.index()].push(index);
+            incoming[edge.to.index()].push(index);
+        }
+
+        Self {
+            names,
+            edges,
+            outgoing,
+            incoming,
+        }
+    }
+
+    /// The number of packages in the graph
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Checks if the graph has no packages
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Gets the full path of a package in the graph
+    pub fn name(&self, package: TableIndex<Package>) -> Hash40 {
+        self.names[package.index()]
+    }
+
+    /// Finds a package in the graph by hash
+    pub fn find(&self, hash: impl Hashable) -> Option<TableIndex<Package>> {
+        let hash = hash.to_hash();
+        self.names
+            .iter()
+            .position(|name| *name == hash)
+            .map(TableIndex::new)
+    }
+
+    /// Gets every edge in the graph
+    pub fn edges(&self) -> &[PackageEdge] {
+        &self.edges
+    }
+
+    /// Gets the edges from a package to the packages it depends on
+    pub fn dependencies(&self, package: TableIndex<Package>) -> impl Iterator<Item = &PackageEdge> {
+        self.outgoing[package.index()]
+            .iter()
+            .map(|edge| &self.edges[*edge])
+    }
+
+    /// Gets the edges to a package from the packages that depend on it
+    pub fn dependents(&self, package: TableIndex<Package>) -> impl Iterator<Item = &PackageEdge> {
+        self.incoming[package.index()]
+            .iter()
+            .map(|edge| &self.edges[*edge])
+    }
+
+    /// Collects every package reachable from `package` by following edges in one direction
+    fn reachable(&self, package: TableIndex<Package>, forward: bool) -> Vec<TableIndex<Package>> {
+        let mut visited = vec![false; self.len()];
+        let mut stack = vec![package];
+        let mut reached = vec![];
+        visited[package.index()] = true;
+
+        while let Some(current) = stack.pop() {
+            let edges = if forward {
+                &self.outgoing[current.index()]
+            } else {
+                &self.incoming[current.index()]
+            };
+
+            for edge in edges {
+                let edge = &self.edges[*edge];
+                let next = if forward { edge.to } else { edge.from };
+                if !visited[next.index()] {
+                    visited[next.index()] = true;
+                    reached.push(next);
+                    stack.push(next);
+                }
+            }
+        }
+
+        reached
+    }
+
+    /// Gets every package which depends on `package`, directly or indirectly
+    pub fn ancestors(&self, package: TableIndex<Package>) -> Vec<TableIndex<Package>> {
+        self.reachable(package, false)
+    }
+
+    /// Gets every package which `package` depends on, directly or indirectly
+    pub fn descendants(&self, package: TableIndex<Package>) -> Vec<TableIndex<Package>> {
+        self.reachable(package, true)
+    }
+
+    /// Finds every cycle in the graph
+    ///
+    /// ### Returns
+    /// Each group of packages which all depend on each other, including single packages which
+    /// depend on themselves
+    pub fn cycles(&self) -> Vec<Vec<TableIndex<Package>>> {
+        // Iterative version of Tarjan's strongly connected components algorithm
+        const UNVISITED: usize = usize::MAX;
+
+        let count = self.len();
+        let mut order = vec![UNVISITED; count];
+        let mut low_link = vec![0; count];
+        let mut on_stack = vec![false; count];
+        let mut stack = vec![];
+        let mut next_order = 0;
+        let mut cycles = vec![];
+
+        for root in 0..count {
+            if order[root] != UNVISITED {
+                continue;
+            }
+
+            // (node, position in its outgoing edges)
+            let mut work = vec![(root, 0)];
+            while let Some((node, position)) = work.pop() {
+                if position == 0 {
+                    order[node] = next_order;
+                    low_link[node] = next_order;
+                    next_order += 1;
+                    stack.push(node);
+                    on_stack[node] = true;
+                }
+
+                if let Some(edge) = self.outgoing[node].get(position) {
+                    work.push((node, position + 1));
+                    let next = self.edges[*edge].to.index();
+                    if order[next] == UNVISITED {
+                        work.push((next, 0));
+                    } else if on_stack[next] {
+                        low_link[node] = low_link[node].min(order[next]);
+                    }
+                    continue;
+                }
+
+                if let Some((parent, _)) = work.last() {
+                    low_link[*parent] = low_link[*parent].min(low_link[node]);
+                }
+
+                if low_link[node] == order[node] {
+                    let mut component = vec![];
+                    while let Some(member) = stack.pop() {
+                        on_stack[member] = false;
+                        component.push(TableIndex::new(member));
+                        if member == node {
+                            break;
+                        }
+                    }
+
+                    let is_self_loop = self.outgoing[node]
+                        .iter()
+                        .any(|edge| self.edges[*edge].to.index() == node);
+
+                    if component.len() > 1 || is_self_loop {
+                        cycles.push(component);
+                    }
+                }
+            }
+        }
+
+        cycles
+    }
+
+    /// Writes the graph in the Graphviz DOT format
+    ///
+    /// Packages are labeled with their full path, using the [`Hash40`] label map
+    pub fn write_dot<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "digraph packages {{")?;
+
+        for (index, name) in self.names.iter().enumerate() {
+            writeln!(
+                writer,
+                "    p{} [label=\"{}\"];",
+                index,
+                escape(&name.to_label())
+            )?;
+        }
+
+        for edge in self.edges.iter() {
+            writeln!(
+                writer,
+                "    p{} -> p{} [label=\"{}\"];",
+                edge.from.index(),
+                edge.to.index(),
+                edge.kind.as_str()
+            )?;
+        }
+
+        writeln!(writer, "}}")
+    }
+
+    /// Writes the graph as a JSON adjacency list
+    ///
+    /// The output is an object which maps the full path of every package to the list of packages
+    /// that it depends on, as `{ "to": <path>, "kind": <kind> }` objects.
+    pub fn write_json<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut json = JsonWriter::new();
+        json.begin_object();
+
+        for (index, name) in self.names.iter().enumerate() {
+            json.key(&name.to_label());
+            json.begin_array();
+            for edge in self.outgoing[index].iter() {
+                let edge = &self.edges[*edge];
+                json.begin_object();
+                json.key("to");
+                json.string(&self.names[edge.to.index()].to_label());
+                json.key("kind");
+                json.string(edge.kind.as_str());
+                json.end_object();
+            }
+            json.end_array();
+        }
+
+        json.end_object();
+        json.finish(writer)
+    }
+}
+
+impl PackagedEngine {
+    /// Builds the dependency graph of every package
+    ///
+    /// ### Panicking
+    /// Panics if the engine has not been [resolved](Self::resolve)
+    pub fn package_graph(&self) -> PackageGraph {
+        let mut edges = BTreeSet::new();
+
+        for (index, package) in self.packages.iter_indexed() {
+            for child in package.child_packages.iter() {
+                edges.insert(PackageEdge {
+                    from: index,
+                    to: self.child_packages[child].package(),
+                    kind: PackageEdgeKind::Child,
+                });
+            }
+
+            if package.has_sub_package || package.is_sym_link {
+                let kind = if package.is_sym_link {
+                    PackageEdgeKind::SymLink
+                } else {
+                    PackageEdgeKind::SubPackage
+                };
+
+                for group in package.groups.iter() {
+                    let group = &self.groups[group];
+                    if group.has_package() {
+                        edges.insert(PackageEdge {
+                            from: index,
+                            to: group.package(),
+                            kind,
+                        });
+                    }
+                }
+            }
+
+            for info in package.infos.iter() {
+                for descriptor in self.infos[info].descriptors.iter() {
+                    let link = match &self.descriptors[descriptor].load_args {
+                        DescriptorLoadArguments::Unowned { link }
+                        | DescriptorLoadArguments::SharedButOwned { link } => link.index(),
+                        _ => continue,
+                    };
+
+                    let link = &self.links[link];
+                    if link.is_owner_package() && link.package() != index {
+                        edges.insert(PackageEdge {
+                            from: index,
+                            to: link.package(),
+                            kind: PackageEdgeKind::SharedFile,
+                        });
+                    }
+                }
+            }
+        }
+
+        let names = self
+            .packages
+            .iter()
+            .map(|package| package.full_path)
+            .collect();
+
+        PackageGraph::new(names, edges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engines::packaged::tests::build_engine;
+
+    #[test]
+    fn edges_follow_children_and_shared_files() {
+        let graph = build_engine().package_graph();
+        let mario = graph.find("fighter/mario/c00").unwrap();
+        let message = graph.find("ui/message").unwrap();
+
+        let kinds: Vec<_> = graph
+            .dependencies(mario)
+            .map(|edge| (edge.to, edge.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (message, PackageEdgeKind::Child),
+                (message, PackageEdgeKind::SharedFile)
+            ]
+        );
+        assert_eq!(graph.dependents(message).count(), 2);
+        assert_eq!(graph.descendants(mario), [message]);
+        assert_eq!(graph.ancestors(message), [mario]);
+        assert!(graph.ancestors(mario).is_empty());
+        assert!(graph.cycles().is_empty());
+    }
+
+    #[test]
+    fn cycles_are_found() {
+        let mut engine = build_engine();
+        engine.add_child_package("ui/message", "fighter/mario/c00");
+        let graph = engine.package_graph();
+
+        let mut cycles = graph.cycles();
+        assert_eq!(cycles.len(), 1);
+        cycles[0].sort();
+        assert_eq!(
+            cycles[0],
+            [
+                graph.find("fighter/mario/c00").unwrap(),
+                graph.find("ui/message").unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn graphs_are_exported() {
+        Hash40::label_map()
+            .lock()
+            .unwrap()
+            .add_labels(["fighter/mario/c00", "ui/message"].map(String::from));
+        let graph = build_engine().package_graph();
+
+        let mut dot = vec![];
+        graph.write_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("digraph packages {"));
+        assert!(dot.contains("p0 [label=\"fighter/mario/c00\"];"));
+        assert!(dot.contains("p0 -> p1 [label=\"shared_file\"];"));
+
+        let mut json = vec![];
+        graph.write_json(&mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["fighter/mario/c00"][0]["to"], "ui/message");
+        assert_eq!(json["fighter/mario/c00"][0]["kind"], "child");
+        assert_eq!(json["ui/message"].as_array().unwrap().len(), 0);
+    }
+}
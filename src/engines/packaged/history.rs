@@ -0,0 +1,204 @@
+use hash40::Hash40;
+use semver::Version;
+
+use crate::{engines::table::TableIndex, Hashable};
+
+use super::{
+    types::{Info, Patch, VersionedFile},
+    PackagedEngine,
+};
+
+/// The state of a file in one of the archive's patches
+///
+/// Returned by [`PackagedEngine::file_history`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FilePatchEntry {
+    /// The patch that the entry is from
+    pub patch: TableIndex<Patch>,
+
+    /// The versioned file for the file in the patch
+    pub versioned_file: TableIndex<VersionedFile>,
+
+    /// The info from the patch's version group, which describes the file as of the patch
+    pub info: TableIndex<Info>,
+
+    /// Whether the file was changed by the patch, rather than carried over from an earlier one
+    pub changed_this_patch: bool,
+}
+
+impl PackagedEngine {
+    /// Finds the patch for a game version
+    pub fn get_patch(&self, version: &Version) -> Option<TableIndex<Patch>> {
+        self.patches
+            .iter_indexed()
+            .find(|(_, patch)| patch.version == *version)
+            .map(|(index, _)| index)
+    }
+
+    /// Pairs each versioned file of a patch with its info from the patch's version group
+    ///
+    /// ### Notes
+    /// The infos of a valid patch line up with its versioned files. When a patch has fewer,
+    /// the versioned files past the end of its infos are skipped instead of panicking.
+    pub(crate) fn patch_entries(
+        &self,
+        patch: TableIndex<Patch>,
+    ) -> impl Iterator<Item = (TableIndex<VersionedFile>, TableIndex<Info>)> + '_ {
+        let patch = &self.patches[patch];
+        patch.versioned_files.iter().zip(patch.infos.iter())
+    }
+
+    /// Gets the history of a file across every patch in the archive
+    ///
+    /// ### Arguments
+    /// * `hash` - The hash of the file
+    ///
+    /// ### Returns
+    /// The version of every patch which has an entry for the file, along with the entry,
+    /// in the order of the patch table. If the file was never versioned, this is empty.
+    ///
+    /// ### Panicking
+    /// Panics if the engine has not been [resolved](Self::resolve)
+    ///
+    /// ### Notes
+    /// This checks every versioned file in every patch, so it is not cheap.
+    /// Cache the results if many files need to be queried.
+    pub fn file_history(&self, hash: impl Hashable) -> Vec<(Version, FilePatchEntry)> {
+        let hash = hash.to_hash();
+
+        let mut history = vec![];
+
+        for (index, patch) in self.patches.iter_indexed() {
+            for (versioned_file, info) in self.patch_entries(index) {
+                let file = &self.versioned_files[versioned_file];
+                if file.path != hash {
+                    continue;
+                }
+
+                history.push((
+                    patch.version.clone(),
+                    FilePatchEntry {
+                        patch: index,
+                        versioned_file,
+                        info,
+                        changed_this_patch: file.changed_this_patch,
+                    },
+                ));
+            }
+        }
+
+        history
+    }
+
+    /// Gets every file that was changed in a game version
+    ///
+    /// ### Arguments
+    /// * `version` - The version of the patch
+    ///
+    /// ### Returns
+    /// The full path of every file which the patch marks as changed, or `None` if there
+    /// is no patch for the version
+    ///
+    /// ### Panicking
+    /// Panics if the engine has not been [resolved](Self::resolve)
+    pub fn changed_in(&self, version: &Version) -> Option<Vec<Hash40>> {
+        let patch = &self.patches[self.get_patch(version)?];
+
+        let changed = patch
+            .versioned_files
+            .iter()
+            .map(|file| &self.versioned_files[file])
+            .filter(|file| file.changed_this_patch)
+            .map(|file| file.path)
+            .collect();
+
+        Some(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engines::{
+        packaged::tests::{add_patch, build_engine},
+        table::TableContiguousReference,
+    };
+
+    const MODEL: &str = "fighter/mario/model/body/c00/model.numdlb";
+    const MOTION: &str = "fighter/mario/motion/body/c00/a00wait1.nuanmb";
+
+    fn build_patched_engine() -> PackagedEngine {
+        let mut engine = build_engine();
+        add_patch(
+            &mut engine,
+            Version::new(13, 0, 0),
+            &[(MODEL, true), (MOTION, true)],
+        );
+        add_patch(
+            &mut engine,
+            Version::new(13, 0, 1),
+            &[(MODEL, false), (MOTION, true)],
+        );
+        engine
+    }
+
+    #[test]
+    fn history_lists_every_patch() {
+        let engine = build_patched_engine();
+
+        let history: Vec<_> = engine
+            .file_history(MODEL)
+            .into_iter()
+            .map(|(version, entry)| (version, entry.changed_this_patch))
+            .collect();
+        assert_eq!(
+            history,
+            [
+                (Version::new(13, 0, 0), true),
+                (Version::new(13, 0, 1), false)
+            ]
+        );
+
+        let (_, entry) = &engine.file_history(MOTION)[1];
+        assert_eq!(
+            engine.versioned_files[entry.versioned_file].path,
+            MOTION.to_hash()
+        );
+        assert_eq!(entry.info, engine.patches[entry.patch].infos.get(1));
+        assert_eq!(
+            engine.paths[engine.infos[entry.info].path()].full_path,
+            MOTION.to_hash()
+        );
+
+        assert!(engine.file_history("ui/message/msg_name.msbt").is_empty());
+    }
+
+    #[test]
+    fn changed_files_are_listed_by_version() {
+        let engine = build_patched_engine();
+
+        assert_eq!(
+            engine.changed_in(&Version::new(13, 0, 0)).unwrap(),
+            [MODEL.to_hash(), MOTION.to_hash()]
+        );
+        assert_eq!(
+            engine.changed_in(&Version::new(13, 0, 1)).unwrap(),
+            [MOTION.to_hash()]
+        );
+        assert!(engine.changed_in(&Version::new(12, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn files_past_the_end_of_the_infos_are_skipped() {
+        let mut engine = build_patched_engine();
+        let patch = engine.get_patch(&Version::new(13, 0, 1)).unwrap();
+
+        let start = engine.patches[patch].infos.get(0).index();
+        let mut infos = TableContiguousReference::new_from_count(start, 1);
+        infos.resolve(&engine.infos).unwrap();
+        engine.patches[patch].infos = infos;
+
+        assert_eq!(engine.file_history(MODEL).len(), 2);
+        assert_eq!(engine.file_history(MOTION).len(), 1);
+    }
+}
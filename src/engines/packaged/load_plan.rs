@@ -0,0 +1,363 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::{
+    engines::{table::TableIndex, Locale, Region},
+    Hashable,
+};
+
+use super::{
+    types::{Descriptor, DescriptorLoadArguments, Group, Info, Metadata, Package},
+    PackagedEngine,
+};
+
+/// The deepest chain of descriptor redirections that will be followed before giving up
+const MAX_REDIRECT_DEPTH: usize = 8;
+
+/// A group whose data is read while loading a package
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GroupRead {
+    /// The group that is read
+    pub group: TableIndex<Group>,
+
+    /// The offset of the group's data, relative to the start of the archive's file data
+    pub archive_offset: usize,
+
+    /// The size of the group's data in the archive
+    pub compressed_size: usize,
+
+    /// The size of the group's data once decompressed
+    pub decompressed_size: usize,
+}
+
+/// A single file that is read while loading a package
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FileRead {
+    /// The package which requested the file
+    pub package: TableIndex<Package>,
+
+    /// The info which the file was requested through
+    pub info: TableIndex<Info>,
+
+    /// The descriptor which the file data was found through, after following any
+    /// unowned, shared or skipped descriptors
+    pub descriptor: TableIndex<Descriptor>,
+
+    /// The group that the file data belongs to
+    pub group: TableIndex<Group>,
+
+    /// The metadata for the file data
+    pub metadata: TableIndex<Metadata>,
+
+    /// The offset of the file data, relative to the start of the archive's file data
+    pub archive_offset: usize,
+
+    /// The size of the file data in the archive
+    pub compressed_size: usize,
+
+    /// The size of the file data once decompressed
+    pub decompressed_size: usize,
+}
+
+/// Everything the game would read in order to load a package
+///
+/// Created by [`PackagedEngine::load_plan`]
+#[derive(Debug, Clone)]
+pub struct LoadPlan {
+    /// The locale that the plan was made for
+    pub locale: Locale,
+
+    /// Every package that is loaded, starting with the requested one and followed by
+    /// its child packages and sub packages
+    pub packages: Vec<TableIndex<Package>>,
+
+    /// Every group that file data is read from, in the order they are first read
+    pub groups: Vec<GroupRead>,
+
+    /// Every file that is read, in load order. File data is only listed once, even
+    /// if it is requested by more than one info.
+    pub files: Vec<FileRead>,
+
+    /// The total size of all of the file data read from the archive
+    ///
+    /// This saturates at `usize::MAX`, which is what [`Metadata::new`] uses for sizes that
+    /// haven't been set yet
+    pub compressed_size: usize,
+
+    /// The total size of all of the file data once decompressed, saturating the same way as
+    /// [`compressed_size`](Self::compressed_size)
+    pub decompressed_size: usize,
+}
+
+/// Picks the position of the variant for a locale out of a localized or regional set
+///
+/// The first entry of every localized/regional set is the shared variant, which is also
+/// what is used for [`Locale::Invalid`]
+fn variant_position(is_localized: bool, is_regional: bool, locale: Locale) -> usize {
+    if is_localized && locale != Locale::Invalid {
+        1 + locale as usize
+    } else if is_regional && Region::from(locale) != Region::Invalid {
+        1 + Region::from(locale) as usize
+    } else {
+        0
+    }
+}
+
+impl PackagedEngine {
+    /// Selects the descriptor of an info that is used for a locale
+    fn select_descriptor(&self, info: TableIndex<Info>, locale: Locale) -> TableIndex<Descriptor> {
+        let info = &self.infos[info];
+        let position = variant_position(info.is_localized, info.is_regional, locale);
+
+        // Infos without a descriptor for the variant fall back to the shared one
+        let position = if position < info.descriptors.len() {
+            position
+        } else {
+            0
+        };
+        let descriptor = info.descriptors.get(position);
+
+        // Unsupported locales/regions redirect to one that is supported
+        match &self.descriptors[descriptor].load_args {
+            DescriptorLoadArguments::UnsupportedRegion { region_locale }
+                if 1 + (*region_locale as usize) < info.descriptors.len() =>
+            {
+                info.descriptors.get(1 + *region_locale as usize)
+            }
+            _ => descriptor,
+        }
+    }
+
+    /// Follows a descriptor through any redirections to the descriptor that owns its data
+    ///
+    /// `select` picks the descriptor to continue with when a redirection points to another info
+    fn follow_descriptor(
+        &self,
+        descriptor: TableIndex<Descriptor>,
+        select: impl Fn(TableIndex<Info>) -> Option<TableIndex<Descriptor>>,
+    ) -> Option<(TableIndex<Descriptor>, TableIndex<Metadata>)> {
+        let mut descriptor = descriptor;
+
+        for _ in 0..MAX_REDIRECT_DEPTH {
+            let info = match &self.descriptors[descriptor].load_args {
+                DescriptorLoadArguments::Unowned { link }
+                | DescriptorLoadArguments::SharedButOwned { link } => {
+                    self.links[link.index()].info()
+                }
+                DescriptorLoadArguments::PackageSkip { info } => info.index(),
+                DescriptorLoadArguments::Owned { .. } => {
+                    let descriptor_ref = &self.descriptors[descriptor];
+                    return descriptor_ref
+                        .has_metadata()
+                        .then(|| (descriptor, descriptor_ref.metadata()));
+                }
+                DescriptorLoadArguments::Unknown
+                | DescriptorLoadArguments::UnsupportedRegion { .. } => return None,
+            };
+
+            descriptor = select(info)?;
+        }
+
+        None
+    }
+
+    /// Follows an info through any redirections to the descriptor that owns its data
+    pub(crate) fn find_file_data(
+        &self,
+        info: TableIndex<Info>,
+        locale: Locale,
+    ) -> Option<(TableIndex<Descriptor>, TableIndex<Metadata>)> {
+        self.follow_descriptor(self.select_descriptor(info, locale), |info| {
+            Some(self.select_descriptor(info, locale))
+        })
+    }
+
+    /// Follows a descriptor through any redirections to the descriptor that owns its data
+    ///
+    /// When a redirection points to another info, the descriptor at the same `position` is
+    /// used, or the info's last descriptor if it has fewer.
+    pub(crate) fn descriptor_data(
+        &self,
+        descriptor: TableIndex<Descriptor>,
+        position: usize,
+    ) -> Option<(TableIndex<Descriptor>, TableIndex<Metadata>)> {
+        self.follow_descriptor(descriptor, |info| {
+            let descriptors = &self.infos[info].descriptors;
+            (!descriptors.is_empty()).then(|| descriptors.get(position.min(descriptors.len() - 1)))
+        })
+    }
+
+    /// Simulates loading a package, listing everything that the game would read
+    ///
+    /// ### Arguments
+    /// * `package` - The hash of the package's full path
+    /// * `locale` - The locale to pick localized and regional data with
+    ///
+    /// ### Returns
+    /// The load plan, or `None` if the package does not exist
+    ///
+    /// ### Panicking
+    /// Panics if the engine has not been [resolved](Self::resolve)
+    ///
+    /// ### Notes
+    /// Child packages and sub packages are loaded after the package that references them,
+    /// and every package is only loaded once. Offsets are relative to the start of the
+    /// archive's file data, and the totals only count each file's data once.
+    pub fn load_plan(&self, package: impl Hashable, locale: Locale) -> Option<LoadPlan> {
+        let root = *self.package_lookup.get(&package.to_hash())?;
+
+        let mut plan = LoadPlan {
+            locale,
+            packages: vec![],
+            groups: vec![],
+            files: vec![],
+            compressed_size: 0,
+            decompressed_size: 0,
+        };
+
+        let mut seen_packages = HashSet::new();
+        let mut seen_groups = HashSet::new();
+        let mut seen_metadatas = HashSet::new();
+
+        let mut queue = VecDeque::from([root]);
+        seen_packages.insert(root);
+
+        while let Some(index) = queue.pop_front() {
+            plan.packages.push(index);
+            let package = &self.packages[index];
+
+            let mut infos: Vec<TableIndex<Info>> = package.infos.iter().collect();
+
+            let group_position =
+                variant_position(package.is_localized, package.is_regional, locale);
+            let mut groups = vec![package.groups.get(0)];
+            if group_position != 0 && group_position < package.groups.len() {
+                groups.push(package.groups.get(group_position));
+            }
+
+            for group in groups {
+                let group = &self.groups[group];
+                if group.has_package() {
+                    let sub_package = group.package();
+                    if sub_package != index && seen_packages.insert(sub_package) {
+                        queue.push_back(sub_package);
+                    }
+                } else if group.has_group() {
+                    let info_group = &self.groups[group.group()];
+                    if info_group.is_info_group() {
+                        infos.extend(info_group.infos().iter());
+                    }
+                }
+            }
+
+            for child in package.child_packages.iter() {
+                let child = self.child_packages[child].package();
+                if seen_packages.insert(child) {
+                    queue.push_back(child);
+                }
+            }
+
+            for info in infos {
+                let (descriptor, metadata) = match self.find_file_data(info, locale) {
+                    Some(data) => data,
+                    None => continue,
+                };
+
+                if !seen_metadatas.insert(metadata) {
+                    continue;
+                }
+
+                let group = self.descriptors[descriptor].group();
+                let group_ref = &self.groups[group];
+                if seen_groups.insert(group) {
+                    plan.groups.push(GroupRead {
+                        group,
+                        archive_offset: group_ref.archive_offset,
+                        compressed_size: group_ref.compressed_size,
+                        decompressed_size: group_ref.decompressed_size,
+                    });
+                }
+
+                let metadata_ref = &self.metadatas[metadata];
+                plan.compressed_size = plan
+                    .compressed_size
+                    .saturating_add(metadata_ref.compressed_size);
+                plan.decompressed_size = plan
+                    .decompressed_size
+                    .saturating_add(metadata_ref.decompressed_size);
+                plan.files.push(FileRead {
+                    package: index,
+                    info,
+                    descriptor,
+                    group,
+                    metadata,
+                    archive_offset: group_ref
+                        .archive_offset
+                        .saturating_add(metadata_ref.group_offset),
+                    compressed_size: metadata_ref.compressed_size,
+                    decompressed_size: metadata_ref.decompressed_size,
+                });
+            }
+        }
+
+        Some(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engines::packaged::tests::build_engine;
+
+    #[test]
+    fn plans_pick_the_variant_for_the_locale() {
+        let mut engine = build_engine();
+        for metadata in engine.metadatas.iter_mut() {
+            metadata.group_offset = 0;
+            metadata.compressed_size = 0x10;
+        }
+
+        let mario = *engine
+            .package_lookup
+            .get(&hash40::Hash40::new("fighter/mario/c00"))
+            .unwrap();
+        let message = *engine
+            .package_lookup
+            .get(&hash40::Hash40::new("ui/message"))
+            .unwrap();
+
+        // The message is loaded through mario's shared info, so the child package adds nothing
+        for (locale, variant_size) in [
+            (Locale::Invalid, 0),
+            (Locale::Japanese, 1),
+            (Locale::UsEnglish, 2),
+        ] {
+            let plan = engine.load_plan("fighter/mario/c00", locale).unwrap();
+            assert_eq!(plan.packages, [mario, message]);
+            assert_eq!(plan.files.len(), 3);
+            assert!(plan.files.iter().all(|file| file.package == mario));
+            assert_eq!(plan.files[2].decompressed_size, variant_size);
+            assert_eq!(plan.groups.len(), 2);
+            assert_eq!(plan.compressed_size, 0x30);
+            assert_eq!(plan.decompressed_size, 0x300 + variant_size);
+        }
+
+        let plan = engine.load_plan("ui/message", Locale::Japanese).unwrap();
+        assert_eq!(plan.packages, [message]);
+        assert_eq!(plan.files.len(), 1);
+        assert_eq!(plan.files[0].package, message);
+
+        assert!(engine
+            .load_plan("fighter/luigi/c00", Locale::Japanese)
+            .is_none());
+    }
+
+    #[test]
+    fn unset_sizes_saturate() {
+        let plan = build_engine()
+            .load_plan("fighter/mario/c00", Locale::Japanese)
+            .unwrap();
+        assert_eq!(plan.compressed_size, usize::MAX);
+        assert_eq!(plan.files[0].archive_offset, usize::MAX);
+        assert_eq!(plan.decompressed_size, 0x301);
+    }
+}
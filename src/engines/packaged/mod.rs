@@ -1,6 +1,4 @@
 use std::{
-    cell::{Ref, RefMut},
-    collections::BTreeMap,
     io::{Seek, SeekFrom, Write},
     num::NonZeroUsize,
     rc::Rc,
@@ -12,39 +10,123 @@ use crate::{Hashable, INVALID_INDEX};
 use self::bucket_map::BucketMap;
 
 pub mod bucket_map;
+pub mod dependents;
+pub mod graph;
+pub mod history;
+pub mod load_plan;
+pub mod query;
 pub mod types;
 
 use binrw::{BinRead, BinWrite, VecArgs};
-use hash40::Hash40;
 use semver::Version;
 use types::*;
 
 use super::{
+    lookup::HashLookup,
     read_table,
-    table::{TableCell, TableMaker},
-    HashKey,
+    table::{
+        check_bounds, ResolveError, Table, TableIndex, TableMaker, TableReference, TableRemap,
+    },
+    HashKey, VariantKind,
 };
 
+/// The number of path buckets that a new engine starts with
+const DEFAULT_BUCKET_COUNT: usize = 0x1000;
+
+/// File engine to access the packages and files of the packaged filesystem
+///
+/// ### Lazy resolution
+/// An engine read from an archive starts out unresolved. Either resolve all of it with
+/// [`resolve`](Self::resolve), or resolve only what is needed through the accessors which take
+/// `&mut self`: [`resolve_package`](Self::resolve_package), [`resolve_file`](Self::resolve_file),
+/// [`get_package_mut`](Self::get_package_mut) and [`get_file_mut`](Self::get_file_mut).
+///
+/// [`get_package`](Self::get_package) and [`get_file`](Self::get_file) only borrow the engine,
+/// so they can't resolve anything and return entries exactly as they are stored. Following
+/// the references of an entry which hasn't been resolved yet panics.
+#[derive(Clone)]
 pub struct PackagedEngine {
     pub version: Version,
-    pub(crate) package_lookup: BTreeMap<Hash40, TableCell<Package>>,
-    pub(crate) file_lookup: BucketMap<TableCell<Path>>,
+    pub(crate) package_lookup: HashLookup<TableIndex<Package>>,
+    pub(crate) file_lookup: BucketMap<TableIndex<Path>>,
 
-    pub packages: Vec<TableCell<Package>>,
-    pub child_packages: Vec<TableCell<ChildPackage>>,
-    pub groups: Vec<TableCell<Group>>,
+    pub packages: Table<Package>,
+    pub child_packages: Table<ChildPackage>,
+    pub groups: Table<Group>,
 
-    pub paths: Vec<TableCell<Path>>,
-    pub links: Vec<TableCell<Link>>,
-    pub infos: Vec<TableCell<Info>>,
-    pub descriptors: Vec<TableCell<Descriptor>>,
-    pub metadatas: Vec<TableCell<Metadata>>,
+    pub paths: Table<Path>,
+    pub links: Table<Link>,
+    pub infos: Table<Info>,
+    pub descriptors: Table<Descriptor>,
+    pub metadatas: Table<Metadata>,
 
-    pub patches: Vec<TableCell<Patch>>,
-    pub versioned_files: Vec<TableCell<VersionedFile>>,
+    pub patches: Table<Patch>,
+    pub versioned_files: Table<VersionedFile>,
+
+    pub(crate) resolve_offsets: Option<ResolveOffsets>,
+}
+
+/// Where each section of the info table starts, which is needed to resolve
+/// references that are relative to those sections
+#[derive(Copy, Clone)]
+pub(crate) struct ResolveOffsets {
+    info_group_info_start: usize,
+    version_group_info_start: usize,
+    latest_patch_file_start: usize,
+}
+
+impl ResolveOffsets {
+    /// Gets the offset to apply to the package skip references of an info's descriptors
+    fn info_offset(&self, info_index: usize) -> usize {
+        if info_index >= self.info_group_info_start {
+            0
+        } else {
+            self.info_group_info_start
+        }
+    }
+
+    /// Checks if an info is one of the versioned infos
+    fn is_versioned(&self, info_index: usize) -> bool {
+        info_index >= self.version_group_info_start
+    }
 }
 
 impl PackagedEngine {
+    /// Creates an engine with no packages or files
+    ///
+    /// ### Arguments
+    /// * `version` - The version of the archive
+    ///
+    /// ### Notes
+    /// Everything in the engine is already resolved, and stays resolved as packages and
+    /// files are added. There are no info groups, version groups or patches.
+    pub fn new(version: Version) -> Self {
+        Self {
+            version,
+            package_lookup: HashLookup::new(),
+            file_lookup: BucketMap::new(NonZeroUsize::new(DEFAULT_BUCKET_COUNT).unwrap()),
+
+            packages: Table::new(),
+            child_packages: Table::new(),
+            groups: Table::new(),
+
+            paths: Table::new(),
+            links: Table::new(),
+            infos: Table::new(),
+            descriptors: Table::new(),
+            metadatas: Table::new(),
+
+            patches: Table::new(),
+            versioned_files: Table::new(),
+
+            resolve_offsets: Some(ResolveOffsets {
+                info_group_info_start: 0,
+                version_group_info_start: usize::MAX,
+                latest_patch_file_start: 0,
+            }),
+        }
+    }
+
     pub fn from_directory(path: impl AsRef<std::path::Path>) -> binrw::BinResult<Self> {
         let path = path.as_ref();
 
@@ -58,7 +140,7 @@ impl PackagedEngine {
         let version = Version::new(version.2 as u64, version.1 as u64, version.0 as u64);
         let num_versions = u32::read(&mut version_data)? as usize;
 
-        let patches: Vec<TableCell<Patch>> = BinRead::read_args(
+        let patches: Vec<Patch> = BinRead::read_args(
             &mut version_data,
             VecArgs::builder().count(num_versions).finalize(),
         )?;
@@ -66,8 +148,6 @@ impl PackagedEngine {
         let mut versioned_files = vec![];
 
         for patch in patches.iter() {
-            let patch = patch.get();
-
             versioned_files.extend(Vec::read_args(
                 &mut version_data,
                 VecArgs::builder()
@@ -78,22 +158,21 @@ impl PackagedEngine {
             version_data.seek(SeekFrom::Current(patch.lookup_size_in_bytes as i64))?;
         }
 
-        let packages: Vec<TableCell<Package>> =
-            read_table(&path.join("packages.bin"), Package::REPR_SIZE)?;
-        let child_packages: Vec<TableCell<ChildPackage>> =
+        let packages: Table<Package> = read_table(&path.join("packages.bin"), Package::REPR_SIZE)?;
+        let child_packages: Table<ChildPackage> =
             read_table(&path.join("child_packages.bin"), ChildPackage::REPR_SIZE)?;
-        let groups: Vec<TableCell<Group>> = read_table(&path.join("groups.bin"), Group::REPR_SIZE)?;
-        let paths: Vec<TableCell<Path>> = read_table(&path.join("paths.bin"), Path::REPR_SIZE)?;
-        let links: Vec<TableCell<Link>> = read_table(&path.join("links.bin"), Link::REPR_SIZE)?;
-        let infos: Vec<TableCell<Info>> = read_table(&path.join("infos.bin"), Info::REPR_SIZE)?;
-        let descriptors: Vec<TableCell<Descriptor>> =
+        let groups: Table<Group> = read_table(&path.join("groups.bin"), Group::REPR_SIZE)?;
+        let paths: Table<Path> = read_table(&path.join("paths.bin"), Path::REPR_SIZE)?;
+        let links: Table<Link> = read_table(&path.join("links.bin"), Link::REPR_SIZE)?;
+        let infos: Table<Info> = read_table(&path.join("infos.bin"), Info::REPR_SIZE)?;
+        let descriptors: Table<Descriptor> =
             read_table(&path.join("descriptors.bin"), Descriptor::REPR_SIZE)?;
-        let metadatas: Vec<TableCell<Metadata>> =
+        let metadatas: Table<Metadata> =
             read_table(&path.join("metadatas.bin"), Metadata::REPR_SIZE)?;
 
         let package_lookup = packages
-            .iter()
-            .map(|package| (package.get().full_path, package.clone()))
+            .iter_indexed()
+            .map(|(index, package)| (package.full_path, index))
             .collect();
 
         let bucket_count = (std::fs::metadata(path.join("path_buckets.bin"))?.len() as usize) / 8;
@@ -102,8 +181,8 @@ impl PackagedEngine {
             NonZeroUsize::new(bucket_count).expect("Bucket count should be non-zero!"),
         );
 
-        for path in paths.iter() {
-            file_lookup.insert(path.get().full_path, path.clone());
+        for (index, path) in paths.iter_indexed() {
+            file_lookup.insert(path.full_path, index);
         }
 
         Ok(Self {
@@ -121,134 +200,430 @@ impl PackagedEngine {
             descriptors,
             metadatas,
 
-            patches,
-            versioned_files,
+            patches: Table::from(patches),
+            versioned_files: Table::from(versioned_files),
+
+            resolve_offsets: None,
         })
     }
 
-    pub fn resolve(&self) {
-        for package in self.packages.iter() {
-            package
-                .get_mut()
-                .resolve(&self.groups, &self.infos, &self.child_packages);
+    /// Resolves the tables which every other resolution depends on
+    ///
+    /// The groups and patches are needed to find where the info group and version group
+    /// sections start in the info table, which is required to resolve paths and descriptors.
+    /// They are small, so they are always resolved up front.
+    ///
+    /// ### Returns
+    /// * `Ok(_)` - The offsets used to resolve the rest of the engine
+    /// * `Err(_)` - A group or patch refers to something outside of its table
+    fn resolve_offsets(&mut self) -> Result<ResolveOffsets, ResolveError> {
+        if let Some(offsets) = self.resolve_offsets {
+            return Ok(offsets);
         }
 
-        for child_package in self.child_packages.iter() {
-            child_package.get_mut().resolve(&self.packages);
+        fn first_info(group: &Group) -> Result<usize, ResolveError> {
+            check_bounds(0, group.infos().len())?;
+            Ok(group.infos().get(0).index())
         }
 
+        let group_count = self.groups.len();
         let mut info_group_info_start = 0;
-        let mut version_group_info_start = 0;
-        for (index, group) in self.groups.iter().enumerate() {
-            group.get_mut().resolve(
+        let mut version_group_info_start = None;
+        for (index, group) in self.groups.iter_mut().enumerate() {
+            group.resolve(
                 &self.packages,
-                &self.groups,
+                group_count,
                 &self.infos,
                 &self.metadatas,
                 index,
-            );
+            )?;
 
-            if group.get().is_info_group() && info_group_info_start == 0 {
-                info_group_info_start =
-                    (group.get().infos().cells()[0].guid() - self.infos[0].guid()) as usize;
+            if group.is_info_group() && info_group_info_start == 0 {
+                info_group_info_start = first_info(group)?;
             }
 
-            if group.get().is_version_group() && version_group_info_start == 0 {
-                version_group_info_start =
-                    (group.get().infos().cells()[0].guid() - self.infos[0].guid()) as usize;
+            if group.is_version_group() && version_group_info_start.is_none() {
+                version_group_info_start = Some(first_info(group)?);
             }
         }
 
-        let latest_patch_file_info_start = (self.groups.last().unwrap().get().infos().cells()[0]
-            .guid()
-            - self.infos[0].guid()) as usize;
-        let latest_patch_file_start = latest_patch_file_info_start - version_group_info_start;
-        for path in self.paths.iter() {
-            path.get_mut()
-                .resolve(&self.links, &self.versioned_files, latest_patch_file_start);
-        }
-
-        for link in self.links.iter() {
-            link.get_mut()
-                .resolve(&self.packages, &self.groups, &self.infos);
-        }
-
-        for (index, info) in self.infos.iter().enumerate() {
-            info.get_mut()
-                .resolve(&self.paths, &self.links, &self.descriptors);
-            let info_offset = if index >= info_group_info_start {
-                0
-            } else {
-                info_group_info_start
-            };
-            for mut descriptor in info.get().descriptors.iter_mut() {
-                descriptor.resolve(
-                    &self.groups,
-                    &self.links,
-                    &self.infos,
-                    &self.metadatas,
-                    &self.patches,
-                    info_offset,
-                    index >= version_group_info_start,
-                );
+        // Archives built from scratch don't have a version group, so nothing is versioned
+        let version_group_info_start = version_group_info_start.unwrap_or(usize::MAX);
+        let latest_patch_file_start = match self.groups.last() {
+            Some(group) if group.is_version_group() => {
+                first_info(group)? - version_group_info_start
             }
-        }
+            _ => 0,
+        };
 
-        for (count, patch) in self.patches.iter().enumerate() {
-            patch.get_mut().resolve(
+        for (count, patch) in self.patches.iter_mut().enumerate() {
+            patch.resolve(
                 &self.versioned_files,
                 &self.infos,
                 &self.groups,
                 count,
                 version_group_info_start,
-            );
+            )?;
         }
 
-        for versioned_file in self.versioned_files.iter() {
-            versioned_file.get_mut().resolve(&self.links, &self.infos);
+        let offsets = ResolveOffsets {
+            info_group_info_start,
+            version_group_info_start,
+            latest_patch_file_start,
+        };
+
+        self.resolve_offsets = Some(offsets);
+        Ok(offsets)
+    }
+
+    /// Resolves all of the tables in the engine
+    ///
+    /// ### Panicking
+    /// This function will panic if there is unexpected data in the
+    /// tables, such as an OOB index. Use [`try_resolve`](Self::try_resolve)
+    /// to handle that instead.
+    ///
+    /// ### Notes
+    /// Entries which have already been resolved lazily, such as by [`resolve_package`](Self::resolve_package),
+    /// are left as they are. The result is identical either way.
+    pub fn resolve(&mut self) {
+        if let Err(error) = self.try_resolve() {
+            panic!("{}", error);
         }
     }
 
-    pub fn get_package(&self, hash: impl Hashable) -> Option<Ref<'_, Package>> {
-        self.package_lookup.get(&hash.to_hash()).map(TableCell::get)
+    /// Resolves all of the tables in the engine
+    ///
+    /// ### Returns
+    /// * `Ok(())` - Every table was resolved
+    /// * `Err(_)` - An entry refers to something that doesn't exist. The engine is
+    ///   left partially resolved, and should not be used further.
+    pub fn try_resolve(&mut self) -> Result<(), ResolveError> {
+        let offsets = self.resolve_offsets()?;
+
+        let Self {
+            packages,
+            child_packages,
+            groups,
+            paths,
+            links,
+            infos,
+            descriptors,
+            metadatas,
+            patches,
+            versioned_files,
+            ..
+        } = self;
+
+        for package in packages.iter_mut() {
+            package.resolve(groups, infos, child_packages)?;
+        }
+
+        for child_package in child_packages.iter_mut() {
+            child_package.resolve(packages)?;
+        }
+
+        for path in paths.iter_mut() {
+            path.resolve(links, versioned_files, offsets.latest_patch_file_start)?;
+        }
+
+        for link in links.iter_mut() {
+            link.resolve(packages, groups, infos)?;
+        }
+
+        for info in infos.iter_mut() {
+            info.resolve(paths, links, descriptors)?;
+        }
+
+        // The descriptors need the whole info table to resolve package skips,
+        // so they can only be resolved once every info knows its descriptors
+        for (index, info) in infos.iter().enumerate() {
+            for descriptor in info.descriptors.iter() {
+                descriptors[descriptor].resolve(
+                    groups,
+                    links,
+                    infos,
+                    metadatas,
+                    patches,
+                    offsets.info_offset(index),
+                    offsets.is_versioned(index),
+                )?;
+            }
+        }
+
+        for versioned_file in versioned_files.iter_mut() {
+            versioned_file.resolve(links, infos)?;
+        }
+
+        Ok(())
     }
 
-    pub fn get_package_mut(&self, hash: impl Hashable) -> Option<RefMut<'_, Package>> {
-        self.package_lookup
-            .get(&hash.to_hash())
-            .map(TableCell::get_mut)
+    /// Resolves a package and everything it owns, without resolving the rest of the engine
+    ///
+    /// ### Arguments
+    /// * `package` - The index of the package to resolve
+    fn resolve_package_at(&mut self, package: TableIndex<Package>) -> Result<(), ResolveError> {
+        let offsets = self.resolve_offsets()?;
+
+        self.packages[package].resolve(&self.groups, &self.infos, &self.child_packages)?;
+
+        let package = &self.packages[package];
+        let child_packages = package.child_packages.indices().clone();
+        let infos = package.infos.indices().clone();
+
+        for child_package in child_packages.iter() {
+            self.child_packages[child_package].resolve(&self.packages)?;
+        }
+
+        for info in infos.iter() {
+            self.resolve_info_at(info, offsets)?;
+        }
+
+        Ok(())
     }
 
-    pub fn get_file(&self, hash: impl Hashable) -> Option<Ref<'_, Path>> {
-        self.file_lookup.get(hash.to_hash()).map(TableCell::get)
+    /// Resolves an info, its descriptors, and the path and links they refer to
+    ///
+    /// ### Arguments
+    /// * `info` - The index of the info to resolve
+    /// * `offsets` - The offsets from [`resolve_offsets`](Self::resolve_offsets)
+    fn resolve_info_at(
+        &mut self,
+        info: TableIndex<Info>,
+        offsets: ResolveOffsets,
+    ) -> Result<(), ResolveError> {
+        // Infos, paths and links can refer back to each other, so only ever
+        // descend into one that hasn't been resolved yet
+        if self.infos[info].is_resolved() {
+            return Ok(());
+        }
+
+        self.infos[info].resolve(&self.paths, &self.links, &self.descriptors)?;
+
+        let descriptors = self.infos[info].descriptors.indices().clone();
+        for descriptor in descriptors.iter() {
+            self.descriptors[descriptor].resolve(
+                &self.groups,
+                &self.links,
+                &self.infos,
+                &self.metadatas,
+                &self.patches,
+                offsets.info_offset(info.index()),
+                offsets.is_versioned(info.index()),
+            )?;
+        }
+
+        let (path, link) = (self.infos[info].path(), self.infos[info].link());
+        self.resolve_path_at(path, offsets)?;
+        self.resolve_link_at(link, offsets)?;
+
+        for descriptor in descriptors.iter() {
+            match self.descriptors[descriptor].load_args {
+                DescriptorLoadArguments::Unowned { ref link }
+                | DescriptorLoadArguments::SharedButOwned { ref link } => {
+                    let link = link.index();
+                    self.resolve_link_at(link, offsets)?;
+                }
+                DescriptorLoadArguments::PackageSkip { ref info } => {
+                    let info = info.index();
+                    self.resolve_info_at(info, offsets)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
     }
 
-    pub fn get_file_mut(&self, hash: impl Hashable) -> Option<RefMut<'_, Path>> {
-        self.file_lookup.get(hash.to_hash()).map(TableCell::get_mut)
+    /// Resolves a path, along with its link and versioned file
+    ///
+    /// ### Arguments
+    /// * `path` - The index of the path to resolve
+    /// * `offsets` - The offsets from [`resolve_offsets`](Self::resolve_offsets)
+    fn resolve_path_at(
+        &mut self,
+        path: TableIndex<Path>,
+        offsets: ResolveOffsets,
+    ) -> Result<(), ResolveError> {
+        if self.paths[path].is_resolved() {
+            return Ok(());
+        }
+
+        self.paths[path].resolve(
+            &self.links,
+            &self.versioned_files,
+            offsets.latest_patch_file_start,
+        )?;
+
+        let path = &self.paths[path];
+        let link = path.link();
+        let versioned_file = path.has_versioned_file().then(|| path.versioned_file());
+
+        self.resolve_link_at(link, offsets)?;
+
+        if let Some(versioned_file) = versioned_file {
+            self.versioned_files[versioned_file].resolve(&self.links, &self.infos)?;
+            let versioned_file = &self.versioned_files[versioned_file];
+            let (link, info) = (versioned_file.link(), versioned_file.info());
+            self.resolve_link_at(link, offsets)?;
+            self.resolve_info_at(info, offsets)?;
+        }
+
+        Ok(())
     }
 
-    pub fn reorganize(self) -> Self {
-        let writer = PackagedWriter::from_engine(self);
-        Self {
-            version: writer.version,
-            package_lookup: writer.package_lookup,
-            file_lookup: writer.file_lookup,
+    /// Resolves a link, along with the info that it points to
+    ///
+    /// ### Arguments
+    /// * `link` - The index of the link to resolve
+    /// * `offsets` - The offsets from [`resolve_offsets`](Self::resolve_offsets)
+    ///
+    /// ### Notes
+    /// The package or group which owns the link is not resolved, only the reference to it is
+    fn resolve_link_at(
+        &mut self,
+        link: TableIndex<Link>,
+        offsets: ResolveOffsets,
+    ) -> Result<(), ResolveError> {
+        if self.links[link].is_resolved() {
+            return Ok(());
+        }
 
-            packages: writer.packages.into_inner(),
-            child_packages: writer.child_packages.into_inner(),
-            groups: writer.groups.into_inner(),
+        self.links[link].resolve(&self.packages, &self.groups, &self.infos)?;
+        let info = self.links[link].info();
+        self.resolve_info_at(info, offsets)
+    }
 
-            paths: writer.paths.into_inner(),
-            links: writer.links.into_inner(),
-            infos: writer.infos.into_inner(),
-            descriptors: writer.descriptors.into_inner(),
-            metadatas: writer.metadatas.into_inner(),
+    /// Lazily resolves a path, panicking if it can't be
+    fn expect_path_resolved(&mut self, path: TableIndex<Path>) {
+        if let Err(error) = self
+            .resolve_offsets()
+            .and_then(|offsets| self.resolve_path_at(path, offsets))
+        {
+            panic!("{}", error);
+        }
+    }
 
-            patches: writer.patches.into_inner(),
-            versioned_files: writer.versioned_files.into_inner(),
+    /// Lazily resolves a package, panicking if it can't be
+    fn expect_package_resolved(&mut self, package: TableIndex<Package>) {
+        if let Err(error) = self.resolve_package_at(package) {
+            panic!("{}", error);
         }
     }
 
+    /// Gets a package by hash, resolving it first if it has not been resolved yet
+    ///
+    /// ### Arguments
+    /// * `hash` - The hash of the package
+    ///
+    /// ### Returns
+    /// * `Some(_)` - The resolved package
+    /// * `None` - There is no package with that hash
+    ///
+    /// ### Notes
+    /// Only the package's child packages and infos are resolved, along with everything those
+    /// infos refer to. This is much faster than [`resolve`](Self::resolve) when only
+    /// a handful of packages are needed.
+    ///
+    /// ### Panicking
+    /// This function will panic if anything it resolves refers to something that doesn't exist
+    pub fn resolve_package(&mut self, hash: impl Hashable) -> Option<&Package> {
+        let index = *self.package_lookup.get(&hash.to_hash())?;
+        self.expect_package_resolved(index);
+        Some(&self.packages[index])
+    }
+
+    /// Gets a file by hash, resolving it first if it has not been resolved yet
+    ///
+    /// ### Arguments
+    /// * `hash` - The hash of the file
+    ///
+    /// ### Returns
+    /// * `Some(_)` - The resolved path of the file
+    /// * `None` - There is no file with that hash
+    ///
+    /// ### Notes
+    /// Only the path, its link, and the infos reachable from them are resolved.
+    ///
+    /// ### Panicking
+    /// This function will panic if anything it resolves refers to something that doesn't exist
+    pub fn resolve_file(&mut self, hash: impl Hashable) -> Option<&Path> {
+        let index = *self.file_lookup.get(hash.to_hash())?;
+        self.expect_path_resolved(index);
+        Some(&self.paths[index])
+    }
+
+    /// Gets a file by hash, resolving it first if it has not been resolved yet, without
+    /// panicking on unexpected data
+    ///
+    /// ### Returns
+    /// * `Ok(Some(_))` - The resolved path of the file
+    /// * `Ok(None)` - There is no file with that hash
+    /// * `Err(_)` - Something the file refers to doesn't exist
+    ///
+    /// ### Notes
+    /// See [`resolve_file`](Self::resolve_file) for what is resolved.
+    pub fn try_resolve_file(&mut self, hash: impl Hashable) -> Result<Option<&Path>, ResolveError> {
+        let index = match self.file_lookup.get(hash.to_hash()) {
+            Some(index) => *index,
+            None => return Ok(None),
+        };
+
+        let offsets = self.resolve_offsets()?;
+        self.resolve_path_at(index, offsets)?;
+        Ok(Some(&self.paths[index]))
+    }
+
+    /// Gets a package by hash, as it is
+    ///
+    /// ### Notes
+    /// This never resolves the package, since lazy resolution needs mutable access. Its
+    /// references can only be followed once the engine has been [resolved](Self::resolve),
+    /// or the package has been through [`resolve_package`](Self::resolve_package)
+    /// or [`get_package_mut`](Self::get_package_mut).
+    pub fn get_package(&self, hash: impl Hashable) -> Option<&Package> {
+        self.package_lookup
+            .get(&hash.to_hash())
+            .map(|index| &self.packages[*index])
+    }
+
+    /// Gets a package by hash, resolving it first if it has not been resolved yet
+    ///
+    /// ### Notes
+    /// See [`resolve_package`](Self::resolve_package) for what is resolved.
+    pub fn get_package_mut(&mut self, hash: impl Hashable) -> Option<&mut Package> {
+        let index = *self.package_lookup.get(&hash.to_hash())?;
+        self.expect_package_resolved(index);
+        Some(&mut self.packages[index])
+    }
+
+    /// Gets a file by hash, as it is
+    ///
+    /// ### Notes
+    /// This never resolves the file, since lazy resolution needs mutable access. Its
+    /// references can only be followed once the engine has been [resolved](Self::resolve),
+    /// or the file has been through [`resolve_file`](Self::resolve_file)
+    /// or [`get_file_mut`](Self::get_file_mut).
+    pub fn get_file(&self, hash: impl Hashable) -> Option<&Path> {
+        self.file_lookup
+            .get(hash.to_hash())
+            .map(|index| &self.paths[*index])
+    }
+
+    /// Gets a file by hash, resolving it first if it has not been resolved yet
+    ///
+    /// ### Notes
+    /// See [`resolve_file`](Self::resolve_file) for what is resolved.
+    pub fn get_file_mut(&mut self, hash: impl Hashable) -> Option<&mut Path> {
+        let index = *self.file_lookup.get(hash.to_hash())?;
+        self.expect_path_resolved(index);
+        Some(&mut self.paths[index])
+    }
+
+    pub fn reorganize(self) -> Self {
+        PackagedWriter::from_engine(self).into_engine()
+    }
+
     pub fn has_file(&self, hash: impl Hashable) -> bool {
         self.get_file(hash).is_some()
     }
@@ -257,59 +632,279 @@ impl PackagedEngine {
         self.get_package(hash).is_some()
     }
 
-    pub fn add_file(&mut self, file: impl AsRef<str>, package: impl Hashable) -> TableCell<Info> {
+    pub fn add_file(&mut self, file: impl AsRef<str>, package: impl Hashable) -> TableIndex<Info> {
         let file = file.as_ref();
         if self.has_file(file) {
             panic!("File {} already exists!", file);
         }
 
-        let package = if let Some(cell) = self.package_lookup.get(&package.to_hash()) {
-            cell
+        let package = if let Some(index) = self.package_lookup.get(&package.to_hash()) {
+            *index
         } else {
             panic!("Package does not exist!")
         };
 
+        let group = self.packages[package].groups.get(0);
+        let metadata = self.metadatas.push(Metadata::new());
+
         let mut descriptor = Descriptor::new();
-        descriptor.set_metadata(TableCell::new(Metadata::new()));
-        descriptor.set_group(package.get().groups.cells()[0].clone());
+        descriptor.set_metadata(metadata);
+        descriptor.set_group(group);
 
-        package.get().groups.cells()[0]
-            .get_mut()
-            .metadatas_mut()
-            .push(descriptor.raw_metadata().clone());
+        self.groups[group].metadatas_mut().push(metadata);
 
         let mut info = Info::new();
 
-        info.descriptors.replace(vec![TableCell::new(descriptor)]);
+        info.descriptors
+            .replace([self.descriptors.push(descriptor)]);
+
+        // The link and the info point to each other, so the info's index
+        // has to be known before it is actually added to the table
+        let info_index = self.infos.next_index();
 
-        let link = TableCell::new(Link::new());
+        let mut link = Link::new();
+        link.set_package(package);
+        link.set_info(info_index);
 
-        link.get_mut().set_package(package.clone());
-        info.set_link(link.clone());
+        let link = self.links.push(link);
+        info.set_link(link);
 
         let mut path = Path::from_str(file).unwrap();
-        path.set_link(link.clone());
+        path.set_link(link);
 
         info.is_graphics_archive = path.has_graphics_archive_extension();
         info.is_regular_file = !info.is_graphics_archive;
 
-        info.set_path(TableCell::new(path));
+        let full_path = path.full_path;
+        let path = self.paths.push(path);
+        info.set_path(path);
 
-        self.file_lookup
-            .insert(info.path().full_path, info.raw_path().clone());
+        self.file_lookup.insert(full_path, path);
 
-        let info = TableCell::new(info);
+        let info = self.infos.push(info);
+        self.packages[package].infos.push(info);
+        info
+    }
+
+    /// Adds a new package with no files
+    ///
+    /// ### Arguments
+    /// * `package` - The full path of the package
+    /// * `lifetime` - The lifetime of the package
+    /// * `variants` - Whether the package has a group for every locale or region
+    ///
+    /// ### Returns
+    /// The index of the new package
+    ///
+    /// ### Panicking
+    /// * The package already exists
+    /// * The package's path has no parent or name
+    pub fn add_package(
+        &mut self,
+        package: impl AsRef<str>,
+        lifetime: impl Hashable,
+        variants: VariantKind,
+    ) -> TableIndex<Package> {
+        let package = package.as_ref();
+        if self.has_package(package) {
+            panic!("Package {} already exists!", package);
+        }
+
+        let mut new_package = Package::from_str(package).unwrap();
+        new_package.lifetime = lifetime.to_hash();
+        new_package.is_localized = variants == VariantKind::Localized;
+        new_package.is_regional = variants == VariantKind::Regional;
+
+        // The shared group comes first, followed by one group for each variant
+        new_package
+            .groups
+            .replace((0..=variants.variant_count()).map(|_| self.groups.push(Group::new())));
+
+        let full_path = new_package.full_path;
+        let index = self.packages.push(new_package);
+        self.package_lookup.insert(full_path, index);
+        index
+    }
+
+    /// Makes one package a child of another, so that it is loaded along with it
+    ///
+    /// ### Arguments
+    /// * `package` - The hash of the parent package
+    /// * `child` - The hash of the child package
+    ///
+    /// ### Panicking
+    /// Panics if either package does not exist
+    pub fn add_child_package(
+        &mut self,
+        package: impl Hashable,
+        child: impl Hashable,
+    ) -> TableIndex<ChildPackage> {
+        let package = if let Some(index) = self.package_lookup.get(&package.to_hash()) {
+            *index
+        } else {
+            panic!("Package does not exist!")
+        };
+
+        let child = child.to_hash();
+        let child_index = if let Some(index) = self.package_lookup.get(&child) {
+            *index
+        } else {
+            panic!("Child package does not exist!")
+        };
+
+        let child_package = self
+            .child_packages
+            .push(ChildPackage::new(child, child_index));
+        self.packages[package].child_packages.push(child_package);
+        child_package
+    }
+
+    /// Gives a file a variant for every locale or region of the package which owns it
+    ///
+    /// ### Arguments
+    /// * `info` - The info of the file, as returned by [`add_file`](Self::add_file)
+    ///
+    /// ### Returns
+    /// The metadata for each variant, starting with the shared data the file already had
+    ///
+    /// ### Panicking
+    /// * The file already has variants
+    /// * The package which owns the file is neither localized nor regional
+    pub fn localize_file(&mut self, info: TableIndex<Info>) -> Vec<TableIndex<Metadata>> {
+        let package = &self.packages[self.links[self.infos[info].link()].package()];
+        let (is_localized, is_regional) = (package.is_localized, package.is_regional);
+        if !is_localized && !is_regional {
+            panic!("Package does not have localized or regional groups!");
+        }
+
+        let groups: Vec<_> = package.groups.iter().collect();
+
+        let info_ref = &self.infos[info];
+        if info_ref.is_localized || info_ref.is_regional {
+            panic!("File already has variants!");
+        }
+
+        let base = info_ref.descriptors.get(0);
+        let mut descriptors = vec![base];
+        let mut metadatas = vec![self.descriptors[base].metadata()];
+
+        for group in groups.into_iter().skip(1) {
+            let metadata = self.metadatas.push(Metadata::new());
+            self.groups[group].metadatas_mut().push(metadata);
+
+            let mut descriptor = Descriptor::new();
+            descriptor.set_metadata(metadata);
+            descriptor.set_group(group);
+
+            descriptors.push(self.descriptors.push(descriptor));
+            metadatas.push(metadata);
+        }
+
+        let info = &mut self.infos[info];
+        info.descriptors.replace(descriptors);
+        info.is_localized = is_localized;
+        info.is_regional = is_regional;
+        metadatas
+    }
+
+    /// Shares a file that is owned by one package with another package
+    ///
+    /// ### Arguments
+    /// * `file` - The hash of the file to share
+    /// * `package` - The hash of the package to share it with
+    ///
+    /// ### Returns
+    /// The package's new info for the file
+    ///
+    /// ### Panicking
+    /// Panics if the file or the package does not exist
+    ///
+    /// ### Notes
+    /// The new info has no data of its own. Each of its descriptors redirects to the
+    /// data of the info which owns the file, at the same variant.
+    pub fn add_shared_file(
+        &mut self,
+        file: impl Hashable,
+        package: impl Hashable,
+    ) -> TableIndex<Info> {
+        let path = if let Some(path) = self.file_lookup.get(file.to_hash()) {
+            *path
+        } else {
+            panic!("File does not exist!")
+        };
 
-        link.get_mut().set_info(info.clone());
-        package.get_mut().infos.push(info.clone());
+        let package = if let Some(index) = self.package_lookup.get(&package.to_hash()) {
+            *index
+        } else {
+            panic!("Package does not exist!")
+        };
+
+        let link = self.paths[path].link();
+        let owner = &self.infos[self.links[link].info()];
+
+        let mut info = Info::new();
+        info.is_shared = true;
+        info.is_localized = owner.is_localized;
+        info.is_regional = owner.is_regional;
+        info.is_graphics_archive = owner.is_graphics_archive;
+        info.is_regular_file = owner.is_regular_file;
+
+        let groups = &self.packages[package].groups;
+        let groups: Vec<_> = (0..owner.descriptors.len())
+            .map(|position| groups.get(position.min(groups.len() - 1)))
+            .collect();
+
+        info.descriptors.replace(groups.into_iter().map(|group| {
+            let mut descriptor = Descriptor::new();
+            descriptor.set_group(group);
+            descriptor.load_args = DescriptorLoadArguments::Unowned {
+                link: TableReference::Resolved(link),
+            };
+            self.descriptors.push(descriptor)
+        }));
+
+        info.set_path(path);
+        info.set_link(link);
+
+        let info = self.infos.push(info);
+        self.packages[package].infos.push(info);
         info
     }
 }
 
+/// The mappings from the old indices of each packaged table to their reorganized ones
+pub(crate) struct PackagedRemap {
+    pub packages: TableRemap<Package>,
+    pub child_packages: TableRemap<ChildPackage>,
+    pub groups: TableRemap<Group>,
+    pub paths: TableRemap<Path>,
+    pub links: TableRemap<Link>,
+    pub infos: TableRemap<Info>,
+    pub descriptors: TableRemap<Descriptor>,
+    pub metadatas: TableRemap<Metadata>,
+    pub patches: TableRemap<Patch>,
+    pub versioned_files: TableRemap<VersionedFile>,
+}
+
+/// The indices referenced by entries of the packaged tables, for each table
+#[derive(Default)]
+pub(crate) struct PackagedReferences {
+    pub packages: Vec<TableIndex<Package>>,
+    pub child_packages: Vec<TableIndex<ChildPackage>>,
+    pub groups: Vec<TableIndex<Group>>,
+    pub paths: Vec<TableIndex<Path>>,
+    pub links: Vec<TableIndex<Link>>,
+    pub infos: Vec<TableIndex<Info>>,
+    pub descriptors: Vec<TableIndex<Descriptor>>,
+    pub metadatas: Vec<TableIndex<Metadata>>,
+    pub patches: Vec<TableIndex<Patch>>,
+    pub versioned_files: Vec<TableIndex<VersionedFile>>,
+}
+
 pub struct PackagedWriter {
     version: Version,
-    pub(crate) package_lookup: BTreeMap<Hash40, TableCell<Package>>,
-    pub(crate) file_lookup: BucketMap<TableCell<Path>>,
+    pub(crate) package_lookup: HashLookup<TableIndex<Package>>,
+    pub(crate) file_lookup: BucketMap<TableIndex<Path>>,
     pub(crate) packages: TableMaker<Package>,
     pub(crate) child_packages: TableMaker<ChildPackage>,
     pub(crate) groups: TableMaker<Group>,
@@ -326,133 +921,209 @@ pub struct PackagedWriter {
 }
 
 impl PackagedWriter {
-    fn push_package(&mut self, package: &TableCell<Package>) {
-        self.packages.push(package.clone());
-        let package = package.get();
-        for group in package.groups.cells().iter() {
+    fn push_package(&mut self, package: TableIndex<Package>) {
+        self.packages.push(package);
+        let package = &self.packages[package];
+        let groups = package.groups.indices().clone();
+        let child_packages = package.child_packages.indices().clone();
+        let infos = package.infos.indices().clone();
+
+        for group in groups.iter() {
             self.push_group(group);
         }
 
-        for child_package in package.child_packages.cells().iter() {
+        for child_package in child_packages.iter() {
             self.push_child_package(child_package);
         }
 
-        for info in package.infos.cells().iter() {
+        for info in infos.iter() {
             self.push_info(info, false, false);
         }
     }
 
-    fn push_child_package(&mut self, child_package: &TableCell<ChildPackage>) {
-        self.child_packages.push(child_package.clone());
+    fn push_child_package(&mut self, child_package: TableIndex<ChildPackage>) {
+        self.child_packages.push(child_package);
     }
 
-    fn push_group(&mut self, group: &TableCell<Group>) {
-        self.groups.push(group.clone());
-        let group = group.get();
+    fn push_group(&mut self, group: TableIndex<Group>) {
+        self.groups.push(group);
+        let group = &self.groups[group];
 
         if group.is_metadata_group() {
-            for metadata in group.metadatas().cells().iter() {
+            let metadatas = group.metadatas().indices().clone();
+            for metadata in metadatas.iter() {
                 if !self.metadatas.has_cell(metadata) {
                     self.push_metadata(metadata);
                 }
             }
         } else {
             let is_versioned = group.is_version_group();
-            for info in group.infos().cells().iter() {
+            let infos = group.infos().indices().clone();
+            for info in infos.iter() {
                 self.push_info(info, true, is_versioned);
             }
         }
     }
 
-    fn push_path(&mut self, path: &TableCell<Path>) {
-        self.paths.push(path.clone());
+    fn push_path(&mut self, path: TableIndex<Path>) {
+        self.paths.push(path);
     }
 
-    fn push_link(&mut self, link: &TableCell<Link>) {
-        self.links.push(link.clone());
+    fn push_link(&mut self, link: TableIndex<Link>) {
+        self.links.push(link);
     }
 
     fn push_info(
         &mut self,
-        info: &TableCell<Info>,
+        info: TableIndex<Info>,
         is_info_group_info: bool,
         is_version_group_info: bool,
     ) {
-        self.infos.push(info.clone());
-        let info = info.get();
-        for descriptor in info.descriptors.cells().iter() {
+        self.infos.push(info);
+        let descriptors = self.infos[info].descriptors.indices().clone();
+        for descriptor in descriptors.iter() {
             self.push_descriptor(descriptor, is_info_group_info, is_version_group_info);
         }
 
+        let info = &self.infos[info];
+        let (is_shared, link, path) = (info.is_shared, info.link(), info.path());
+
         if is_info_group_info {
-            if !info.is_shared && !self.links.has_cell(info.raw_link()) {
-                self.push_link(info.raw_link())
+            if !is_shared && !self.links.has_cell(link) {
+                self.push_link(link)
             }
         } else {
-            if !info.is_shared
+            if !is_shared
                 && !matches!(
-                    info.descriptors.cells()[0].get().load_args,
+                    self.descriptors[descriptors.get(0)].load_args,
                     DescriptorLoadArguments::PackageSkip { .. }
                 )
-                && !self.links.has_cell(info.raw_link())
+                && !self.links.has_cell(link)
             {
-                self.push_link(info.raw_link())
+                self.push_link(link)
             }
 
-            if !self.paths.has_cell(info.raw_path()) {
-                self.push_path(info.raw_path());
+            if !self.paths.has_cell(path) {
+                self.push_path(path);
             }
         }
     }
 
     fn push_descriptor(
         &mut self,
-        descriptor: &TableCell<Descriptor>,
+        descriptor: TableIndex<Descriptor>,
         is_info_group_descriptor: bool,
         is_version_group_descriptor: bool,
     ) {
-        self.descriptors.push(descriptor.clone());
-        let descriptor = descriptor.get();
+        self.descriptors.push(descriptor);
+        let descriptor = &self.descriptors[descriptor];
 
-        if is_version_group_descriptor {
+        let metadata = if is_version_group_descriptor {
             match &descriptor.load_args {
                 DescriptorLoadArguments::Owned { patch }
                     if self.patches.has_cell(patch.patch())
                         && descriptor.has_metadata()
-                        && !self.metadatas.has_cell(descriptor.raw_metadata()) =>
+                        && !self.metadatas.has_cell(descriptor.metadata()) =>
                 {
-                    self.push_metadata(descriptor.raw_metadata());
+                    Some(descriptor.metadata())
                 }
-                _ => {}
+                _ => None,
             }
         } else if is_info_group_descriptor {
-            self.push_metadata(descriptor.raw_metadata());
+            Some(descriptor.metadata())
+        } else {
+            None
+        };
+
+        if let Some(metadata) = metadata {
+            self.push_metadata(metadata);
         }
     }
 
-    fn push_metadata(&mut self, metadata: &TableCell<Metadata>) {
-        self.metadatas.push(metadata.clone());
+    fn push_metadata(&mut self, metadata: TableIndex<Metadata>) {
+        self.metadatas.push(metadata);
     }
 
-    fn push_patch(&mut self, patch: &TableCell<Patch>) {
-        self.patches.push(patch.clone());
-        for file in patch.get().versioned_files.cells().iter() {
+    fn push_patch(&mut self, patch: TableIndex<Patch>) {
+        self.patches.push(patch);
+        let patch = &self.patches[patch];
+        let versioned_files = patch.versioned_files.indices().clone();
+        let infos = patch.infos.indices().clone();
+        let group = patch.group();
+
+        for file in versioned_files.iter() {
             self.push_versioned_file(file);
         }
-        self.push_group(patch.get().raw_group());
-        for info in patch
-            .get()
-            .infos
-            .cells()
-            .iter()
-            .skip(patch.get().group().infos().len())
-        {
+        self.push_group(group);
+        for info in infos.iter().skip(self.groups[group].infos().len()) {
             self.push_info(info, true, true);
         }
     }
 
-    fn push_versioned_file(&mut self, versioned_file: &TableCell<VersionedFile>) {
-        self.versioned_files.push(versioned_file.clone());
+    fn push_versioned_file(&mut self, versioned_file: TableIndex<VersionedFile>) {
+        self.versioned_files.push(versioned_file);
+    }
+
+    /// Pushes every entry which is referenced by an entry in the new tables, but was
+    /// not reached while walking the packages, groups and patches
+    ///
+    /// Without this, those references (such as the metadata of some versioned files)
+    /// would be remapped to the invalid index. The entries are pushed after everything
+    /// else, until no pushed entry references one which is missing.
+    fn push_referenced(&mut self) {
+        // Every table but the metadatas can reference other entries
+        macro_rules! push_referenced {
+            ([$($source:ident),*], [$($target:ident),*]) => {{
+                $(let mut $source = 0;)*
+                loop {
+                    let mut refs = PackagedReferences::default();
+                    $(
+                        let pushed = self.$source.pushed();
+                        for index in &pushed[$source..] {
+                            self.$source[*index].references(&mut refs);
+                        }
+                        $source = pushed.len();
+                    )*
+
+                    let mut pushed_any = false;
+                    $(
+                        for index in refs.$target {
+                            pushed_any |= self.$target.push_missing(index);
+                        }
+                    )*
+
+                    if !pushed_any {
+                        break;
+                    }
+                }
+            }};
+        }
+
+        push_referenced!(
+            [
+                packages,
+                child_packages,
+                groups,
+                paths,
+                links,
+                infos,
+                descriptors,
+                patches,
+                versioned_files
+            ],
+            [
+                packages,
+                child_packages,
+                groups,
+                paths,
+                links,
+                infos,
+                descriptors,
+                metadatas,
+                patches,
+                versioned_files
+            ]
+        );
     }
 
     pub fn from_engine(engine: PackagedEngine) -> Self {
@@ -461,59 +1132,161 @@ impl PackagedWriter {
             package_lookup,
             file_lookup,
             packages,
+            child_packages,
             groups,
+            paths,
+            links,
+            infos,
+            descriptors,
+            metadatas,
             patches,
+            versioned_files,
             ..
         } = engine;
 
+        let package_indices: Vec<_> = packages.indices().collect();
+        let info_groups: Vec<_> = groups
+            .iter_indexed()
+            .filter(|(_, group)| group.is_info_group() && !group.is_version_group())
+            .map(|(index, _)| index)
+            .collect();
+        let patch_indices: Vec<_> = patches.indices().collect();
+
         let mut this = Self {
             version,
             package_lookup,
             file_lookup,
 
-            packages: TableMaker::new(),
-            child_packages: TableMaker::new(),
-            groups: TableMaker::new(),
-            paths: TableMaker::new(),
-            links: TableMaker::new(),
-            infos: TableMaker::new(),
-            descriptors: TableMaker::new(),
-            metadatas: TableMaker::new(),
-            patches: TableMaker::new(),
-            versioned_files: TableMaker::new(),
+            packages: TableMaker::new(packages),
+            child_packages: TableMaker::new(child_packages),
+            groups: TableMaker::new(groups),
+            paths: TableMaker::new(paths),
+            links: TableMaker::new(links),
+            infos: TableMaker::new(infos),
+            descriptors: TableMaker::new(descriptors),
+            metadatas: TableMaker::new(metadatas),
+            patches: TableMaker::new(patches),
+            versioned_files: TableMaker::new(versioned_files),
 
             last_patch_files_start: 0,
             info_group_info_start: 0,
         };
 
-        for package in packages {
-            this.push_package(&package);
+        for package in package_indices {
+            this.push_package(package);
         }
 
-        for group in groups {
-            if group.get().is_info_group() && !group.get().is_version_group() {
-                this.push_group(&group);
-                if this.info_group_info_start == 0 {
-                    this.info_group_info_start =
-                        this.infos.get_index(&group.get().infos().cells()[0]) as usize;
-                }
+        for group in info_groups {
+            this.push_group(group);
+            if this.info_group_info_start == 0 {
+                this.info_group_info_start =
+                    this.infos.get_index(this.groups[group].infos().get(0)) as usize;
             }
         }
 
-        for patch in patches.iter() {
-            this.push_patch(patch);
+        for patch in patch_indices.iter() {
+            this.push_patch(*patch);
         }
 
-        if let Some(last_patch) = patches.last() {
+        if let Some(last_patch) = patch_indices.last() {
             this.last_patch_files_start = this
                 .versioned_files
-                .get_index(&last_patch.get().versioned_files.cells()[0])
+                .get_index(this.patches[*last_patch].versioned_files.get(0))
                 as usize;
         }
 
         this
     }
 
+    /// Consumes the writer, building a new engine out of the reorganized tables
+    ///
+    /// ### Returns
+    /// The reorganized engine, with every reference pointing into the new tables
+    ///
+    /// ### Notes
+    /// Entries which are still referenced by a kept entry are kept as well, after
+    /// the rest of their table. Any other entries which could not be reached while
+    /// reorganizing are dropped, along with their lookups.
+    pub(crate) fn into_engine(mut self) -> PackagedEngine {
+        self.push_referenced();
+
+        let (mut packages, packages_remap) = self.packages.into_parts();
+        let (mut child_packages, child_packages_remap) = self.child_packages.into_parts();
+        let (mut groups, groups_remap) = self.groups.into_parts();
+        let (mut paths, paths_remap) = self.paths.into_parts();
+        let (mut links, links_remap) = self.links.into_parts();
+        let (mut infos, infos_remap) = self.infos.into_parts();
+        let (mut descriptors, descriptors_remap) = self.descriptors.into_parts();
+        let (metadatas, metadatas_remap) = self.metadatas.into_parts();
+        let (mut patches, patches_remap) = self.patches.into_parts();
+        let (mut versioned_files, versioned_files_remap) = self.versioned_files.into_parts();
+
+        let remap = PackagedRemap {
+            packages: packages_remap,
+            child_packages: child_packages_remap,
+            groups: groups_remap,
+            paths: paths_remap,
+            links: links_remap,
+            infos: infos_remap,
+            descriptors: descriptors_remap,
+            metadatas: metadatas_remap,
+            patches: patches_remap,
+            versioned_files: versioned_files_remap,
+        };
+
+        packages
+            .iter_mut()
+            .for_each(|package| package.remap(&remap));
+        child_packages
+            .iter_mut()
+            .for_each(|child| child.remap(&remap));
+        groups.iter_mut().for_each(|group| group.remap(&remap));
+        paths.iter_mut().for_each(|path| path.remap(&remap));
+        links.iter_mut().for_each(|link| link.remap(&remap));
+        infos.iter_mut().for_each(|info| info.remap(&remap));
+        descriptors
+            .iter_mut()
+            .for_each(|descriptor| descriptor.remap(&remap));
+        patches.iter_mut().for_each(|patch| patch.remap(&remap));
+        versioned_files
+            .iter_mut()
+            .for_each(|file| file.remap(&remap));
+
+        let package_lookup = self
+            .package_lookup
+            .into_iter()
+            .map(|(hash, package)| (hash, remap.packages.get(package)))
+            .filter(|(_, package)| package.is_valid())
+            .collect();
+
+        let mut file_lookup = self.file_lookup;
+        file_lookup.retain(|_, path| {
+            *path = remap.paths.get(*path);
+            path.is_valid()
+        });
+
+        PackagedEngine {
+            version: self.version,
+            package_lookup,
+            file_lookup,
+
+            packages,
+            child_packages,
+            groups,
+
+            paths,
+            links,
+            infos,
+            descriptors,
+            metadatas,
+
+            patches,
+            versioned_files,
+
+            resolve_offsets: None,
+        }
+    }
+
     pub fn to_directory(self, path: impl AsRef<std::path::Path>) -> binrw::BinResult<()> {
         let path = path.as_ref();
         let this = Rc::new(self);
@@ -553,8 +1326,7 @@ impl PackagedWriter {
         for (count, info) in this.infos.iter().enumerate() {
             if count == this.info_group_info_start {
                 info_group_descriptor_start =
-                    this.descriptors
-                        .get_index(&info.get().descriptors.cells()[0]) as usize;
+                    this.descriptors.get_index(info.descriptors.get(0)) as usize;
             }
             info.write_with_args(&mut infos, Rc::clone(&this))?;
         }
@@ -615,15 +1387,14 @@ impl PackagedWriter {
 
         let mut package_keys = std::io::Cursor::new(vec![]);
 
-        for (hash, package) in package_lookup {
-            HashKey::new(hash, packages.get_index(&package) as usize)
+        for (hash, package) in package_lookup.iter_sorted() {
+            HashKey::new(*hash, packages.get_index(*package) as usize)
                 .write_to(&mut package_keys)?;
         }
 
         let mut path_buckets = std::io::Cursor::new(vec![]);
-        let file_lookup = file_lookup.into_inner();
         let mut total = 0;
-        for bucket in file_lookup.iter() {
+        for bucket in file_lookup.buckets() {
             total.write_to(&mut path_buckets)?;
             total += bucket.len() as u32;
             total.write_to(&mut path_buckets)?;
@@ -631,11 +1402,8 @@ impl PackagedWriter {
 
         let mut path_keys = std::io::Cursor::new(vec![]);
 
-        for (key, path) in file_lookup
-            .into_iter()
-            .flat_map(|bucket| bucket.into_iter())
-        {
-            HashKey::new(key, paths.get_index(&path) as usize).write_to(&mut path_keys)?;
+        for (key, path) in file_lookup.iter_sorted() {
+            HashKey::new(*key, paths.get_index(*path) as usize).write_to(&mut path_keys)?;
         }
 
         std::fs::write(path.join("package_keys.bin"), package_keys.into_inner())?;
@@ -662,8 +1430,8 @@ impl PackagedWriter {
             (bucket.len() as u32).write_to(writer)?;
         }
 
-        for (hash, cell) in this.file_lookup.iter() {
-            HashKey::new(*hash, this.paths.get_index(cell) as usize).write_to(writer)?;
+        for (hash, cell) in this.file_lookup.iter_sorted() {
+            HashKey::new(*hash, this.paths.get_index(*cell) as usize).write_to(writer)?;
         }
 
         for path in this.paths.iter() {
@@ -674,8 +1442,8 @@ impl PackagedWriter {
             link.write_with_args(writer, Rc::clone(&this))?;
         }
 
-        for (hash, cell) in this.package_lookup.iter() {
-            HashKey::new(*hash, this.packages.get_index(cell) as usize).write_to(writer)?;
+        for (hash, cell) in this.package_lookup.iter_sorted() {
+            HashKey::new(*hash, this.packages.get_index(*cell) as usize).write_to(writer)?;
         }
 
         for package in this.packages.iter() {
@@ -698,24 +1466,18 @@ impl PackagedWriter {
         };
 
         for (count, group) in this.groups.iter().enumerate() {
-            if output.packaged_info_len == 0 && group.get().is_info_group() {
-                let group_ = group.get();
-                let info = &group_.infos().cells()[0];
-                let info_ = info.get();
-                let desc = &info_.descriptors.cells()[0];
-                let desc_ = desc.get();
-                let data = desc_.raw_metadata();
+            if output.packaged_info_len == 0 && group.is_info_group() {
+                let info = group.infos().get(0);
+                let desc = this.infos[info].descriptors.get(0);
+                let data = this.descriptors[desc].metadata();
                 output.packaged_info_len = this.infos.get_index(info) as usize;
                 output.packaged_descriptor_len = this.descriptors.get_index(desc) as usize;
                 output.packaged_data_len = this.metadatas.get_index(data) as usize;
                 output.metadata_group_len = count;
-            } else if output.group_info_len == 0 && group.get().is_version_group() {
-                let group_ = group.get();
-                let info = &group_.infos().cells()[0];
-                let info_ = info.get();
-                let desc = &info_.descriptors.cells()[0];
-                let desc_ = desc.get();
-                let data = desc_.raw_metadata();
+            } else if output.group_info_len == 0 && group.is_version_group() {
+                let info = group.infos().get(0);
+                let desc = this.infos[info].descriptors.get(0);
+                let data = this.descriptors[desc].metadata();
                 output.group_info_len =
                     this.infos.get_index(info) as usize - output.packaged_info_len;
                 output.group_descriptor_len =
@@ -790,3 +1552,173 @@ pub struct ToMemoryResults {
     pub info_group_len: usize,
     pub version_group_len: usize,
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    pub(super) fn build_engine() -> PackagedEngine {
+        let mut engine = PackagedEngine::new(Version::new(13, 0, 1));
+        engine.add_package("fighter/mario/c00", "fighter", VariantKind::None);
+        engine.add_package("ui/message", "ui", VariantKind::Localized);
+        engine.add_child_package("fighter/mario/c00", "ui/message");
+
+        for (index, file) in [
+            "fighter/mario/model/body/c00/model.numdlb",
+            "fighter/mario/motion/body/c00/a00wait1.nuanmb",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let info = engine.add_file(file, "fighter/mario/c00");
+            let descriptor = engine.infos[info].descriptors.get(0);
+            let metadata = engine.descriptors[descriptor].metadata();
+            engine.metadatas[metadata].decompressed_size = 0x100 * (index + 1);
+        }
+
+        let info = engine.add_file("ui/message/msg_name.msbt", "ui/message");
+        for (index, metadata) in engine.localize_file(info).into_iter().enumerate() {
+            engine.metadatas[metadata].decompressed_size = index;
+        }
+
+        engine.add_shared_file("ui/message/msg_name.msbt", "fighter/mario/c00");
+        engine
+    }
+
+    /// Adds a patch which versions `files`, each with a copy of its current info
+    ///
+    /// Each file is `(path, changed_this_patch)`
+    pub(crate) fn add_patch(
+        engine: &mut PackagedEngine,
+        version: Version,
+        files: &[(&str, bool)],
+    ) -> TableIndex<Patch> {
+        let options = binrw::ReadOptions::new(binrw::Endian::Little);
+        let file_start = engine.versioned_files.len();
+        let info_start = engine.infos.len();
+
+        for (file, changed) in files {
+            let link = engine.get_file(*file).unwrap().link();
+            let info = engine
+                .infos
+                .push(engine.infos[engine.links[link].info()].clone());
+
+            let mut bytes = vec![];
+            let key = file.to_hash().0 | (!changed as u64) << 40;
+            bytes.extend_from_slice(&key.to_le_bytes());
+            for value in [info.index() as u32, 0, link.index() as u32] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+
+            let mut versioned_file =
+                VersionedFile::read_options(&mut Cursor::new(bytes), &options, ()).unwrap();
+            versioned_file
+                .resolve(&engine.links, &engine.infos)
+                .unwrap();
+            engine.versioned_files.push(versioned_file);
+        }
+
+        let mut bytes = vec![version.patch as u8, version.minor as u8];
+        bytes.extend_from_slice(&(version.major as u16).to_le_bytes());
+        let changed = files.iter().filter(|(_, changed)| *changed).count();
+        for value in [files.len(), 0, file_start, 0, files.len(), 0, changed] {
+            bytes.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+
+        let mut patch = Patch::read_options(&mut Cursor::new(bytes), &options, ()).unwrap();
+        patch
+            .resolve(
+                &engine.versioned_files,
+                &engine.infos,
+                &engine.groups,
+                0,
+                info_start - file_start,
+            )
+            .unwrap();
+        engine.patches.push(patch)
+    }
+
+    fn write(engine: PackagedEngine) -> Vec<u8> {
+        let mut writer = Cursor::new(vec![]);
+        PackagedWriter::from_engine(engine)
+            .to_memory(&mut writer)
+            .unwrap();
+        writer.into_inner()
+    }
+
+    fn file_metadata(engine: &PackagedEngine, file: &str) -> TableIndex<Metadata> {
+        let link = engine.get_file(file).unwrap().link();
+        let descriptor = engine.infos[engine.links[link].info()].descriptors.get(0);
+        engine.descriptors[descriptor].metadata()
+    }
+
+    #[test]
+    fn lazy_resolution_writes_the_same_tables() {
+        let directory = std::env::temp_dir().join(format!("snarc-lazy-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        PackagedWriter::from_engine(build_engine())
+            .to_directory(&directory)
+            .unwrap();
+
+        let mut eager = PackagedEngine::from_directory(&directory).unwrap();
+        let mut lazy = PackagedEngine::from_directory(&directory).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let package = lazy.resolve_package("ui/message").unwrap();
+        assert_eq!(package.infos.len(), 1);
+        assert!(lazy
+            .resolve_file("fighter/mario/model/body/c00/model.numdlb")
+            .is_some());
+        assert!(lazy
+            .try_resolve_file("fighter/mario/motion/body/c00/a00wait1.nuanmb")
+            .unwrap()
+            .is_some());
+        assert!(lazy
+            .try_resolve_file("fighter/mario/lazy_missing.nuanmb")
+            .unwrap()
+            .is_none());
+        assert!(!lazy
+            .get_package("fighter/mario/c00")
+            .unwrap()
+            .infos
+            .is_resolved());
+
+        eager.resolve();
+        lazy.resolve();
+        assert_eq!(write(eager), write(lazy));
+    }
+
+    #[test]
+    fn reorganize_writes_the_same_tables() {
+        let reorganized = build_engine().reorganize();
+        assert_eq!(write(build_engine()), write(reorganized));
+    }
+
+    #[test]
+    fn reorganize_keeps_referenced_entries() {
+        let file = "fighter/mario/motion/body/c00/a00wait1.nuanmb";
+        let mut engine = build_engine();
+
+        // Leave the file's metadata referenced by its descriptor, but not by any group
+        let metadata = file_metadata(&engine, file);
+        let group = engine
+            .get_package("fighter/mario/c00")
+            .unwrap()
+            .groups
+            .get(0);
+        let kept: Vec<_> = engine.groups[group]
+            .metadatas()
+            .iter()
+            .filter(|index| *index != metadata)
+            .collect();
+        engine.groups[group].metadatas_mut().replace(kept);
+        engine.metadatas[metadata].decompressed_size = 0x1234;
+
+        let engine = engine.reorganize();
+        let metadata = file_metadata(&engine, file);
+        assert!(metadata.is_valid());
+        assert_eq!(engine.metadatas[metadata].decompressed_size, 0x1234);
+    }
+}
@@ -0,0 +1,389 @@
+use std::ops::{Bound, RangeBounds};
+
+use hash40::Hash40;
+
+use crate::{engines::table::TableIndex, Hashable};
+
+use super::{
+    types::{Descriptor, DescriptorLoadArguments, Info, Metadata, Path},
+    PackagedEngine,
+};
+
+/// The variant of a [`DescriptorLoadArguments`], without its data
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LoadArgumentsKind {
+    Unowned,
+    Owned,
+    PackageSkip,
+    Unknown,
+    SharedButOwned,
+    UnsupportedRegion,
+}
+
+impl From<&DescriptorLoadArguments> for LoadArgumentsKind {
+    fn from(args: &DescriptorLoadArguments) -> Self {
+        match args {
+            DescriptorLoadArguments::Unowned { .. } => Self::Unowned,
+            DescriptorLoadArguments::Owned { .. } => Self::Owned,
+            DescriptorLoadArguments::PackageSkip { .. } => Self::PackageSkip,
+            DescriptorLoadArguments::Unknown => Self::Unknown,
+            DescriptorLoadArguments::SharedButOwned { .. } => Self::SharedButOwned,
+            DescriptorLoadArguments::UnsupportedRegion { .. } => Self::UnsupportedRegion,
+        }
+    }
+}
+
+/// A file which matched a [`FileQuery`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FileHandle {
+    /// The path of the file
+    pub path: TableIndex<Path>,
+
+    /// The info which owns the file
+    pub info: TableIndex<Info>,
+
+    /// The first descriptor of the info which matched the query
+    pub descriptor: TableIndex<Descriptor>,
+
+    /// The metadata of the file data that the descriptor refers to, after following any
+    /// unowned, shared or skipped descriptors. `None` if the descriptor has no data.
+    pub metadata: Option<TableIndex<Metadata>>,
+}
+
+/// A builder which filters the files in the packaged filesystem
+///
+/// Created by [`PackagedEngine::query`]. Every filter is optional, and a file matches
+/// when its owning [`Info`] passes the info filters and at least one of its descriptors
+/// passes the descriptor and metadata filters.
+pub struct FileQuery<'a> {
+    engine: &'a PackagedEngine,
+
+    is_graphics_archive: Option<bool>,
+    is_localized: Option<bool>,
+    is_regional: Option<bool>,
+    is_shared: Option<bool>,
+    is_unknown_flag: Option<bool>,
+    extension: Option<Hash40>,
+
+    load_arguments: Option<LoadArgumentsKind>,
+
+    is_compressed: Option<bool>,
+    is_standard_zstd: Option<bool>,
+    is_regional_versioned_data: Option<bool>,
+    is_localized_versioned_data: Option<bool>,
+    compressed_size: (Bound<usize>, Bound<usize>),
+    decompressed_size: (Bound<usize>, Bound<usize>),
+}
+
+/// Checks an optional filter against a value
+fn check(filter: Option<bool>, value: bool) -> bool {
+    filter.iter().all(|filter| *filter == value)
+}
+
+/// Copies the bounds of a range, so that they can be stored
+fn owned_bounds(range: impl RangeBounds<usize>) -> (Bound<usize>, Bound<usize>) {
+    (range.start_bound().cloned(), range.end_bound().cloned())
+}
+
+impl<'a> FileQuery<'a> {
+    /// Filters by [`Info::is_graphics_archive`]
+    pub fn graphics_archive(mut self, value: bool) -> Self {
+        self.is_graphics_archive = Some(value);
+        self
+    }
+
+    /// Filters by [`Info::is_localized`]
+    pub fn localized(mut self, value: bool) -> Self {
+        self.is_localized = Some(value);
+        self
+    }
+
+    /// Filters by [`Info::is_regional`]
+    pub fn regional(mut self, value: bool) -> Self {
+        self.is_regional = Some(value);
+        self
+    }
+
+    /// Filters by [`Info::is_shared`]
+    pub fn shared(mut self, value: bool) -> Self {
+        self.is_shared = Some(value);
+        self
+    }
+
+    /// Filters by [`Info::is_unknown_flag`]
+    pub fn unknown_flag(mut self, value: bool) -> Self {
+        self.is_unknown_flag = Some(value);
+        self
+    }
+
+    /// Filters by the extension of the file's path, with no leading `.`
+    pub fn extension(mut self, extension: impl Hashable) -> Self {
+        self.extension = Some(extension.to_hash());
+        self
+    }
+
+    /// Filters by the variant of [`DescriptorLoadArguments`]
+    pub fn load_arguments(mut self, kind: LoadArgumentsKind) -> Self {
+        self.load_arguments = Some(kind);
+        self
+    }
+
+    /// Filters by [`Metadata::is_compressed`]
+    pub fn compressed(mut self, value: bool) -> Self {
+        self.is_compressed = Some(value);
+        self
+    }
+
+    /// Filters by [`Metadata::is_standard_zstd`]
+    pub fn standard_zstd(mut self, value: bool) -> Self {
+        self.is_standard_zstd = Some(value);
+        self
+    }
+
+    /// Filters by [`Metadata::is_regional_versioned_data`]
+    pub fn regional_versioned_data(mut self, value: bool) -> Self {
+        self.is_regional_versioned_data = Some(value);
+        self
+    }
+
+    /// Filters by [`Metadata::is_localized_versioned_data`]
+    pub fn localized_versioned_data(mut self, value: bool) -> Self {
+        self.is_localized_versioned_data = Some(value);
+        self
+    }
+
+    /// Filters by the size of the file data in the archive
+    pub fn compressed_size(mut self, range: impl RangeBounds<usize>) -> Self {
+        self.compressed_size = owned_bounds(range);
+        self
+    }
+
+    /// Filters by the size of the file data once decompressed
+    pub fn decompressed_size(mut self, range: impl RangeBounds<usize>) -> Self {
+        self.decompressed_size = owned_bounds(range);
+        self
+    }
+
+    /// Checks if any of the metadata filters are set
+    fn has_metadata_filters(&self) -> bool {
+        self.is_compressed.is_some()
+            || self.is_standard_zstd.is_some()
+            || self.is_regional_versioned_data.is_some()
+            || self.is_localized_versioned_data.is_some()
+            || self.compressed_size != (Bound::Unbounded, Bound::Unbounded)
+            || self.decompressed_size != (Bound::Unbounded, Bound::Unbounded)
+    }
+
+    fn matches_info(&self, info: &Info, path: &Path) -> bool {
+        check(self.is_graphics_archive, info.is_graphics_archive)
+            && check(self.is_localized, info.is_localized)
+            && check(self.is_regional, info.is_regional)
+            && check(self.is_shared, info.is_shared)
+            && check(self.is_unknown_flag, info.is_unknown_flag)
+            && self
+                .extension
+                .iter()
+                .all(|extension| *extension == path.extension)
+    }
+
+    fn matches_metadata(&self, metadata: &Metadata) -> bool {
+        check(self.is_compressed, metadata.is_compressed)
+            && check(self.is_standard_zstd, metadata.is_standard_zstd)
+            && check(
+                self.is_regional_versioned_data,
+                metadata.is_regional_versioned_data,
+            )
+            && check(
+                self.is_localized_versioned_data,
+                metadata.is_localized_versioned_data,
+            )
+            && self.compressed_size.contains(&metadata.compressed_size)
+            && self.decompressed_size.contains(&metadata.decompressed_size)
+    }
+
+    /// Checks a single file against the query
+    fn check_path(&self, path: TableIndex<Path>) -> Option<FileHandle> {
+        let engine = self.engine;
+        let path_ref = &engine.paths[path];
+        let info = engine.links[path_ref.link()].info();
+        let info_ref = &engine.infos[info];
+
+        if !self.matches_info(info_ref, path_ref) {
+            return None;
+        }
+
+        let has_metadata_filters = self.has_metadata_filters();
+
+        info_ref
+            .descriptors
+            .iter()
+            .enumerate()
+            .find_map(|(position, descriptor)| {
+                let args = &engine.descriptors[descriptor].load_args;
+                if !self
+                    .load_arguments
+                    .iter()
+                    .all(|kind| *kind == LoadArgumentsKind::from(args))
+                {
+                    return None;
+                }
+
+                let metadata = engine
+                    .descriptor_data(descriptor, position)
+                    .map(|(_, metadata)| metadata);
+
+                if has_metadata_filters {
+                    let metadata = metadata?;
+                    if !self.matches_metadata(&engine.metadatas[metadata]) {
+                        return None;
+                    }
+                }
+
+                Some(FileHandle {
+                    path,
+                    info,
+                    descriptor,
+                    metadata,
+                })
+            })
+    }
+
+    /// Iterates over every file which matches the query
+    pub fn iter(&self) -> FileQueryIter<'_, 'a> {
+        FileQueryIter {
+            query: self,
+            next: 0,
+        }
+    }
+}
+
+/// The lazy iterator over the files which match a [`FileQuery`]
+///
+/// Files are checked as the iterator advances, in the order of the path table
+pub struct FileQueryIter<'q, 'a> {
+    query: &'q FileQuery<'a>,
+    next: usize,
+}
+
+impl<'q, 'a> Iterator for FileQueryIter<'q, 'a> {
+    type Item = FileHandle;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next < self.query.engine.paths.len() {
+            let path = TableIndex::new(self.next);
+            self.next += 1;
+
+            if let Some(handle) = self.query.check_path(path) {
+                return Some(handle);
+            }
+        }
+
+        None
+    }
+}
+
+impl<'q, 'a> IntoIterator for &'q FileQuery<'a> {
+    type Item = FileHandle;
+    type IntoIter = FileQueryIter<'q, 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl PackagedEngine {
+    /// Starts a query over every file in the packaged filesystem
+    ///
+    /// ### Panicking
+    /// Iterating the query panics if the engine has not been [resolved](Self::resolve)
+    pub fn query(&self) -> FileQuery<'_> {
+        FileQuery {
+            engine: self,
+            is_graphics_archive: None,
+            is_localized: None,
+            is_regional: None,
+            is_shared: None,
+            is_unknown_flag: None,
+            extension: None,
+            load_arguments: None,
+            is_compressed: None,
+            is_standard_zstd: None,
+            is_regional_versioned_data: None,
+            is_localized_versioned_data: None,
+            compressed_size: (Bound::Unbounded, Bound::Unbounded),
+            decompressed_size: (Bound::Unbounded, Bound::Unbounded),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engines::packaged::tests::build_engine;
+
+    fn paths(engine: &PackagedEngine, query: &FileQuery) -> Vec<Hash40> {
+        query
+            .iter()
+            .map(|handle| engine.paths[handle.path].full_path)
+            .collect()
+    }
+
+    #[test]
+    fn info_filters_select_files() {
+        let engine = build_engine();
+
+        assert_eq!(engine.query().iter().count(), 3);
+        assert_eq!(
+            paths(&engine, &engine.query().localized(true)),
+            ["ui/message/msg_name.msbt".to_hash()]
+        );
+        assert_eq!(
+            paths(
+                &engine,
+                &engine.query().localized(false).extension("nuanmb")
+            ),
+            ["fighter/mario/motion/body/c00/a00wait1.nuanmb".to_hash()]
+        );
+        assert_eq!(
+            engine
+                .query()
+                .load_arguments(LoadArgumentsKind::Owned)
+                .iter()
+                .count(),
+            3
+        );
+        assert_eq!(
+            engine
+                .query()
+                .load_arguments(LoadArgumentsKind::Unowned)
+                .iter()
+                .count(),
+            0
+        );
+        assert_eq!(engine.query().graphics_archive(true).iter().count(), 0);
+    }
+
+    #[test]
+    fn metadata_filters_find_the_matching_descriptor() {
+        let engine = build_engine();
+
+        assert_eq!(
+            paths(&engine, &engine.query().decompressed_size(0x100..0x200)),
+            ["fighter/mario/model/body/c00/model.numdlb".to_hash()]
+        );
+        assert_eq!(engine.query().compressed(true).iter().count(), 0);
+
+        // Each variant of the localized file has its position as its size
+        let query = engine.query().localized(true).decompressed_size(3..=3);
+        let handles: Vec<_> = query.iter().collect();
+        assert_eq!(handles.len(), 1);
+        assert_eq!(
+            handles[0].descriptor,
+            engine.infos[handles[0].info].descriptors.get(3)
+        );
+        assert_eq!(
+            engine.metadatas[handles[0].metadata.unwrap()].decompressed_size,
+            3
+        );
+    }
+}
@@ -1,22 +1,20 @@
 use crate::{
-    engines::{table::*, HashKey},
+    engines::{table::*, HashKey, VariantKind},
     Hashable, INVALID_INDEX, INVALID_INDEX32,
 };
 use binrw::{binread, binrw, BinRead, BinWrite};
 use camino::Utf8Path;
 use hash40::Hash40;
 use semver::Version;
-use std::{
-    cell::{Ref, RefMut},
-    num::NonZeroUsize,
-    rc::Rc,
-};
+use std::{num::NonZeroUsize, rc::Rc};
 use std::{ops::Range, str::FromStr};
 use thiserror::Error;
 
-use super::{bucket_map::BucketMap, PackagedWriter};
+use super::{bucket_map::BucketMap, PackagedReferences, PackagedRemap, PackagedWriter};
 
 #[binread]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Package {
     #[br(temp)]
     path_and_group_index: HashKey,
@@ -77,6 +75,8 @@ pub struct Package {
 }
 
 #[binread]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChildPackage {
     #[br(temp)]
     key: HashKey,
@@ -104,6 +104,8 @@ multi_reference!(
 );
 
 #[binread]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Group {
     #[br(map = |size: u64| size as usize)]
     pub archive_offset: usize,
@@ -141,6 +143,8 @@ multi_reference!(
 );
 
 #[binread]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Path {
     #[br(temp)]
     path_and_link_index: HashKey,
@@ -179,6 +183,8 @@ multi_reference!(
 );
 
 #[binread]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Link {
     #[br(map = |index: u32| LinkOwnerReference::Unresolved(index as usize))]
     owner: LinkOwnerReference,
@@ -188,6 +194,8 @@ pub struct Link {
 }
 
 #[binread]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Info {
     #[br(map = |index: u32| TableReference::Unresolved(index as usize))]
     path: TableReference<Path>,
@@ -238,6 +246,8 @@ multi_reference!(
     }
 );
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DescriptorLoadArguments {
     Unowned {
         link: TableReference<Link>,
@@ -265,6 +275,8 @@ multi_reference!(
 );
 
 #[binread]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Descriptor {
     #[br(map = |index: u32| TableReference::Unresolved(index as usize))]
     group: TableReference<Group>,
@@ -282,6 +294,8 @@ pub struct Descriptor {
 }
 
 #[binrw]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Metadata {
     #[br(map = |offset: u32| offset as usize)]
     #[bw(map = |offset: &usize| *offset as u32)]
@@ -324,6 +338,8 @@ pub struct Metadata {
 }
 
 #[binread]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Patch {
     #[br(map = |version: (u8, u8, u16)| Version::new(version.2 as u64, version.1 as u64, version.0 as u64))]
     pub version: Version,
@@ -355,6 +371,8 @@ pub struct Patch {
 }
 
 #[binread]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VersionedFile {
     #[br(temp)]
     path_and_changed: HashKey,
@@ -462,13 +480,13 @@ impl Package {
 
     pub fn resolve(
         &mut self,
-        groups: &[TableCell<Group>],
-        infos: &[TableCell<Info>],
-        child_packages: &[TableCell<ChildPackage>],
-    ) {
-        self.groups.resolve(groups);
-        self.infos.resolve(infos);
-        self.child_packages.resolve(child_packages);
+        groups: &[Group],
+        infos: &[Info],
+        child_packages: &[ChildPackage],
+    ) -> Result<(), ResolveError> {
+        self.groups.resolve(groups)?;
+        self.infos.resolve(infos)?;
+        self.child_packages.resolve(child_packages)
     }
 
     pub fn is_resolved(&self) -> bool {
@@ -476,11 +494,58 @@ impl Package {
     }
 }
 
+impl FromStr for Package {
+    type Err = PathFromStrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let path = Utf8Path::new(s);
+        let full_path = path.as_str().to_hash();
+        let parent = path
+            .parent()
+            .ok_or(PathFromStrError::MissingParent)?
+            .as_str()
+            .to_hash();
+        let name = path
+            .file_name()
+            .ok_or(PathFromStrError::MissingFileName)?
+            .to_hash();
+
+        let mut groups = TableContiguousReference::invalid();
+        groups.replace([]);
+        let mut infos = TableContiguousReference::invalid();
+        infos.replace([]);
+        let mut child_packages = TableContiguousReference::invalid();
+        child_packages.replace([]);
+
+        Ok(Self {
+            full_path,
+            name,
+            parent,
+            lifetime: Hash40::new(""),
+            groups,
+            infos,
+            child_packages,
+            is_localized: false,
+            is_regional: false,
+            has_sub_package: false,
+            sym_link_is_regional: false,
+            is_sym_link: false,
+        })
+    }
+}
+
 impl ChildPackage {
     pub(crate) const REPR_SIZE: usize = 0x8;
 
-    pub fn resolve(&mut self, packages: &[TableCell<Package>]) {
-        self.package.resolve(packages);
+    pub fn new(full_path: Hash40, package: TableIndex<Package>) -> Self {
+        Self {
+            full_path,
+            package: TableReference::Resolved(package),
+        }
+    }
+
+    pub fn resolve(&mut self, packages: &[Package]) -> Result<(), ResolveError> {
+        self.package.resolve(packages)
     }
 
     pub fn is_resolved(&self) -> bool {
@@ -491,73 +556,84 @@ impl ChildPackage {
 impl GroupSubPackageReference {
     pub fn resolve(
         &mut self,
-        packages: &[TableCell<Package>],
-        groups: &[TableCell<Group>],
-    ) -> Option<usize> {
-        let mut tmp = Self::None;
-        std::mem::swap(self, &mut tmp);
-        let mut sub_index = None;
-        *self = match tmp {
-            Self::Unresolved(index) => {
-                sub_index = Some(index);
-                if index == 0 {
-                    Self::None
-                } else if index < packages.len() {
-                    Self::Package(packages[index].clone())
-                } else {
-                    Self::Group(groups[index].clone())
-                }
-            }
-            other => other,
+        packages: &[Package],
+        group_count: usize,
+    ) -> Result<Option<usize>, ResolveError> {
+        let index = match *self {
+            Self::Unresolved(index) => index,
+            _ => return Ok(None),
+        };
+
+        *self = if index == 0 {
+            Self::None
+        } else if index < packages.len() {
+            Self::Package(TableIndex::new(index))
+        } else {
+            check_bounds(index, group_count)?;
+            Self::Group(TableIndex::new(index))
         };
-        sub_index
+
+        Ok(Some(index))
     }
 }
 
 impl GroupFileReference {
     pub fn resolve(
         &mut self,
-        infos: &[TableCell<Info>],
-        metadatas: &[TableCell<Metadata>],
+        infos: &[Info],
+        metadatas: &[Metadata],
         is_info_group: bool,
-    ) {
-        let mut tmp = Self::Unresolved(INVALID_INDEX..INVALID_INDEX);
-        std::mem::swap(self, &mut tmp);
-        *self = match tmp {
-            Self::Unresolved(range) => {
-                if is_info_group {
-                    let mut set = TableContiguousReference(TableReferenceSet::Unresolved(range));
-                    set.resolve(infos);
-                    Self::Info(set)
-                } else {
-                    let mut set = TableContiguousReference(TableReferenceSet::Unresolved(range));
-                    set.resolve(metadatas);
-                    Self::Metadata(set)
-                }
-            }
-            other => other,
-        }
+    ) -> Result<(), ResolveError> {
+        let range = match self {
+            Self::Unresolved(range) => range.clone(),
+            _ => return Ok(()),
+        };
+
+        *self = if is_info_group {
+            let mut set = TableContiguousReference(TableReferenceSet::Unresolved(range));
+            set.resolve(infos)?;
+            Self::Info(set)
+        } else {
+            let mut set = TableContiguousReference(TableReferenceSet::Unresolved(range));
+            set.resolve(metadatas)?;
+            Self::Metadata(set)
+        };
+
+        Ok(())
     }
 }
 
 impl Group {
     pub(crate) const REPR_SIZE: usize = 0x1C;
 
+    /// Creates an empty metadata group, with no data and no sub package
+    pub fn new() -> Self {
+        let mut metadatas = TableContiguousReference::invalid();
+        metadatas.replace([]);
+
+        Self {
+            archive_offset: 0,
+            decompressed_size: 0,
+            compressed_size: 0,
+            files: GroupFileReference::Metadata(metadatas),
+            sub_package: GroupSubPackageReference::None,
+        }
+    }
+
     pub fn resolve(
         &mut self,
-        packages: &[TableCell<Package>],
-        groups: &[TableCell<Group>],
-        infos: &[TableCell<Info>],
-        metadatas: &[TableCell<Metadata>],
+        packages: &[Package],
+        group_count: usize,
+        infos: &[Info],
+        metadatas: &[Metadata],
         self_index: usize,
-    ) {
-        let is_info_group = if let Some(sub_index) = self.sub_package.resolve(packages, groups) {
-            sub_index == 0 || sub_index == self_index
-        } else {
-            false
+    ) -> Result<(), ResolveError> {
+        let is_info_group = match self.sub_package.resolve(packages, group_count)? {
+            Some(sub_index) => sub_index == 0 || sub_index == self_index,
+            None => false,
         };
 
-        self.files.resolve(infos, metadatas, is_info_group);
+        self.files.resolve(infos, metadatas, is_info_group)
     }
 
     pub fn is_resolved(&self) -> bool {
@@ -593,20 +669,24 @@ impl Group {
     }
 }
 
+impl Default for Group {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PathVersionedFileReference {
     pub fn resolve(
         &mut self,
-        versioned_files: &[TableCell<VersionedFile>],
+        versioned_files: &[VersionedFile],
         versioned_file_offset: usize,
-    ) {
-        let mut tmp = Self::None;
-        std::mem::swap(self, &mut tmp);
-        *self = match tmp {
-            Self::Unresolved(index) => {
-                Self::VersionedFile(versioned_files[index + versioned_file_offset].clone())
-            }
-            other => other,
+    ) -> Result<(), ResolveError> {
+        if let Self::Unresolved(index) = *self {
+            let index = offset_index(index, versioned_file_offset, versioned_files.len())?;
+            *self = Self::VersionedFile(TableIndex::new(index));
         }
+
+        Ok(())
     }
 }
 
@@ -615,13 +695,13 @@ impl Path {
 
     pub fn resolve(
         &mut self,
-        links: &[TableCell<Link>],
-        versioned_files: &[TableCell<VersionedFile>],
+        links: &[Link],
+        versioned_files: &[VersionedFile],
         versioned_file_offset: usize,
-    ) {
-        self.link.resolve(links);
+    ) -> Result<(), ResolveError> {
+        self.link.resolve(links)?;
         self.versioned_file
-            .resolve(versioned_files, versioned_file_offset);
+            .resolve(versioned_files, versioned_file_offset)
     }
 
     pub fn is_resolved(&self) -> bool {
@@ -685,19 +765,17 @@ impl FromStr for Path {
 }
 
 impl LinkOwnerReference {
-    pub fn resolve(&mut self, packages: &[TableCell<Package>], groups: &[TableCell<Group>]) {
-        let mut tmp = Self::Unresolved(INVALID_INDEX);
-        std::mem::swap(self, &mut tmp);
-        *self = match tmp {
-            Self::Unresolved(index) => {
-                if index < packages.len() {
-                    Self::Package(packages[index].clone())
-                } else {
-                    Self::Group(groups[index].clone())
-                }
-            }
-            other => other,
+    pub fn resolve(&mut self, packages: &[Package], groups: &[Group]) -> Result<(), ResolveError> {
+        if let Self::Unresolved(index) = *self {
+            *self = if index < packages.len() {
+                Self::Package(TableIndex::new(index))
+            } else {
+                check_bounds(index, groups.len())?;
+                Self::Group(TableIndex::new(index))
+            };
         }
+
+        Ok(())
     }
 }
 
@@ -713,12 +791,12 @@ impl Link {
 
     pub fn resolve(
         &mut self,
-        packages: &[TableCell<Package>],
-        groups: &[TableCell<Group>],
-        infos: &[TableCell<Info>],
-    ) {
-        self.owner.resolve(packages, groups);
-        self.info.resolve(infos);
+        packages: &[Package],
+        groups: &[Group],
+        infos: &[Info],
+    ) -> Result<(), ResolveError> {
+        self.owner.resolve(packages, groups)?;
+        self.info.resolve(infos)
     }
 
     pub fn is_resolved(&self) -> bool {
@@ -745,43 +823,59 @@ impl Info {
 
     pub fn resolve(
         &mut self,
-        paths: &[TableCell<Path>],
-        links: &[TableCell<Link>],
-        descriptors: &[TableCell<Descriptor>],
-    ) {
-        self.path.resolve(paths);
-        self.link.resolve(links);
-        self.descriptors.resolve(descriptors);
+        paths: &[Path],
+        links: &[Link],
+        descriptors: &[Descriptor],
+    ) -> Result<(), ResolveError> {
+        self.path.resolve(paths)?;
+        self.link.resolve(links)?;
+        self.descriptors.resolve(descriptors)
     }
 
     pub fn is_resolved(&self) -> bool {
         self.path.is_resolved() && self.link.is_resolved() && self.descriptors.is_resolved()
     }
+
+    /// Whether this info has a descriptor for every locale, for every region, or just one
+    pub fn variants(&self) -> VariantKind {
+        if self.is_localized {
+            VariantKind::Localized
+        } else if self.is_regional {
+            VariantKind::Regional
+        } else {
+            VariantKind::None
+        }
+    }
 }
 
 impl DescriptorMetadataReference {
-    pub fn resolve(&mut self, metadatas: &[TableCell<Metadata>]) {
-        let mut tmp = Self::None;
-        std::mem::swap(self, &mut tmp);
-        *self = match tmp {
-            Self::Unresolved(index) => Self::Metadata(metadatas[index].clone()),
-            other => other,
+    pub fn resolve(&mut self, metadatas: &[Metadata]) -> Result<(), ResolveError> {
+        if let Self::Unresolved(index) = *self {
+            check_bounds(index, metadatas.len())?;
+            *self = Self::Metadata(TableIndex::new(index));
         }
+
+        Ok(())
     }
 }
 
 impl DescriptorLoadArgumentsPatchReference {
-    pub fn resolve(&mut self, patches: &[TableCell<Patch>], is_versioned_descriptor: bool) {
+    pub fn resolve(
+        &mut self,
+        patches: &[Patch],
+        is_versioned_descriptor: bool,
+    ) -> Result<(), ResolveError> {
         if !is_versioned_descriptor {
             *self = Self::None;
-            return;
+            return Ok(());
         }
-        let mut tmp = Self::None;
-        std::mem::swap(self, &mut tmp);
-        *self = match tmp {
-            Self::Unresolved(index) => Self::Patch(patches[index].clone()),
-            other => other,
+
+        if let Self::Unresolved(index) = *self {
+            check_bounds(index, patches.len())?;
+            *self = Self::Patch(TableIndex::new(index));
         }
+
+        Ok(())
     }
 }
 
@@ -794,18 +888,18 @@ impl DescriptorLoadArguments {
 
     pub fn resolve(
         &mut self,
-        links: &[TableCell<Link>],
-        infos: &[TableCell<Info>],
-        patches: &[TableCell<Patch>],
+        links: &[Link],
+        infos: &[Info],
+        patches: &[Patch],
         info_offset: usize,
         is_versioned_descriptor: bool,
-    ) {
+    ) -> Result<(), ResolveError> {
         match self {
             Self::Unowned { link } => link.resolve(links),
             Self::Owned { patch } => patch.resolve(patches, is_versioned_descriptor),
             Self::PackageSkip { info } => info.resolve_with_offset(infos, info_offset),
             Self::SharedButOwned { link } => link.resolve(links),
-            _ => {}
+            _ => Ok(()),
         }
     }
 
@@ -832,18 +926,18 @@ impl Descriptor {
 
     pub fn resolve(
         &mut self,
-        groups: &[TableCell<Group>],
-        links: &[TableCell<Link>],
-        infos: &[TableCell<Info>],
-        metadatas: &[TableCell<Metadata>],
-        patches: &[TableCell<Patch>],
+        groups: &[Group],
+        links: &[Link],
+        infos: &[Info],
+        metadatas: &[Metadata],
+        patches: &[Patch],
         info_offset: usize,
         is_versioned_descriptor: bool,
-    ) {
-        self.group.resolve(groups);
-        self.metadata.resolve(metadatas);
+    ) -> Result<(), ResolveError> {
+        self.group.resolve(groups)?;
+        self.metadata.resolve(metadatas)?;
         self.load_args
-            .resolve(links, infos, patches, info_offset, is_versioned_descriptor);
+            .resolve(links, infos, patches, info_offset, is_versioned_descriptor)
     }
 
     pub fn is_resolved(&self) -> bool {
@@ -870,15 +964,15 @@ impl Metadata {
 impl Patch {
     pub fn resolve(
         &mut self,
-        files: &[TableCell<VersionedFile>],
-        infos: &[TableCell<Info>],
-        groups: &[TableCell<Group>],
+        files: &[VersionedFile],
+        infos: &[Info],
+        groups: &[Group],
         patch_index: usize,
         info_offset: usize,
-    ) {
-        self.versioned_files.resolve(files);
-        self.infos.resolve_with_offset(infos, info_offset);
-        self.group.resolve_with_offset(groups, patch_index);
+    ) -> Result<(), ResolveError> {
+        self.versioned_files.resolve(files)?;
+        self.infos.resolve_with_offset(infos, info_offset)?;
+        self.group.resolve_with_offset(groups, patch_index)
     }
 
     pub fn is_resolved(&self) -> bool {
@@ -887,9 +981,175 @@ impl Patch {
 }
 
 impl VersionedFile {
-    pub fn resolve(&mut self, links: &[TableCell<Link>], infos: &[TableCell<Info>]) {
-        self.link.resolve(links);
-        self.info.resolve(infos);
+    pub fn resolve(&mut self, links: &[Link], infos: &[Info]) -> Result<(), ResolveError> {
+        self.link.resolve(links)?;
+        self.info.resolve(infos)
+    }
+
+    pub(crate) fn remap(&mut self, remap: &PackagedRemap) {
+        self.link.remap(&remap.links);
+        self.info.remap(&remap.infos);
+    }
+
+    pub(crate) fn references(&self, refs: &mut PackagedReferences) {
+        refs.links.extend(self.link.resolved());
+        refs.infos.extend(self.info.resolved());
+    }
+}
+
+impl Package {
+    pub(crate) fn remap(&mut self, remap: &PackagedRemap) {
+        self.groups.remap(&remap.groups);
+        self.infos.remap(&remap.infos);
+        self.child_packages.remap(&remap.child_packages);
+    }
+
+    pub(crate) fn references(&self, refs: &mut PackagedReferences) {
+        refs.groups.extend(self.groups.resolved());
+        refs.infos.extend(self.infos.resolved());
+        refs.child_packages.extend(self.child_packages.resolved());
+    }
+}
+
+impl ChildPackage {
+    pub(crate) fn remap(&mut self, remap: &PackagedRemap) {
+        self.package.remap(&remap.packages);
+    }
+
+    pub(crate) fn references(&self, refs: &mut PackagedReferences) {
+        refs.packages.extend(self.package.resolved());
+    }
+}
+
+impl Group {
+    pub(crate) fn remap(&mut self, remap: &PackagedRemap) {
+        match &mut self.files {
+            GroupFileReference::Info(infos) => infos.remap(&remap.infos),
+            GroupFileReference::Metadata(metadatas) => metadatas.remap(&remap.metadatas),
+            GroupFileReference::Unresolved(_) => {}
+        }
+
+        match &mut self.sub_package {
+            GroupSubPackageReference::Package(package) => *package = remap.packages.get(*package),
+            GroupSubPackageReference::Group(group) => *group = remap.groups.get(*group),
+            _ => {}
+        }
+    }
+
+    pub(crate) fn references(&self, refs: &mut PackagedReferences) {
+        match &self.files {
+            GroupFileReference::Info(infos) => refs.infos.extend(infos.resolved()),
+            GroupFileReference::Metadata(metadatas) => refs.metadatas.extend(metadatas.resolved()),
+            GroupFileReference::Unresolved(_) => {}
+        }
+
+        match &self.sub_package {
+            GroupSubPackageReference::Package(package) => refs.packages.push(*package),
+            GroupSubPackageReference::Group(group) => refs.groups.push(*group),
+            _ => {}
+        }
+    }
+}
+
+impl Path {
+    pub(crate) fn remap(&mut self, remap: &PackagedRemap) {
+        self.link.remap(&remap.links);
+        if let PathVersionedFileReference::VersionedFile(file) = &mut self.versioned_file {
+            *file = remap.versioned_files.get(*file);
+        }
+    }
+
+    pub(crate) fn references(&self, refs: &mut PackagedReferences) {
+        refs.links.extend(self.link.resolved());
+        if let PathVersionedFileReference::VersionedFile(file) = &self.versioned_file {
+            refs.versioned_files.push(*file);
+        }
+    }
+}
+
+impl Link {
+    pub(crate) fn remap(&mut self, remap: &PackagedRemap) {
+        match &mut self.owner {
+            LinkOwnerReference::Package(package) => *package = remap.packages.get(*package),
+            LinkOwnerReference::Group(group) => *group = remap.groups.get(*group),
+            LinkOwnerReference::Unresolved(_) => {}
+        }
+        self.info.remap(&remap.infos);
+    }
+
+    pub(crate) fn references(&self, refs: &mut PackagedReferences) {
+        match &self.owner {
+            LinkOwnerReference::Package(package) => refs.packages.push(*package),
+            LinkOwnerReference::Group(group) => refs.groups.push(*group),
+            LinkOwnerReference::Unresolved(_) => {}
+        }
+        refs.infos.extend(self.info.resolved());
+    }
+}
+
+impl Info {
+    pub(crate) fn remap(&mut self, remap: &PackagedRemap) {
+        self.path.remap(&remap.paths);
+        self.link.remap(&remap.links);
+        self.descriptors.remap(&remap.descriptors);
+    }
+
+    pub(crate) fn references(&self, refs: &mut PackagedReferences) {
+        refs.paths.extend(self.path.resolved());
+        refs.links.extend(self.link.resolved());
+        refs.descriptors.extend(self.descriptors.resolved());
+    }
+}
+
+impl Descriptor {
+    pub(crate) fn remap(&mut self, remap: &PackagedRemap) {
+        self.group.remap(&remap.groups);
+        if let DescriptorMetadataReference::Metadata(metadata) = &mut self.metadata {
+            *metadata = remap.metadatas.get(*metadata);
+        }
+
+        match &mut self.load_args {
+            DescriptorLoadArguments::Unowned { link }
+            | DescriptorLoadArguments::SharedButOwned { link } => link.remap(&remap.links),
+            DescriptorLoadArguments::Owned {
+                patch: DescriptorLoadArgumentsPatchReference::Patch(patch),
+            } => *patch = remap.patches.get(*patch),
+            DescriptorLoadArguments::PackageSkip { info } => info.remap(&remap.infos),
+            _ => {}
+        }
+    }
+
+    pub(crate) fn references(&self, refs: &mut PackagedReferences) {
+        refs.groups.extend(self.group.resolved());
+        if let DescriptorMetadataReference::Metadata(metadata) = &self.metadata {
+            refs.metadatas.push(*metadata);
+        }
+
+        match &self.load_args {
+            DescriptorLoadArguments::Unowned { link }
+            | DescriptorLoadArguments::SharedButOwned { link } => {
+                refs.links.extend(link.resolved())
+            }
+            DescriptorLoadArguments::Owned {
+                patch: DescriptorLoadArgumentsPatchReference::Patch(patch),
+            } => refs.patches.push(*patch),
+            DescriptorLoadArguments::PackageSkip { info } => refs.infos.extend(info.resolved()),
+            _ => {}
+        }
+    }
+}
+
+impl Patch {
+    pub(crate) fn remap(&mut self, remap: &PackagedRemap) {
+        self.versioned_files.remap(&remap.versioned_files);
+        self.infos.remap(&remap.infos);
+        self.group.remap(&remap.groups);
+    }
+
+    pub(crate) fn references(&self, refs: &mut PackagedReferences) {
+        refs.versioned_files.extend(self.versioned_files.resolved());
+        refs.infos.extend(self.infos.resolved());
+        refs.groups.extend(self.group.resolved());
     }
 }
 
@@ -904,7 +1164,7 @@ impl BinWrite for Package {
     ) -> binrw::BinResult<()> {
         HashKey::new(
             self.full_path,
-            args.groups.get_index(&self.groups.cells()[0]) as usize,
+            args.groups.get_index(self.groups.get(0)) as usize,
         )
         .write_options(writer, options, ())?;
 
@@ -917,7 +1177,7 @@ impl BinWrite for Package {
             0u32.write_options(writer, options, ())?;
         } else {
             args.infos
-                .get_index(&self.infos.cells()[0])
+                .get_index(self.infos.get(0))
                 .write_options(writer, options, ())?;
             (self.infos.len() as u32).write_options(writer, options, ())?;
         }
@@ -927,7 +1187,7 @@ impl BinWrite for Package {
             0u32.write_options(writer, options, ())?;
         } else {
             args.child_packages
-                .get_index(&self.child_packages.cells()[0])
+                .get_index(self.child_packages.get(0))
                 .write_options(writer, options, ())?;
             (self.child_packages.len() as u32).write_options(writer, options, ())?;
         }
@@ -967,7 +1227,7 @@ impl BinWrite for ChildPackage {
     ) -> binrw::BinResult<()> {
         HashKey::new(
             self.full_path,
-            args.packages.get_index(self.package.cell()) as usize,
+            args.packages.get_index(self.package.index()) as usize,
         )
         .write_options(writer, options, ())
     }
@@ -992,7 +1252,7 @@ impl BinWrite for Group {
                 0u32.write_options(writer, options, ())?;
             } else {
                 args.infos
-                    .get_index(&self.infos().cells()[0])
+                    .get_index(self.infos().get(0))
                     .write_options(writer, options, ())?;
                 (self.infos().len() as u32).write_options(writer, options, ())?;
             }
@@ -1001,7 +1261,7 @@ impl BinWrite for Group {
                 0u32.write_options(writer, options, ())?;
             } else {
                 args.groups
-                    .get_index(self.raw_group())
+                    .get_index(self.group())
                     .write_options(writer, options, ())?;
             }
         } else {
@@ -1010,18 +1270,18 @@ impl BinWrite for Group {
                 0u32.write_options(writer, options, ())?;
             } else {
                 args.metadatas
-                    .get_index(&self.metadatas().cells()[0])
+                    .get_index(self.metadatas().get(0))
                     .write_options(writer, options, ())?;
                 (self.metadatas().len() as u32).write_options(writer, options, ())?;
             }
 
             if self.has_group() {
                 args.groups
-                    .get_index(self.raw_group())
+                    .get_index(self.group())
                     .write_options(writer, options, ())?;
             } else if self.has_package() {
                 args.packages
-                    .get_index(self.raw_package())
+                    .get_index(self.package())
                     .write_options(writer, options, ())?;
             } else {
                 INVALID_INDEX32.write_options(writer, options, ())?;
@@ -1040,14 +1300,14 @@ impl BinWrite for Path {
         options: &binrw::WriteOptions,
         args: Self::Args,
     ) -> binrw::BinResult<()> {
-        HashKey::new(
-            self.full_path,
-            args.links.get_index(self.raw_link()) as usize,
-        )
-        .write_options(writer, options, ())?;
+        HashKey::new(self.full_path, args.links.get_index(self.link()) as usize).write_options(
+            writer,
+            options,
+            (),
+        )?;
 
         let versioned_index = if self.has_versioned_file() {
-            args.versioned_files.get_index(self.raw_versioned_file()) as usize
+            args.versioned_files.get_index(self.versioned_file()) as usize
                 - args.last_patch_files_start
         } else {
             INVALID_INDEX
@@ -1070,16 +1330,16 @@ impl BinWrite for Link {
     ) -> binrw::BinResult<()> {
         if self.is_owner_package() {
             args.packages
-                .get_index(self.raw_package())
+                .get_index(self.package())
                 .write_options(writer, options, ())?;
         } else {
             args.groups
-                .get_index(self.raw_group())
+                .get_index(self.group())
                 .write_options(writer, options, ())?;
         }
 
         args.infos
-            .get_index(self.raw_info())
+            .get_index(self.info())
             .write_options(writer, options, ())
     }
 }
@@ -1094,13 +1354,13 @@ impl BinWrite for Info {
         args: Self::Args,
     ) -> binrw::BinResult<()> {
         args.paths
-            .get_index(self.raw_path())
+            .get_index(self.path())
             .write_options(writer, options, ())?;
         args.links
-            .get_index(self.raw_link())
+            .get_index(self.link())
             .write_options(writer, options, ())?;
         args.descriptors
-            .get_index(&self.descriptors.cells()[0])
+            .get_index(self.descriptors.get(0))
             .write_options(writer, options, ())?;
         let flags = {
             let mut var = 0u32;
@@ -1140,7 +1400,7 @@ impl BinWrite for DescriptorLoadArguments {
     ) -> binrw::BinResult<()> {
         let (pwriter, info_offset) = args;
         let bitfield = match self {
-            Self::Unowned { link } => pwriter.links.get_index(link.cell()) & 0x00FF_FFFF,
+            Self::Unowned { link } => pwriter.links.get_index(link.index()) & 0x00FF_FFFF,
             Self::Owned { patch } => {
                 let patch_id = if patch.is_patch() {
                     pwriter.patches.get_index(patch.patch()) & 0x00FF_FFFF
@@ -1150,12 +1410,12 @@ impl BinWrite for DescriptorLoadArguments {
                 0x0100_0000 | patch_id
             }
             Self::PackageSkip { info } => {
-                let index = pwriter.infos.get_index(info.cell()) - info_offset as u32;
+                let index = pwriter.infos.get_index(info.index()) - info_offset as u32;
                 0x0300_0000 | index
             }
             Self::Unknown => 0x0500_0000,
             Self::SharedButOwned { link } => {
-                let index = pwriter.links.get_index(link.cell()) & 0x00FF_FFFF;
+                let index = pwriter.links.get_index(link.index()) & 0x00FF_FFFF;
                 index | 0x0900_0000
             }
             Self::UnsupportedRegion { region_locale } => 0x1000_0000 | *region_locale as u32,
@@ -1177,12 +1437,12 @@ impl BinWrite for Descriptor {
         let (pwriter, info_offset) = args;
         pwriter
             .groups
-            .get_index(self.raw_group())
+            .get_index(self.group())
             .write_options(writer, options, ())?;
         if self.has_metadata() {
             pwriter
                 .metadatas
-                .get_index(self.raw_metadata())
+                .get_index(self.metadata())
                 .write_options(writer, options, ())?;
         } else {
             INVALID_INDEX32.write_options(writer, options, ())?;
@@ -1207,22 +1467,26 @@ impl Patch {
         (self.version.minor as u8).write_options(writer, options, ())?;
         (self.version.major as u16).write_options(writer, options, ())?;
         (self.versioned_files.len() as u32).write_options(writer, options, ())?;
-        (pwriter.groups.get_index(self.raw_group()) - patch_id as u32).write_options(
+        (pwriter.groups.get_index(self.group()) - patch_id as u32).write_options(
             writer,
             options,
             (),
         )?;
         pwriter
             .versioned_files
-            .get_index(&self.versioned_files.cells()[0])
+            .get_index(self.versioned_files.get(0))
             .write_options(writer, options, ())?;
         let lookup_size = 8 * (self.versioned_files.len() + 0x401);
         (lookup_size as u32).write_options(writer, options, ())?;
 
         (self.infos.len() as u32).write_options(writer, options, ())?;
-        let descriptor_count: usize = self.infos.iter().map(|info| info.descriptors.len()).sum();
+        let descriptor_count: usize = self
+            .infos
+            .iter()
+            .map(|info| pwriter.infos[info].descriptors.len())
+            .sum();
         (descriptor_count as u32).write_options(writer, options, ())?;
-        (self.group().infos().len() as u32).write_options(writer, options, ())
+        (pwriter.groups[self.group()].infos().len() as u32).write_options(writer, options, ())
     }
 
     fn write_body<W>(
@@ -1235,8 +1499,12 @@ impl Patch {
         W: std::io::Write + std::io::Seek,
     {
         let mut lookup = BucketMap::new(NonZeroUsize::new(0x400).unwrap());
-        let group_index = pwriter.groups.get_index(self.raw_group()) as usize;
-        for (index, file) in self.versioned_files.iter().enumerate() {
+        let group_index = pwriter.groups.get_index(self.group()) as usize;
+        for (index, file) in self
+            .versioned_files
+            .iter_in(pwriter.versioned_files.source())
+            .enumerate()
+        {
             lookup.insert(file.path, index);
             file.write_options(writer, options, (Rc::clone(&pwriter), group_index))?;
         }
@@ -1300,12 +1568,12 @@ impl BinWrite for VersionedFile {
 
         pwriter
             .infos
-            .get_index(self.raw_info())
+            .get_index(self.info())
             .write_options(writer, options, ())?;
         (group_index as u32).write_options(writer, options, ())?;
         pwriter
             .links
-            .get_index(self.raw_link())
+            .get_index(self.link())
             .write_options(writer, options, ())
     }
 }
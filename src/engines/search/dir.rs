@@ -0,0 +1,408 @@
+use std::cmp::Ordering;
+
+use hash40::Hash40;
+
+use crate::{engines::table::TableIndex, Hashable};
+
+use super::{
+    types::{SearchFolder, SearchPath},
+    SearchEngine,
+};
+
+/// The kind of path that a [`DirEntry`] refers to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Folder,
+}
+
+/// An entry in a [`SearchFolder`], as returned by [`SearchEngine::read_dir`] and [`SearchEngine::walk`]
+#[derive(Copy, Clone)]
+pub struct DirEntry<'a> {
+    index: TableIndex<SearchPath>,
+    path: &'a SearchPath,
+    depth: usize,
+}
+
+impl<'a> DirEntry<'a> {
+    /// Gets whether this entry is a file or a folder
+    pub fn kind(&self) -> EntryKind {
+        if self.path.is_folder() {
+            EntryKind::Folder
+        } else {
+            EntryKind::File
+        }
+    }
+
+    /// Checks if this entry is a file
+    pub fn is_file(&self) -> bool {
+        !self.path.is_folder()
+    }
+
+    /// Checks if this entry is a folder
+    pub fn is_folder(&self) -> bool {
+        self.path.is_folder()
+    }
+
+    /// The name of this entry, including the extension for files
+    pub fn name(&self) -> Hash40 {
+        self.path.name
+    }
+
+    /// The extension of this entry, which is blank for folders
+    pub fn extension(&self) -> Hash40 {
+        self.path.extension
+    }
+
+    /// The full path of this entry, with no trailing `/` for folders
+    pub fn full_path(&self) -> Hash40 {
+        self.path.full_path
+    }
+
+    /// The full path of the folder which contains this entry
+    pub fn parent(&self) -> Hash40 {
+        self.path.parent
+    }
+
+    /// The number of folders between this entry and the folder that was read or walked
+    ///
+    /// Direct children have a depth of `1`
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The index of this entry's [`SearchPath`] in the path table
+    pub fn index(&self) -> TableIndex<SearchPath> {
+        self.index
+    }
+
+    /// The [`SearchPath`] of this entry
+    pub fn path(&self) -> &'a SearchPath {
+        self.path
+    }
+
+    /// The [`SearchFolder`] of this entry, if it is a folder
+    pub fn folder(&self) -> Option<TableIndex<SearchFolder>> {
+        self.path.is_folder().then(|| self.path.folder())
+    }
+}
+
+/// An iterator over the direct children of a [`SearchFolder`]
+///
+/// Created by [`SearchEngine::read_dir`]
+pub struct ReadDir<'a> {
+    engine: &'a SearchEngine,
+    children: std::vec::IntoIter<TableIndex<SearchPath>>,
+    depth: usize,
+}
+
+impl<'a> ReadDir<'a> {
+    pub(super) fn new(
+        engine: &'a SearchEngine,
+        folder: TableIndex<SearchFolder>,
+        depth: usize,
+    ) -> Self {
+        let children: Vec<_> = engine.folders[folder].children.iter().collect();
+        Self {
+            engine,
+            children: children.into_iter(),
+            depth,
+        }
+    }
+}
+
+impl<'a> Iterator for ReadDir<'a> {
+    type Item = DirEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.children.next().map(|index| DirEntry {
+            index,
+            path: &self.engine.paths[index],
+            depth: self.depth,
+        })
+    }
+}
+
+/// The order that [`SearchEngine::walk`] visits the children of each folder in
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SortOrder {
+    /// The order that the children are linked in the archive
+    Table,
+
+    /// Sorted by the hash of their names
+    Hash,
+
+    /// Sorted by the labels of their names, using the [`Hash40`] label map.
+    ///
+    /// Names without a label are sorted by their hex representation
+    Label,
+}
+
+/// A builder for a recursive walk over the search filesystem
+///
+/// Created by [`SearchEngine::walk`], this is modelled after `walkdir`. Entries are
+/// visited depth-first, with each folder yielded before its contents.
+pub struct WalkDir<'a> {
+    engine: &'a SearchEngine,
+    root: Option<TableIndex<SearchFolder>>,
+    min_depth: usize,
+    max_depth: usize,
+    sort: SortOrder,
+    folders_first: bool,
+}
+
+impl<'a> WalkDir<'a> {
+    /// Sets the minimum depth of entries to yield, defaulting to `1`
+    ///
+    /// Entries which are shallower are still traversed, just not yielded
+    pub fn min_depth(mut self, depth: usize) -> Self {
+        self.min_depth = depth;
+        self
+    }
+
+    /// Sets the maximum depth of entries to yield, defaulting to no limit
+    ///
+    /// Folders at the maximum depth are yielded, but not descended into
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Sets the order that the children of each folder are visited in,
+    /// defaulting to [`SortOrder::Table`]
+    pub fn sort(mut self, order: SortOrder) -> Self {
+        self.sort = order;
+        self
+    }
+
+    /// Sets if the folders in each folder are visited before the files, defaulting to `false`
+    pub fn folders_first(mut self, folders_first: bool) -> Self {
+        self.folders_first = folders_first;
+        self
+    }
+}
+
+impl<'a> IntoIterator for WalkDir<'a> {
+    type Item = DirEntry<'a>;
+    type IntoIter = Walk<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let mut walk = Walk {
+            stack: vec![],
+            options: self,
+        };
+
+        if let Some(root) = walk.options.root {
+            walk.push_folder(root, 1);
+        }
+
+        walk
+    }
+}
+
+/// The iterator of a recursive walk over the search filesystem
+///
+/// Created from a [`WalkDir`]
+pub struct Walk<'a> {
+    options: WalkDir<'a>,
+    stack: Vec<std::vec::IntoIter<DirEntry<'a>>>,
+}
+
+impl<'a> Walk<'a> {
+    /// Reads and sorts the children of a folder and pushes them to be visited next
+    fn push_folder(&mut self, folder: TableIndex<SearchFolder>, depth: usize) {
+        let mut entries: Vec<_> = ReadDir::new(self.options.engine, folder, depth).collect();
+
+        match self.options.sort {
+            SortOrder::Table => {}
+            SortOrder::Hash => entries.sort_by_key(|entry| entry.name()),
+            SortOrder::Label => entries.sort_by_cached_key(|entry| entry.name().to_label()),
+        }
+
+        if self.options.folders_first {
+            // Stable, so the order within the folders and files is preserved
+            entries.sort_by(|a, b| match (a.is_folder(), b.is_folder()) {
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                _ => Ordering::Equal,
+            });
+        }
+
+        self.stack.push(entries.into_iter());
+    }
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = DirEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match self.stack.last_mut()?.next() {
+                Some(entry) => entry,
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+
+            if entry.depth < self.options.max_depth {
+                if let Some(folder) = entry.folder() {
+                    self.push_folder(folder, entry.depth + 1);
+                }
+            }
+
+            if entry.depth >= self.options.min_depth {
+                return Some(entry);
+            }
+        }
+    }
+}
+
+impl SearchEngine {
+    /// Reads the direct children of a folder
+    ///
+    /// ### Arguments
+    /// * `folder` - The hash of the folder's full path, with no trailing `/`
+    ///
+    /// ### Returns
+    /// * `Some(_)` - An iterator over the children, in the order they are linked in the archive
+    /// * `None` - The folder does not exist
+    ///
+    /// ### Panicking
+    /// Panics if the engine has not been [resolved](Self::resolve)
+    pub fn read_dir(&self, folder: impl Hashable) -> Option<ReadDir<'_>> {
+        self.folder_lookup
+            .get(&folder.to_hash())
+            .map(|folder| ReadDir::new(self, *folder, 1))
+    }
+
+    /// Recursively walks a folder
+    ///
+    /// ### Arguments
+    /// * `root` - The hash of the folder's full path, with no trailing `/`
+    ///
+    /// ### Returns
+    /// A [`WalkDir`] to configure the walk with. If the folder doesn't exist, the walk is empty.
+    ///
+    /// ### Panicking
+    /// Iterating panics if the engine has not been [resolved](Self::resolve)
+    pub fn walk(&self, root: impl Hashable) -> WalkDir<'_> {
+        WalkDir {
+            engine: self,
+            root: self.folder_lookup.get(&root.to_hash()).copied(),
+            min_depth: 1,
+            max_depth: usize::MAX,
+            sort: SortOrder::Table,
+            folders_first: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_engine() -> SearchEngine {
+        let mut engine = SearchEngine::new();
+        engine.add_file("fighter/fighter_param.prc");
+        engine.add_file("fighter/common/param.prc");
+        engine.add_file("fighter/mario/model/body/c00/model.numdlb");
+        engine.add_file("fighter/mario/motion/body/c00/a00wait1.nuanmb");
+        engine.add_file("fighter/luigi/model/body/c00/model.numdlb");
+        engine.add_file("ui/message/msg_menu.msbt");
+        engine
+    }
+
+    fn full_paths<'a>(entries: impl IntoIterator<Item = DirEntry<'a>>) -> Vec<Hash40> {
+        entries.into_iter().map(|entry| entry.full_path()).collect()
+    }
+
+    #[test]
+    fn read_dir_yields_direct_children() {
+        let engine = build_engine();
+
+        let children: Vec<_> = engine.read_dir("fighter").unwrap().collect();
+        assert_eq!(
+            full_paths(children.iter().copied()),
+            [
+                Hash40::new("fighter/fighter_param.prc"),
+                Hash40::new("fighter/common"),
+                Hash40::new("fighter/mario"),
+                Hash40::new("fighter/luigi"),
+            ]
+        );
+        assert!(children.iter().all(|entry| entry.depth() == 1));
+        assert_eq!(children[0].kind(), EntryKind::File);
+        assert_eq!(children[0].parent(), Hash40::new("fighter"));
+        assert_eq!(children[2].kind(), EntryKind::Folder);
+        assert_eq!(children[2].name(), Hash40::new("mario"));
+
+        assert!(engine.read_dir("fighter/wario").is_none());
+    }
+
+    #[test]
+    fn walk_visits_folders_before_their_contents() {
+        let engine = build_engine();
+
+        let files: Vec<_> = engine
+            .walk("fighter/mario")
+            .into_iter()
+            .filter(DirEntry::is_file)
+            .collect();
+        assert_eq!(
+            full_paths(files.iter().copied()),
+            [
+                Hash40::new("fighter/mario/model/body/c00/model.numdlb"),
+                Hash40::new("fighter/mario/motion/body/c00/a00wait1.nuanmb"),
+            ]
+        );
+        assert_eq!(files[0].depth(), 4);
+
+        let walked = full_paths(engine.walk("fighter/mario").max_depth(2));
+        assert_eq!(
+            walked,
+            [
+                Hash40::new("fighter/mario/model"),
+                Hash40::new("fighter/mario/model/body"),
+                Hash40::new("fighter/mario/motion"),
+                Hash40::new("fighter/mario/motion/body"),
+            ]
+        );
+
+        let deepest = full_paths(engine.walk("fighter/mario").min_depth(4));
+        assert_eq!(deepest.len(), 2);
+
+        assert_eq!(engine.walk("fighter/wario").into_iter().count(), 0);
+    }
+
+    #[test]
+    fn walk_sorts_children() {
+        let engine = build_engine();
+
+        let by_hash: Vec<_> = engine
+            .walk("fighter")
+            .max_depth(1)
+            .sort(SortOrder::Hash)
+            .into_iter()
+            .map(|entry| entry.name())
+            .collect();
+        assert!(by_hash.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let folders_first: Vec<_> = engine
+            .walk("fighter")
+            .max_depth(1)
+            .folders_first(true)
+            .into_iter()
+            .map(|entry| entry.kind())
+            .collect();
+        assert_eq!(
+            folders_first,
+            [
+                EntryKind::Folder,
+                EntryKind::Folder,
+                EntryKind::Folder,
+                EntryKind::File
+            ]
+        );
+    }
+}
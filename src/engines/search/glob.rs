@@ -0,0 +1,371 @@
+use std::sync::{Arc, Mutex};
+
+use hash40::{label_map::LabelMap, Hash40};
+
+use crate::engines::table::TableIndex;
+
+use super::{
+    dir::{DirEntry, EntryKind, ReadDir},
+    types::{SearchFolder, SearchPath},
+    SearchEngine,
+};
+
+/// How a [`GlobMatch`] was matched against its pattern
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GlobMatchKind {
+    /// Every wildcard component was matched against a known label
+    Label,
+
+    /// At least one wildcard component had no label, and was matched using the
+    /// hash of its extension instead, or matched by `*`
+    ///
+    /// Only `*`, `**` and `*.ext` (where `ext` has no wildcards) can match a name without
+    /// a label. See [`SearchEngine::glob`].
+    Hash,
+}
+
+/// A path in the search filesystem which matched a glob pattern
+#[derive(Debug, Clone)]
+pub struct GlobMatch {
+    /// The index of the matched path in the path table
+    pub path: TableIndex<SearchPath>,
+
+    /// The full path of the match
+    pub full_path: Hash40,
+
+    /// Whether the match is a file or a folder
+    pub kind: EntryKind,
+
+    /// The full path of the match as a string, if every component of it is known
+    pub label: Option<String>,
+
+    /// How the match was made
+    pub matched_by: GlobMatchKind,
+}
+
+/// A single `/`-separated component of a glob pattern
+enum GlobComponent<'a> {
+    /// A component with no wildcards, matched by hash
+    Literal(&'a str),
+
+    /// `**`, which matches any number of folders
+    Recursive,
+
+    /// A component containing `*` or `?`
+    Wildcard(&'a str),
+}
+
+impl<'a> GlobComponent<'a> {
+    fn new(component: &'a str) -> Self {
+        if component == "**" {
+            Self::Recursive
+        } else if component.contains(['*', '?']) {
+            Self::Wildcard(component)
+        } else {
+            Self::Literal(component)
+        }
+    }
+
+    /// Matches an entry against this component
+    ///
+    /// ### Returns
+    /// * `Some(_)` - The entry matched, and how
+    /// * `None` - The entry did not match, or it could not be determined without a label
+    fn matches(&self, entry: &DirEntry, label: Option<&str>) -> Option<GlobMatchKind> {
+        match self {
+            Self::Recursive => Some(GlobMatchKind::Label),
+            Self::Literal(name) => {
+                (entry.name() == Hash40::new(name)).then_some(GlobMatchKind::Label)
+            }
+            Self::Wildcard(pattern) => {
+                if let Some(label) = label {
+                    return wildcard_match(pattern.as_bytes(), label.as_bytes())
+                        .then_some(GlobMatchKind::Label);
+                }
+
+                // Without a label, only patterns which don't depend on the name itself can match
+                if *pattern == "*" {
+                    return Some(GlobMatchKind::Hash);
+                }
+
+                let extension = pattern.strip_prefix("*.")?;
+                if extension.contains(['*', '?']) {
+                    return None;
+                }
+
+                (entry.is_file() && entry.extension() == Hash40::new(extension))
+                    .then_some(GlobMatchKind::Hash)
+            }
+        }
+    }
+}
+
+/// Matches text against a pattern where `*` matches any run of characters
+/// and `?` matches exactly one character
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(b'?') => {
+                p += 1;
+                t += 1;
+            }
+            Some(c) if *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    t = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// The state carried while matching the pattern down the folder hierarchy
+struct GlobState<'a> {
+    engine: &'a SearchEngine,
+    labels: Arc<Mutex<LabelMap>>,
+    matches: Vec<GlobMatch>,
+}
+
+impl<'a> GlobState<'a> {
+    /// Gets the labels of an entry's full path and of its name
+    ///
+    /// ### Arguments
+    /// * `parent` - The label of the entry's parent, where `Some("")` is the root folder
+    ///
+    /// ### Returns
+    /// `(full_path, name)`. The full path is built from the parent's label if it isn't labeled
+    /// itself, so it is `None` when the parent is unlabeled even if the name is known.
+    ///
+    /// ### Notes
+    /// The label map is only locked while looking up this entry, so that other threads
+    /// can use it while a large folder is being walked.
+    fn label_of(&self, entry: &DirEntry, parent: Option<&str>) -> (Option<String>, Option<String>) {
+        let labels = self.labels.lock().unwrap();
+        if let Some(label) = labels.label_of(entry.full_path()) {
+            let name = match label.rsplit_once('/') {
+                Some((_, name)) => name.to_string(),
+                None => label.clone(),
+            };
+            return (Some(label), Some(name));
+        }
+
+        let name = labels.label_of(entry.name());
+        let label = match (parent, &name) {
+            (Some(""), Some(name)) => Some(name.clone()),
+            (Some(parent), Some(name)) => Some(format!("{}/{}", parent, name)),
+            _ => None,
+        };
+        (label, name)
+    }
+
+    fn visit(
+        &mut self,
+        folder: TableIndex<SearchFolder>,
+        folder_label: Option<&str>,
+        components: &[GlobComponent],
+        kind: GlobMatchKind,
+    ) {
+        let component = match components.first() {
+            Some(component) => component,
+            None => return,
+        };
+
+        // `**` can also match no folders at all
+        if let GlobComponent::Recursive = component {
+            self.visit(folder, folder_label, &components[1..], kind);
+        }
+
+        for entry in ReadDir::new(self.engine, folder, 1) {
+            let (label, name) = self.label_of(&entry, folder_label);
+
+            let matched_by = match component.matches(&entry, name.as_deref()) {
+                Some(matched_by) => matched_by,
+                None => continue,
+            };
+
+            let kind = if matched_by == GlobMatchKind::Hash {
+                GlobMatchKind::Hash
+            } else {
+                kind
+            };
+
+            let is_recursive = matches!(component, GlobComponent::Recursive);
+
+            if components.len() == 1 {
+                self.matches.push(GlobMatch {
+                    path: entry.index(),
+                    full_path: entry.full_path(),
+                    kind: entry.kind(),
+                    label: label.clone(),
+                    matched_by: kind,
+                });
+            }
+
+            if let Some(child) = entry.folder() {
+                // `**` stays at the front of the pattern while descending, everything else is consumed
+                let remaining = if is_recursive {
+                    components
+                } else {
+                    &components[1..]
+                };
+                self.visit(child, label.as_deref(), remaining, kind);
+            }
+        }
+    }
+}
+
+impl SearchEngine {
+    /// Finds every path which matches a glob pattern
+    ///
+    /// ### Arguments
+    /// * `pattern` - The pattern to match, relative to the root of the filesystem.
+    ///   Components are separated by `/`, where `*` matches any run of characters in
+    ///   a component, `?` matches a single character, and `**` matches any number of folders.
+    ///
+    /// ### Returns
+    /// Every matching file and folder, in the order they were visited
+    ///
+    /// ### Panicking
+    /// Panics if the engine has not been [resolved](Self::resolve)
+    ///
+    /// ### Notes
+    /// * Components without wildcards are matched by hash, so they don't need to be labeled
+    /// * Wildcard components are matched against the labels in the [`Hash40`] label map
+    /// * Names without a label can only be matched by `*`, by `**`, and by `*.ext` where `ext`
+    ///   has no wildcards, which compares the hash of the file's extension. Every other wildcard
+    ///   component, such as `c0?` or `model_*.nutexb`, silently skips unlabeled names, so the
+    ///   result is incomplete unless the label map covers the folders being searched.
+    ///   Matches made without a label are reported as [`GlobMatchKind::Hash`].
+    pub fn glob(&self, pattern: impl AsRef<str>) -> Vec<GlobMatch> {
+        let pattern = pattern.as_ref().trim_matches('/');
+        let components: Vec<_> = pattern.split('/').map(GlobComponent::new).collect();
+
+        // Skip straight to the deepest folder which doesn't need any matching
+        let literal_count = components
+            .iter()
+            .take(components.len() - 1)
+            .take_while(|component| matches!(component, GlobComponent::Literal(_)))
+            .count();
+
+        let (start, start_label) = if literal_count == 0 {
+            ("/".to_string(), String::new())
+        } else {
+            let prefix = pattern
+                .split('/')
+                .take(literal_count)
+                .collect::<Vec<_>>()
+                .join("/");
+            (prefix.clone(), prefix)
+        };
+
+        let folder = match self.folder_lookup.get(&Hash40::new(&start)) {
+            Some(folder) => *folder,
+            None => return vec![],
+        };
+
+        let mut state = GlobState {
+            engine: self,
+            labels: Hash40::label_map(),
+            matches: vec![],
+        };
+
+        state.visit(
+            folder,
+            Some(&start_label),
+            &components[literal_count..],
+            GlobMatchKind::Label,
+        );
+
+        state.matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_engine() -> SearchEngine {
+        Hash40::label_map().lock().unwrap().add_labels(
+            [
+                "fighter",
+                "mario",
+                "model",
+                "body",
+                "c00",
+                "c01",
+                "model.numdlb",
+                "def_mario_001_col.nutexb",
+                "glob_test",
+            ]
+            .map(String::from),
+        );
+
+        let mut engine = SearchEngine::new();
+        engine.add_file("fighter/mario/model/body/c00/model.numdlb");
+        engine.add_file("fighter/mario/model/body/c00/def_mario_001_col.nutexb");
+        engine.add_file("fighter/mario/model/body/c01/model.numdlb");
+        engine.add_file("glob_test/glob_unlabeled_folder/c00/model.numdlb");
+        engine
+    }
+
+    #[test]
+    fn wildcards_match_labels() {
+        let engine = build_engine();
+
+        let matches = engine.glob("fighter/mario/model/body/c0?/*.numdlb");
+        let labels: Vec<_> = matches
+            .iter()
+            .map(|matched| matched.label.as_deref())
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                Some("fighter/mario/model/body/c00/model.numdlb"),
+                Some("fighter/mario/model/body/c01/model.numdlb"),
+            ]
+        );
+        assert!(matches
+            .iter()
+            .all(|matched| matched.matched_by == GlobMatchKind::Label));
+
+        let files = engine.glob("fighter/**/*.nutexb");
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].kind, EntryKind::File);
+
+        assert!(engine.glob("fighter/luigi/*").is_empty());
+    }
+
+    #[test]
+    fn unlabeled_folders_leave_their_contents_unlabeled() {
+        let engine = build_engine();
+
+        let matches = engine.glob("glob_test/*/c00/*.numdlb");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            matches[0].full_path,
+            Hash40::new("glob_test/glob_unlabeled_folder/c00/model.numdlb")
+        );
+        assert_eq!(matches[0].label, None);
+        assert_eq!(matches[0].matched_by, GlobMatchKind::Hash);
+
+        // Labeled names are still matched below an unlabeled folder
+        let matches = engine.glob("glob_test/*/c0?/model.*");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].label, None);
+    }
+}
@@ -1,6 +1,4 @@
 use std::{
-    cell::{Ref, RefMut},
-    collections::BTreeMap,
     io::{Seek, Write},
     path::Path,
     rc::Rc,
@@ -10,11 +8,14 @@ use std::{
 use crate::Hashable;
 
 use super::{
+    lookup::HashLookup,
     read_table,
-    table::{TableCell, TableMaker},
+    table::{ResolveError, Table, TableIndex, TableLinkedReference, TableMaker},
     HashKey,
 };
 
+pub mod dir;
+pub mod glob;
 pub mod types;
 use binrw::BinWrite;
 use camino::{Utf8Path, Utf8PathBuf};
@@ -22,21 +23,48 @@ use hash40::Hash40;
 use types::*;
 
 /// The engine to drive search filesystem accessing/modification
+#[derive(Clone)]
 pub struct SearchEngine {
     /// The lookup from hash -> folder
-    pub(crate) folder_lookup: BTreeMap<Hash40, TableCell<SearchFolder>>,
+    pub(crate) folder_lookup: HashLookup<TableIndex<SearchFolder>>,
 
     /// The lookup from hash -> path
-    pub(crate) path_lookup: BTreeMap<Hash40, TableCell<SearchPath>>,
+    pub(crate) path_lookup: HashLookup<TableIndex<SearchPath>>,
 
     /// The table of folders
-    pub folders: Vec<TableCell<SearchFolder>>,
+    pub folders: Table<SearchFolder>,
 
     /// The table of paths
-    pub paths: Vec<TableCell<SearchPath>>,
+    pub paths: Table<SearchPath>,
 }
 
 impl SearchEngine {
+    /// Creates an engine which only contains the root folder
+    pub fn new() -> Self {
+        let mut root = SearchFolder {
+            full_path: Hash40::new("/"),
+            parent: Hash40::new(""),
+            name: Hash40::new(""),
+            file_count: 0,
+            folder_count: 0,
+            children: TableLinkedReference::invalid(),
+        };
+        root.children.replace([]);
+
+        let mut folders = Table::new();
+        let root = folders.push(root);
+
+        let mut folder_lookup = HashLookup::new();
+        folder_lookup.insert(Hash40::new("/"), root);
+
+        Self {
+            folder_lookup,
+            path_lookup: HashLookup::new(),
+            folders,
+            paths: Table::new(),
+        }
+    }
+
     /// Reads the required data to construct an engine from the specified folder
     ///
     /// ### Arguments
@@ -53,23 +81,23 @@ impl SearchEngine {
     pub fn from_directory(path: impl AsRef<Path>) -> binrw::BinResult<Self> {
         let path = path.as_ref();
 
-        let folders: Vec<TableCell<SearchFolder>> =
+        let folders: Table<SearchFolder> =
             read_table(&path.join("search_folders.bin"), SearchFolder::REPR_SIZE)?;
-        let paths: Vec<TableCell<SearchPath>> =
+        let paths: Table<SearchPath> =
             read_table(&path.join("search_paths.bin"), SearchPath::REPR_SIZE)?;
 
         let folder_lookup = folders
-            .iter()
-            .map(|cell| (cell.get().full_path, cell.clone()))
+            .iter_indexed()
+            .map(|(index, folder)| (folder.full_path, index))
             .collect();
 
         let path_lookup = paths
-            .iter()
-            .filter_map(|cell| {
-                if cell.get().full_path == Hash40::new("") {
+            .iter_indexed()
+            .filter_map(|(index, path)| {
+                if path.full_path == Hash40::new("") {
                     None
                 } else {
-                    Some((cell.get().full_path, cell.clone()))
+                    Some((path.full_path, index))
                 }
             })
             .collect();
@@ -85,61 +113,74 @@ impl SearchEngine {
     /// Resolves all references in the engine
     ///
     /// ### Panicking
-    /// * There is an issue any structures. See the following for more:
+    /// * There is an issue with any structures. Use [`try_resolve`](Self::try_resolve)
+    ///   to handle that instead.
+    pub fn resolve(&mut self) {
+        if let Err(error) = self.try_resolve() {
+            panic!("{}", error);
+        }
+    }
+
+    /// Resolves all references in the engine
+    ///
+    /// ### Returns
+    /// * `Ok(())` - Every reference was resolved
+    /// * `Err(_)` - There is an issue with any structures, and the engine is left
+    ///   partially resolved. See the following for more:
     ///     * [`SearchFolder::resolve`]
     ///     * [`SearchPath::resolve`]
-    pub fn resolve(&self) {
-        for path in self.paths.iter() {
-            path.get_mut().resolve(&self.paths, &self.folder_lookup);
+    pub fn try_resolve(&mut self) -> Result<(), ResolveError> {
+        let path_count = self.paths.len();
+        for path in self.paths.iter_mut() {
+            path.resolve(path_count, &self.folder_lookup)?;
         }
 
-        for folder in self.folders.iter() {
-            folder.get_mut().resolve(&self.paths);
+        for folder in self.folders.iter_mut() {
+            folder.resolve(&self.paths)?;
         }
+
+        Ok(())
     }
 
     /// Gets an immutable reference to a folder by hash, if it exists
-    pub fn get_folder(&self, hash: impl Hashable) -> Option<Ref<'_, SearchFolder>> {
-        self.folder_lookup.get(&hash.to_hash()).map(TableCell::get)
+    pub fn get_folder(&self, hash: impl Hashable) -> Option<&SearchFolder> {
+        self.folder_lookup
+            .get(&hash.to_hash())
+            .map(|index| &self.folders[*index])
     }
 
     /// Gets the mutable reference to a folder by hash, if it exists
-    pub fn get_folder_mut(&self, hash: impl Hashable) -> Option<RefMut<'_, SearchFolder>> {
+    pub fn get_folder_mut(&mut self, hash: impl Hashable) -> Option<&mut SearchFolder> {
         self.folder_lookup
             .get(&hash.to_hash())
-            .map(TableCell::get_mut)
+            .map(|index| &mut self.folders[*index])
     }
 
     /// Gets an immutable reference to a path by hash, if it exists
-    pub fn get_path(&self, hash: impl Hashable) -> Option<Ref<'_, SearchPath>> {
-        self.path_lookup.get(&hash.to_hash()).map(TableCell::get)
+    pub fn get_path(&self, hash: impl Hashable) -> Option<&SearchPath> {
+        self.path_lookup
+            .get(&hash.to_hash())
+            .map(|index| &self.paths[*index])
     }
 
     /// Gets the mutable reference to a path by hash, if it exists
-    pub fn get_path_mut(&self, hash: impl Hashable) -> Option<RefMut<'_, SearchPath>> {
+    pub fn get_path_mut(&mut self, hash: impl Hashable) -> Option<&mut SearchPath> {
         self.path_lookup
             .get(&hash.to_hash())
-            .map(TableCell::get_mut)
+            .map(|index| &mut self.paths[*index])
     }
 
     pub fn reorganize(self) -> Self {
-        let writer = SearchWriter::from_engine(self);
-        Self {
-            folder_lookup: writer.folder_lookup,
-            path_lookup: writer.path_lookup,
-            folders: writer.folders.into_inner(),
-            paths: writer.paths.into_inner(),
-        }
+        SearchWriter::from_engine(self).into_engine()
     }
 
-    pub fn add_file(&mut self, file: impl AsRef<str>) -> TableCell<SearchPath> {
+    pub fn add_file(&mut self, file: impl AsRef<str>) -> TableIndex<SearchPath> {
         let file = file.as_ref();
 
-        let mut current_folder = self
+        let mut current_folder = *self
             .folder_lookup
             .get(&Hash40::new("/"))
-            .expect("Root folder should be present")
-            .clone();
+            .expect("Root folder should be present");
 
         let path = Utf8Path::new(file);
         let components: Vec<_> = path.components().collect();
@@ -149,43 +190,54 @@ impl SearchEngine {
         for component in components.into_iter().take(components_len - 1) {
             current_path.push(&component);
 
-            let child = current_folder
-                .get()
-                .get_child_by_name(component.as_str())
-                .map(|folder| folder.raw_folder().clone());
+            let child = self.folders[current_folder]
+                .get_child_by_name(&self.paths, component.as_str())
+                .map(SearchPath::folder);
 
             current_folder = if let Some(child) = child {
                 child
             } else {
-                let new_path = TableCell::new(SearchPath::from_str(current_path.as_str()).unwrap());
-                self.path_lookup
-                    .insert(new_path.get().full_path, new_path.clone());
-                current_folder.get_mut().push_child(new_path.clone());
-                let new_path_ = new_path.get();
-                let folder = new_path_.raw_folder().clone();
-                self.folder_lookup
-                    .insert(folder.get().full_path, folder.clone());
+                let mut folder = SearchFolder::from_str(current_path.as_str()).unwrap();
+                folder.children.replace([]);
+                let folder_hash = folder.full_path;
+                let folder = self.folders.push(folder);
+                self.folder_lookup.insert(folder_hash, folder);
+
+                let mut new_path = SearchPath::from_str(current_path.as_str()).unwrap();
+                new_path.set_folder(folder);
+                let path_hash = new_path.full_path;
+                let new_path = self.paths.push(new_path);
+                self.path_lookup.insert(path_hash, new_path);
+                self.folders[current_folder].push_child(&mut self.paths, new_path);
                 folder
             };
         }
 
-        let path = TableCell::new(SearchPath::from_str(path.as_str()).unwrap());
-        if path.get().is_folder() {
+        let path = SearchPath::from_str(path.as_str()).unwrap();
+        if path.is_folder() {
             panic!("Added file must be a file!");
         }
-        self.path_lookup.insert(path.get().full_path, path.clone());
-        current_folder.get_mut().push_child(path.clone());
+        let path_hash = path.full_path;
+        let path = self.paths.push(path);
+        self.path_lookup.insert(path_hash, path);
+        self.folders[current_folder].push_child(&mut self.paths, path);
         path
     }
 }
 
+impl Default for SearchEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Re-organizer and serializer for the search filesystem
 pub struct SearchWriter {
     /// The hash -> folder lookup
-    folder_lookup: BTreeMap<Hash40, TableCell<SearchFolder>>,
+    folder_lookup: HashLookup<TableIndex<SearchFolder>>,
 
     /// The hash -> path lookup
-    path_lookup: BTreeMap<Hash40, TableCell<SearchPath>>,
+    path_lookup: HashLookup<TableIndex<SearchPath>>,
 
     /// The re-organized table of folders
     pub folders: TableMaker<SearchFolder>,
@@ -199,9 +251,10 @@ impl SearchWriter {
     ///
     /// ### Panicking
     /// * The folder is already in the table, or any of its paths are
-    fn push_folder(&mut self, folder: &TableCell<SearchFolder>) {
-        self.folders.push(folder.clone());
-        for path in folder.get().children.cells().iter() {
+    fn push_folder(&mut self, folder: TableIndex<SearchFolder>) {
+        self.folders.push(folder);
+        let children = self.folders[folder].children.indices().clone();
+        for path in children.iter() {
             self.push_path(path);
         }
     }
@@ -210,8 +263,8 @@ impl SearchWriter {
     ///
     /// ### Panicking
     /// * The path is already in the table
-    fn push_path(&mut self, path: &TableCell<SearchPath>) {
-        self.paths.push(path.clone());
+    fn push_path(&mut self, path: TableIndex<SearchPath>) {
+        self.paths.push(path);
     }
 
     /// Consumes the engine and creates a writer, re-organizing all of the
@@ -224,23 +277,63 @@ impl SearchWriter {
             folder_lookup,
             path_lookup,
             folders,
-            ..
+            paths,
         } = engine;
 
+        let folder_indices: Vec<_> = folders.indices().collect();
+
         let mut this = Self {
             folder_lookup,
             path_lookup,
 
-            folders: TableMaker::new(),
-            paths: TableMaker::new(),
+            folders: TableMaker::new(folders),
+            paths: TableMaker::new(paths),
         };
 
-        for folder in folders {
-            this.push_folder(&folder);
+        for folder in folder_indices {
+            this.push_folder(folder);
         }
         this
     }
 
+    /// Consumes the writer, building a new engine out of the re-organized tables
+    ///
+    /// ### Notes
+    /// * Any paths which are not the child of a folder are dropped, along with their lookups
+    pub(crate) fn into_engine(self) -> SearchEngine {
+        let (mut folders, folder_remap) = self.folders.into_parts();
+        let (mut paths, path_remap) = self.paths.into_parts();
+
+        for folder in folders.iter_mut() {
+            folder.remap(&path_remap);
+        }
+
+        for path in paths.iter_mut() {
+            path.remap(&folder_remap, &path_remap);
+        }
+
+        let folder_lookup = self
+            .folder_lookup
+            .into_iter()
+            .map(|(hash, folder)| (hash, folder_remap.get(folder)))
+            .filter(|(_, folder)| folder.is_valid())
+            .collect();
+
+        let path_lookup = self
+            .path_lookup
+            .into_iter()
+            .map(|(hash, path)| (hash, path_remap.get(path)))
+            .filter(|(_, path)| path.is_valid())
+            .collect();
+
+        SearchEngine {
+            folder_lookup,
+            path_lookup,
+            folders,
+            paths,
+        }
+    }
+
     /// Serializes the tables and lookups out to the specified directory
     ///
     /// ### Arguments
@@ -268,20 +361,20 @@ impl SearchWriter {
 
         let mut links = std::io::Cursor::new(vec![]);
         let mut paths = std::io::Cursor::new(vec![]);
-        for path in this.paths.iter() {
-            this.paths.get_index(path).write_to(&mut links)?;
+        for (count, path) in this.paths.iter().enumerate() {
+            (count as u32).write_to(&mut links)?;
             path.write_with_args(&mut paths, Rc::clone(&this))?;
         }
 
         let mut folder_lookup = std::io::Cursor::new(vec![]);
-        for (hash, path) in this.folder_lookup.iter() {
-            HashKey::new(*hash, this.folders.get_index(path) as usize)
+        for (hash, path) in this.folder_lookup.iter_sorted() {
+            HashKey::new(*hash, this.folders.get_index(*path) as usize)
                 .write_to(&mut folder_lookup)?;
         }
 
         let mut path_lookup = std::io::Cursor::new(vec![]);
-        for (hash, path) in this.path_lookup.iter() {
-            HashKey::new(*hash, this.paths.get_index(path) as usize).write_to(&mut path_lookup)?;
+        for (hash, path) in this.path_lookup.iter_sorted() {
+            HashKey::new(*hash, this.paths.get_index(*path) as usize).write_to(&mut path_lookup)?;
         }
 
         std::fs::write(path.join("search_folders.bin"), folders.into_inner())?;
@@ -299,16 +392,16 @@ impl SearchWriter {
     pub fn to_memory<W: Seek + Write>(self, writer: &mut W) -> binrw::BinResult<()> {
         let this = Rc::new(self);
 
-        for (hash, cell) in this.folder_lookup.iter() {
-            HashKey::new(*hash, this.folders.get_index(cell) as usize).write_to(writer)?;
+        for (hash, cell) in this.folder_lookup.iter_sorted() {
+            HashKey::new(*hash, this.folders.get_index(*cell) as usize).write_to(writer)?;
         }
 
         for folder in this.folders.iter() {
             folder.write_with_args(writer, Rc::clone(&this))?;
         }
 
-        for (hash, cell) in this.path_lookup.iter() {
-            HashKey::new(*hash, this.paths.get_index(cell) as usize).write_to(writer)?;
+        for (hash, cell) in this.path_lookup.iter_sorted() {
+            HashKey::new(*hash, this.paths.get_index(*cell) as usize).write_to(writer)?;
         }
 
         for x in 0..this.paths.len() {
@@ -1,14 +1,11 @@
-use std::{
-    cell::{Ref, RefMut},
-    collections::BTreeMap, rc::Rc, str::FromStr
-};
+use std::{rc::Rc, str::FromStr};
 
 use binrw::{binread, BinWrite};
 use camino::Utf8Path;
 use hash40::Hash40;
 
 use crate::{
-    engines::{table::*, HashKey},
+    engines::{lookup::HashLookup, table::*, HashKey},
     INVALID_INDEX, INVALID_INDEX32, Hashable,
 };
 
@@ -22,6 +19,8 @@ use super::SearchWriter;
 /// The search folder is the easiest way to find files which are relative
 /// to each other, or relative to the folder itself.
 #[binread]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SearchFolder {
     #[br(temp)]
     path_and_folder_count: HashKey,
@@ -102,6 +101,8 @@ multi_reference!(
 /// and point to the next path that is the child of their common
 /// parent, meaning paths only have one owning [folder](SearchFolder)
 #[binread]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SearchPath {
     #[br(temp)]
     path_and_next_index: HashKey,
@@ -174,15 +175,16 @@ impl SearchPathFolderReference {
     /// * `hash` - The hash of the path which this reference belongs to
     /// * `lookup` - The hash -> [`SearchFolder`] lookup to find the parent
     /// 
-    /// ### Panicking
-    /// * If this reference should be valid but the path cannot be found in the lookup
-    pub fn resolve(&mut self, hash: Hash40, lookup: &BTreeMap<Hash40, TableCell<SearchFolder>>) {
-        let mut tmp = Self::None;
-        std::mem::swap(self, &mut tmp);
-        *self = match tmp {
-            Self::Unresolved(_) => Self::Folder(lookup.get(&hash).cloned().expect("Lookup should contain hash")),
-            other => other,
+    /// ### Returns
+    /// * `Ok(())` - The reference was resolved
+    /// * `Err(_)` - This reference should be valid but the path cannot be found in the lookup
+    pub fn resolve(&mut self, hash: Hash40, lookup: &HashLookup<TableIndex<SearchFolder>>) -> Result<(), ResolveError> {
+        if let Self::Unresolved(_) = self {
+            let folder = lookup.get(&hash).copied().ok_or(ResolveError::MissingFolder(hash))?;
+            *self = Self::Folder(folder);
         }
+
+        Ok(())
     }
 }
 
@@ -190,17 +192,18 @@ impl SearchPathNextReference {
     /// Resolves this reference
     /// 
     /// ### Arguments
-    /// * `paths` - The slice of paths to resolve with
+    /// * `path_count` - The number of paths in the table to resolve with
     /// 
-    /// ### Panicking
-    /// * This reference's unresolved index is OOB of `paths`
-    pub fn resolve(&mut self, paths: &[TableCell<SearchPath>]) {
-        let mut tmp = Self::None;
-        std::mem::swap(self, &mut tmp);
-        *self = match tmp {
-            Self::Unresolved(index) => Self::Path(paths[index].clone()),
-            other => other,
+    /// ### Returns
+    /// * `Ok(())` - The reference was resolved
+    /// * `Err(_)` - This reference's unresolved index is OOB of the path table
+    pub fn resolve(&mut self, path_count: usize) -> Result<(), ResolveError> {
+        if let Self::Unresolved(index) = *self {
+            check_bounds(index, path_count)?;
+            *self = Self::Path(TableIndex::new(index));
         }
+
+        Ok(())
     }
 }
 
@@ -213,10 +216,11 @@ impl SearchFolder {
     /// ### Arguments
     /// * `paths` - The slice of [`SearchPath`] to resolve with
     /// 
-    /// ### Panicking
-    /// * There was an error resolving the underlying [`TableLinkedReference`]
-    pub fn resolve(&mut self, paths: &[TableCell<SearchPath>]) {
-        self.children.resolve(paths);
+    /// ### Returns
+    /// * `Ok(())` - The folder was resolved
+    /// * `Err(_)` - There was an error resolving the underlying [`TableLinkedReference`]
+    pub fn resolve(&mut self, paths: &[SearchPath]) -> Result<(), ResolveError> {
+        self.children.resolve(paths)
     }
 
     /// Checks if this folder is resolved
@@ -224,19 +228,30 @@ impl SearchFolder {
         self.children.is_resolved()
     }
 
-    pub fn get_child_by_name(&self, name: impl Hashable) -> Option<Ref<'_, SearchPath>> {
+    pub fn get_child_by_name<'a>(
+        &self,
+        paths: &'a [SearchPath],
+        name: impl Hashable,
+    ) -> Option<&'a SearchPath> {
         let name = name.to_hash();
 
-        self.children.iter().find(|child| child.name == name)
+        self.children
+            .iter()
+            .map(|child| &paths[child.index()])
+            .find(|child| child.name == name)
     }
 
-    pub fn push_child(&mut self, child: TableCell<SearchPath>) {
-        if let Some(last) = self.children.cells().last() {
-            last.get_mut().set_path(child.clone());
+    pub fn push_child(&mut self, paths: &mut [SearchPath], child: TableIndex<SearchPath>) {
+        if let Some(last) = self.children.iter().last() {
+            paths[last.index()].set_path(child);
         }
 
         self.children.push(child);
     }
+
+    pub(crate) fn remap(&mut self, paths: &TableRemap<SearchPath>) {
+        self.children.remap(paths);
+    }
 }
 
 impl FromStr for SearchFolder {
@@ -267,20 +282,21 @@ impl SearchPath {
     /// Resolves this path
     /// 
     /// ### Arguments
-    /// * `paths` - The slice of paths to resolve the next reference with
+    /// * `path_count` - The number of paths to resolve the next reference with
     /// * `folder_lookup` - The hash -> [`SearchFolder`] lookup to use when resolving
-    /// folders
+    ///   folders
     /// 
-    /// ### Panicking
-    /// * The next reference is OOB of `paths`
-    /// * The folder cannot be found in `folder_lookup` when it is a folder
+    /// ### Returns
+    /// * `Ok(())` - The path was resolved
+    /// * `Err(_)` - The next reference is OOB of the path table, or the folder
+    ///   cannot be found in `folder_lookup` when it is a folder
     pub fn resolve(
         &mut self,
-        paths: &[TableCell<SearchPath>],
-        folder_lookup: &BTreeMap<Hash40, TableCell<SearchFolder>>,
-    ) {
-        self.folder.resolve(self.full_path, folder_lookup);
-        self.next.resolve(paths);
+        path_count: usize,
+        folder_lookup: &HashLookup<TableIndex<SearchFolder>>,
+    ) -> Result<(), ResolveError> {
+        self.folder.resolve(self.full_path, folder_lookup)?;
+        self.next.resolve(path_count)
     }
 
     /// Checks if this path is resolved
@@ -290,7 +306,21 @@ impl SearchPath {
 
     /// Checks if this path represents a [`SearchFolder`]
     pub fn is_folder(&self) -> bool {
-        self.folder.is_folder()
+        !self.folder.is_none()
+    }
+
+    pub(crate) fn remap(
+        &mut self,
+        folders: &TableRemap<SearchFolder>,
+        paths: &TableRemap<SearchPath>,
+    ) {
+        if let SearchPathFolderReference::Folder(folder) = &mut self.folder {
+            *folder = folders.get(*folder);
+        }
+
+        if let SearchPathNextReference::Path(next) = &mut self.next {
+            *next = paths.get(*next);
+        }
     }
 }
 
@@ -305,10 +335,10 @@ impl FromStr for SearchPath {
         let name = path.file_name().ok_or(SearchFromStrError::MissingFileName)?.to_hash();
         let extension = path.extension().map_or(Hash40::new(""), Hashable::to_hash);
 
+        // The folder itself has to be added to the engine's table, so this is left
+        // unresolved until it is
         let folder = if extension == Hash40::new("") {
-            let mut folder = SearchFolder::from_str(s)?;
-            folder.children.replace(vec![]);
-            SearchPathFolderReference::Folder(TableCell::new(folder))
+            SearchPathFolderReference::Unresolved(INVALID_INDEX)
         } else {
             SearchPathFolderReference::None
         };
@@ -328,8 +358,8 @@ expose_reference!(SearchPath, folder, SearchFolder, SearchPathFolderReference, F
 expose_reference!(optional, SearchPath, next, SearchPath, SearchPathNextReference, Path);
 
 impl LinkedReference for SearchPath {
-    fn next(&self) -> Option<TableCell<Self>> {
-        (!self.next.is_none()).then(|| self.next.path().clone())
+    fn next(&self) -> Option<TableIndex<Self>> {
+        (!self.next.is_none()).then(|| self.next.path())
     }
 }
 
@@ -348,7 +378,7 @@ impl BinWrite for SearchFolder {
         if self.children.is_empty() {
             INVALID_INDEX32.write_options(writer, options, ())?;
         } else {
-            args.paths.get_index(&self.children.cells()[0]).write_options(writer, options, ())?;
+            args.paths.get_index(self.children.get(0)).write_options(writer, options, ())?;
         }
         [0u8; 4].write_options(writer, options, ())
     }
@@ -1,6 +1,4 @@
 use std::{
-    cell::{Ref, RefMut},
-    collections::BTreeMap,
     io::{Seek, Write},
     path::Path,
     rc::Rc,
@@ -9,11 +7,12 @@ use std::{
 pub mod types;
 use binrw::BinWrite;
 use hash40::Hash40;
+use thiserror::Error;
 use types::*;
 
 use crate::Hashable;
 
-use super::{read_table, table::*, HashKey};
+use super::{lookup::HashLookup, read_table, table::*, HashKey, VariantKind};
 
 /// File engine to access data in the stream filesystem
 ///
@@ -22,25 +21,81 @@ use super::{read_table, table::*, HashKey};
 /// of data from code outside of scope of the application.
 ///
 /// For example, BGM and movie files will be found here
+#[derive(Clone)]
 pub struct StreamEngine {
     /// The lookup from the hash of a file path to the
     /// [`StreamPath`]
-    pub(crate) path_lookup: BTreeMap<Hash40, TableCell<StreamPath>>,
+    pub(crate) path_lookup: HashLookup<TableIndex<StreamPath>>,
 
     /// All of the folders in the stream filesystem
-    pub folders: Vec<TableCell<StreamFolder>>,
+    pub folders: Table<StreamFolder>,
 
     /// All of the paths in the stream filesystem
-    pub paths: Vec<TableCell<StreamPath>>,
+    pub paths: Table<StreamPath>,
 
     /// All of the stream links in the stream filesystem
-    pub links: Vec<TableCell<StreamLink>>,
+    pub links: Table<StreamLink>,
 
     /// All of the stream metadatas in the stream filesystem
-    pub metadatas: Vec<TableCell<StreamMetadata>>,
+    pub metadatas: Table<StreamMetadata>,
+}
+
+/// The data that the game loads for one locale or region of a stream file
+///
+/// Returned by [`StreamEngine::resolve_variant`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamResolution {
+    /// The link that the game picks for the locale or region
+    pub link: TableIndex<StreamLink>,
+
+    /// The metadata that the link redirects to
+    pub metadata: TableIndex<StreamMetadata>,
+
+    /// Every other locale or region of the file which loads the same metadata, in link order
+    ///
+    /// The tables don't record which of the variants the data was made for, so when this
+    /// isn't empty the requested variant may be falling back to them, or they to it.
+    pub shared_with: Vec<StreamVariant>,
+}
+
+impl StreamResolution {
+    /// Whether the data is shared with another locale or region, in either direction
+    pub fn is_shared(&self) -> bool {
+        !self.shared_with.is_empty()
+    }
+}
+
+/// An error which prevented the fallbacks of a stream file from being edited
+#[derive(Debug, Error)]
+pub enum StreamVariantError {
+    #[error("Stream file {0} does not exist")]
+    MissingFile(Hash40),
+
+    #[error("Stream file {file} has no link for {variant:?}")]
+    MissingVariant {
+        file: Hash40,
+        variant: StreamVariant,
+    },
+
+    #[error("Stream file {file} can't fall back from {variant:?} to itself")]
+    SelfFallback {
+        file: Hash40,
+        variant: StreamVariant,
+    },
 }
 
 impl StreamEngine {
+    /// Creates an engine with no folders or files
+    pub fn new() -> Self {
+        Self {
+            path_lookup: HashLookup::new(),
+            folders: Table::new(),
+            paths: Table::new(),
+            links: Table::new(),
+            metadatas: Table::new(),
+        }
+    }
+
     /// Reads the tables from the provided folder and constructs a new engine
     ///
     /// ### Arguments
@@ -60,21 +115,21 @@ impl StreamEngine {
         let path = path.as_ref();
 
         // Read all of the tables
-        let folders: Vec<TableCell<StreamFolder>> =
+        let folders: Table<StreamFolder> =
             read_table(&path.join("stream_folders.bin"), StreamFolder::REPR_SIZE)?;
-        let paths: Vec<TableCell<StreamPath>> =
+        let paths: Table<StreamPath> =
             read_table(&path.join("stream_paths.bin"), StreamPath::REPR_SIZE)?;
-        let links: Vec<TableCell<StreamLink>> =
+        let links: Table<StreamLink> =
             read_table(&path.join("stream_links.bin"), StreamLink::REPR_SIZE)?;
-        let metadatas: Vec<TableCell<StreamMetadata>> = read_table(
+        let metadatas: Table<StreamMetadata> = read_table(
             &path.join("stream_metadatas.bin"),
             StreamMetadata::REPR_SIZE,
         )?;
 
         // Generate the lookup
         let path_lookup = paths
-            .iter()
-            .map(|path| (path.get().full_path, path.clone()))
+            .iter_indexed()
+            .map(|(index, path)| (path.full_path, index))
             .collect();
 
         Ok(Self {
@@ -89,20 +144,35 @@ impl StreamEngine {
     /// Resolves all of the tables in the filesystem
     ///
     /// ### Panicking
-    /// This function can panic if there is unexpected data in the
-    /// tables, such as an OOB index.
-    pub fn resolve(&self) {
-        for mut folder in self.folders.iter().map(TableCell::get_mut) {
-            folder.resolve(&self.paths);
+    /// This function will panic if there is unexpected data in the
+    /// tables, such as an OOB index. Use [`try_resolve`](Self::try_resolve)
+    /// to handle that instead.
+    pub fn resolve(&mut self) {
+        if let Err(error) = self.try_resolve() {
+            panic!("{}", error);
         }
+    }
 
-        for mut path in self.paths.iter().map(TableCell::get_mut) {
-            path.resolve(&self.links);
+    /// Resolves all of the tables in the filesystem
+    ///
+    /// ### Returns
+    /// * `Ok(())` - Every table was resolved
+    /// * `Err(_)` - An entry refers to something that doesn't exist. The filesystem is
+    ///   left partially resolved, and should not be used further.
+    pub fn try_resolve(&mut self) -> Result<(), ResolveError> {
+        for folder in self.folders.iter_mut() {
+            folder.resolve(&self.paths)?;
         }
 
-        for mut link in self.links.iter().map(TableCell::get_mut) {
-            link.resolve(&self.metadatas);
+        for path in self.paths.iter_mut() {
+            path.resolve(&self.links)?;
         }
+
+        for link in self.links.iter_mut() {
+            link.resolve(&self.metadatas)?;
+        }
+
+        Ok(())
     }
 
     /// Gets an immutable path reference from the provided path
@@ -113,8 +183,10 @@ impl StreamEngine {
     /// ### Returns
     /// * `Some(_)` - The path for the specified hash exists
     /// * `None` - The path for the specified hash does not exist
-    pub fn get_path(&self, hash: impl Hashable) -> Option<Ref<'_, StreamPath>> {
-        self.path_lookup.get(&hash.to_hash()).map(TableCell::get)
+    pub fn get_path(&self, hash: impl Hashable) -> Option<&StreamPath> {
+        self.path_lookup
+            .get(&hash.to_hash())
+            .map(|index| &self.paths[*index])
     }
 
     /// Gets a mutable path reference from the provided path
@@ -125,21 +197,224 @@ impl StreamEngine {
     /// ### Returns
     /// * `Some(_)` - The path for the specified hash exists
     /// * `None` - The path for the specified hash does not exist
-    pub fn get_path_mut(&self, hash: impl Hashable) -> Option<RefMut<'_, StreamPath>> {
+    pub fn get_path_mut(&mut self, hash: impl Hashable) -> Option<&mut StreamPath> {
         self.path_lookup
             .get(&hash.to_hash())
-            .map(TableCell::get_mut)
+            .map(|index| &mut self.paths[*index])
     }
 
     pub fn reorganize(self) -> Self {
-        let writer = StreamWriter::from_engine(self);
-        Self {
-            path_lookup: writer.path_lookup,
-            folders: writer.folders.into_inner(),
-            paths: writer.paths.into_inner(),
-            links: writer.links.into_inner(),
-            metadatas: writer.metadatas.into_inner(),
+        StreamWriter::from_engine(self).into_engine()
+    }
+
+    /// Finds the link of a stream file for a locale or region
+    fn variant_link(
+        &self,
+        file: Hash40,
+        variant: StreamVariant,
+    ) -> Result<(TableIndex<StreamPath>, usize), StreamVariantError> {
+        let path = *self
+            .path_lookup
+            .get(&file)
+            .ok_or(StreamVariantError::MissingFile(file))?;
+
+        self.paths[path]
+            .variant_position(variant)
+            .map(|position| (path, position))
+            .ok_or(StreamVariantError::MissingVariant { file, variant })
+    }
+
+    /// Finds the data that the game loads for one locale or region of a stream file
+    ///
+    /// ### Arguments
+    /// * `file` - The hash of the file's full path
+    /// * `variant` - The locale or region to load the file for
+    ///
+    /// ### Returns
+    /// * `Some(_)` - The link and metadata which are used, and which other variants share them
+    /// * `None` - The file does not exist, or it has no link for the variant
+    ///
+    /// ### Notes
+    /// Variants fall back to each other by sharing a [`StreamMetadata`]. Nothing in the tables
+    /// says which variant owns a shared metadata, so every variant sharing it is reported in
+    /// [`shared_with`](StreamResolution::shared_with) rather than picking one as the owner.
+    pub fn resolve_variant(
+        &self,
+        file: impl Hashable,
+        variant: impl Into<StreamVariant>,
+    ) -> Option<StreamResolution> {
+        let (path, position) = self.variant_link(file.to_hash(), variant.into()).ok()?;
+        let path = &self.paths[path];
+
+        let link = path.links.get(position);
+        let metadata = self.links[link].metadata();
+
+        let shared_with = path
+            .links
+            .iter()
+            .enumerate()
+            .filter(|(other, link)| *other != position && self.links[*link].metadata() == metadata)
+            .filter_map(|(other, _)| path.variant_at(other))
+            .collect();
+
+        Some(StreamResolution {
+            link,
+            metadata,
+            shared_with,
+        })
+    }
+
+    /// Makes one locale or region of a stream file use the data of another
+    ///
+    /// ### Arguments
+    /// * `file` - The hash of the file's full path
+    /// * `variant` - The locale or region which should fall back
+    /// * `fallback` - The locale or region whose data should be used instead
+    ///
+    /// ### Returns
+    /// * `Ok(_)` - The metadata that the variant now uses
+    /// * `Err(_)` - The file doesn't exist, doesn't have either variant, or they are the same link
+    ///
+    /// ### Notes
+    /// * The metadata that the variant used before is left in the table, and is dropped
+    ///   when the tables are [reorganized](Self::reorganize) if nothing else uses it.
+    /// * The direction of the fallback is not stored, since the tables have no room for it.
+    ///   Afterwards [`resolve_variant`](Self::resolve_variant) reports the two variants as
+    ///   sharing their data, whichever way around they were passed here.
+    pub fn set_fallback(
+        &mut self,
+        file: impl Hashable,
+        variant: impl Into<StreamVariant>,
+        fallback: impl Into<StreamVariant>,
+    ) -> Result<TableIndex<StreamMetadata>, StreamVariantError> {
+        let file = file.to_hash();
+        let variant = variant.into();
+
+        let (path, position) = self.variant_link(file, variant)?;
+        let (_, fallback_position) = self.variant_link(file, fallback.into())?;
+
+        if position == fallback_position {
+            return Err(StreamVariantError::SelfFallback { file, variant });
+        }
+
+        let links = &self.paths[path].links;
+        let link = links.get(position);
+        let metadata = self.links[links.get(fallback_position)].metadata();
+
+        self.links[link].set_metadata(metadata);
+        Ok(metadata)
+    }
+
+    /// Gives one locale or region of a stream file its own data, if it shares it with another
+    ///
+    /// ### Arguments
+    /// * `file` - The hash of the file's full path
+    /// * `variant` - The locale or region which should stop sharing its data
+    ///
+    /// ### Returns
+    /// * `Ok(_)` - The metadata that the variant now uses, which is its own
+    /// * `Err(_)` - The file doesn't exist, or doesn't have the variant
+    ///
+    /// ### Notes
+    /// The new metadata starts as a copy of the shared data, so the variant keeps loading the
+    /// same data until its size and offset are changed. Every other variant keeps using the
+    /// shared metadata. If the data was not shared, the metadata is returned as it is.
+    pub fn clear_fallback(
+        &mut self,
+        file: impl Hashable,
+        variant: impl Into<StreamVariant>,
+    ) -> Result<TableIndex<StreamMetadata>, StreamVariantError> {
+        let file = file.to_hash();
+        let variant = variant.into();
+
+        let resolution = self
+            .resolve_variant(file, variant)
+            .ok_or(StreamVariantError::MissingVariant { file, variant })?;
+
+        if !resolution.is_shared() {
+            return Ok(resolution.metadata);
         }
+
+        let StreamMetadata { size, offset } = self.metadatas[resolution.metadata];
+        let metadata = self.metadatas.push(StreamMetadata { size, offset });
+        self.links[resolution.link].set_metadata(metadata);
+        Ok(metadata)
+    }
+
+    /// Adds a new file to the stream filesystem
+    ///
+    /// ### Arguments
+    /// * `file` - The full path of the file, including the `stream:/` prefix
+    /// * `variants` - Whether the file has a variant for every locale or region
+    ///
+    /// ### Returns
+    /// The index of the new path
+    ///
+    /// ### Panicking
+    /// Panics if the file already exists
+    ///
+    /// ### Notes
+    /// * The file is put in the folder named after the first component of its path,
+    ///   which is created if it doesn't exist yet
+    /// * Every link gets its own empty [`StreamMetadata`], which still needs its size and offset set
+    pub fn add_file(
+        &mut self,
+        file: impl AsRef<str>,
+        variants: VariantKind,
+    ) -> TableIndex<StreamPath> {
+        let file = file.as_ref();
+        if self.get_path(file).is_some() {
+            panic!("File {} already exists!", file);
+        }
+
+        let folder_name = file
+            .trim_start_matches("stream:/")
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .to_hash();
+
+        let folder = self
+            .folders
+            .iter_indexed()
+            .find(|(_, folder)| folder.name == folder_name)
+            .map(|(index, _)| index);
+
+        let folder = match folder {
+            Some(folder) => folder,
+            None => {
+                let mut paths = TableContiguousReference::invalid();
+                paths.replace([]);
+                self.folders.push(StreamFolder {
+                    name: folder_name,
+                    paths,
+                })
+            }
+        };
+
+        let mut links = TableContiguousReference::invalid();
+        links.replace((0..variants.variant_count().max(1)).map(|_| {
+            let metadata = self.metadatas.push(StreamMetadata { size: 0, offset: 0 });
+            self.links.push(StreamLink::new(metadata))
+        }));
+
+        let full_path = file.to_hash();
+        let path = self.paths.push(StreamPath {
+            full_path,
+            links,
+            is_localized: variants == VariantKind::Localized,
+            is_regional: variants == VariantKind::Regional,
+        });
+
+        self.path_lookup.insert(full_path, path);
+        self.folders[folder].paths.push(path);
+        path
+    }
+}
+
+impl Default for StreamEngine {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -153,7 +428,7 @@ impl StreamEngine {
 /// as there might be unreferenced entries.
 pub struct StreamWriter {
     /// The lookup from hash to [`StreamPath`]
-    path_lookup: BTreeMap<Hash40, TableCell<StreamPath>>,
+    path_lookup: HashLookup<TableIndex<StreamPath>>,
 
     /// The new table for folders
     pub(crate) folders: TableMaker<StreamFolder>,
@@ -176,9 +451,10 @@ impl StreamWriter {
     ///
     /// ### Panicking
     /// This function panics if the folder is already present
-    fn push_folder(&mut self, folder: &TableCell<StreamFolder>) {
-        self.folders.push(folder.clone());
-        for path in folder.get().paths.cells().iter() {
+    fn push_folder(&mut self, folder: TableIndex<StreamFolder>) {
+        self.folders.push(folder);
+        let paths = self.folders[folder].paths.indices().clone();
+        for path in paths.iter() {
             self.push_path(path);
         }
     }
@@ -190,9 +466,10 @@ impl StreamWriter {
     ///
     /// ### Panicking
     /// This function panics if the path is already present
-    fn push_path(&mut self, path: &TableCell<StreamPath>) {
-        self.paths.push(path.clone());
-        for link in path.get().links.cells().iter() {
+    fn push_path(&mut self, path: TableIndex<StreamPath>) {
+        self.paths.push(path);
+        let links = self.paths[path].links.indices().clone();
+        for link in links.iter() {
             self.push_link(link);
         }
     }
@@ -204,12 +481,13 @@ impl StreamWriter {
     ///
     /// ### Panicking
     /// This function panics if the link is already present
-    fn push_link(&mut self, link: &TableCell<StreamLink>) {
-        self.links.push(link.clone());
+    fn push_link(&mut self, link: TableIndex<StreamLink>) {
+        self.links.push(link);
         // Since links can refer to metadata from previous links,
         // we don't push the metadata if it is already present.
-        if !self.metadatas.has_cell(link.get().raw_metadata()) {
-            self.push_metadata(link.get().raw_metadata());
+        let metadata = self.links[link].metadata();
+        if !self.metadatas.has_cell(metadata) {
+            self.push_metadata(metadata);
         }
     }
 
@@ -220,8 +498,8 @@ impl StreamWriter {
     ///
     /// ### Panicking
     /// This function panics if the metadata is already present
-    fn push_metadata(&mut self, metadata: &TableCell<StreamMetadata>) {
-        self.metadatas.push(metadata.clone());
+    fn push_metadata(&mut self, metadata: TableIndex<StreamMetadata>) {
+        self.metadatas.push(metadata);
     }
 
     /// Constructs and reorganizes the tables from the provided engine,
@@ -240,24 +518,69 @@ impl StreamWriter {
         let StreamEngine {
             path_lookup,
             folders,
-            ..
+            paths,
+            links,
+            metadatas,
         } = engine;
 
+        let folder_indices: Vec<_> = folders.indices().collect();
+
         let mut this = Self {
             path_lookup,
-            folders: TableMaker::new(),
-            paths: TableMaker::new(),
-            links: TableMaker::new(),
-            metadatas: TableMaker::new(),
+            folders: TableMaker::new(folders),
+            paths: TableMaker::new(paths),
+            links: TableMaker::new(links),
+            metadatas: TableMaker::new(metadatas),
         };
 
-        for folder in folders {
-            this.push_folder(&folder);
+        for folder in folder_indices {
+            this.push_folder(folder);
         }
 
         this
     }
 
+    /// Consumes the writer and constructs an engine from the reorganized tables
+    ///
+    /// ### Returns
+    /// The reorganized engine, with all references pointing into the new tables
+    ///
+    /// ### Notes
+    /// Any entries which are not owned by a folder are dropped, along with their lookups
+    pub(crate) fn into_engine(self) -> StreamEngine {
+        let (mut folders, _) = self.folders.into_parts();
+        let (mut paths, path_remap) = self.paths.into_parts();
+        let (mut links, link_remap) = self.links.into_parts();
+        let (metadatas, metadata_remap) = self.metadatas.into_parts();
+
+        for folder in folders.iter_mut() {
+            folder.remap(&path_remap);
+        }
+
+        for path in paths.iter_mut() {
+            path.remap(&link_remap);
+        }
+
+        for link in links.iter_mut() {
+            link.remap(&metadata_remap);
+        }
+
+        let path_lookup = self
+            .path_lookup
+            .into_iter()
+            .map(|(hash, path)| (hash, path_remap.get(path)))
+            .filter(|(_, path)| path.is_valid())
+            .collect();
+
+        StreamEngine {
+            path_lookup,
+            folders,
+            paths,
+            links,
+            metadatas,
+        }
+    }
+
     /// Writes the tables to the specified directory, consuming the writer
     ///
     /// ### Arguments
@@ -302,8 +625,8 @@ impl StreamWriter {
         }
 
         let mut lookup = std::io::Cursor::new(vec![]);
-        for (hash, path) in this.path_lookup.iter() {
-            HashKey::new(*hash, this.paths.get_index(path) as usize).write_to(&mut lookup)?;
+        for (hash, path) in this.path_lookup.iter_sorted() {
+            HashKey::new(*hash, this.paths.get_index(*path) as usize).write_to(&mut lookup)?;
         }
 
         std::fs::write(path.join("stream_folders.bin"), folders.into_inner())?;
@@ -322,8 +645,8 @@ impl StreamWriter {
             folder.write_with_args(writer, Rc::clone(&this))?;
         }
 
-        for (hash, cell) in this.path_lookup.iter() {
-            HashKey::new(*hash, this.paths.get_index(cell) as usize).write_to(writer)?;
+        for (hash, cell) in this.path_lookup.iter_sorted() {
+            HashKey::new(*hash, this.paths.get_index(*cell) as usize).write_to(writer)?;
         }
 
         for path in this.paths.iter() {
@@ -341,3 +664,51 @@ impl StreamWriter {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engines::{Locale, Region};
+
+    const FILE: &str = "stream:/sound/bgm/bgm_crs01.nus3audio";
+
+    #[test]
+    fn fallbacks_are_set_resolved_and_cleared() {
+        let mut engine = StreamEngine::new();
+        engine.add_file(FILE, VariantKind::Regional);
+
+        let europe = engine.resolve_variant(FILE, Region::Europe).unwrap();
+        assert!(!europe.is_shared());
+
+        let metadata = engine
+            .set_fallback(FILE, Region::Europe, Region::NorthAmerica)
+            .unwrap();
+
+        let resolution = engine.resolve_variant(FILE, Locale::EuFrench).unwrap();
+        assert_eq!(resolution.metadata, metadata);
+        assert_eq!(resolution.shared_with, vec![Region::NorthAmerica.into()]);
+
+        let resolution = engine.resolve_variant(FILE, Region::NorthAmerica).unwrap();
+        assert_eq!(resolution.shared_with, vec![Region::Europe.into()]);
+
+        assert!(matches!(
+            engine.set_fallback(FILE, Region::Europe, Locale::EuEnglish),
+            Err(StreamVariantError::SelfFallback { .. })
+        ));
+
+        let cleared = engine.clear_fallback(FILE, Region::Europe).unwrap();
+        assert_ne!(cleared, metadata);
+        assert!(!engine
+            .resolve_variant(FILE, Region::Europe)
+            .unwrap()
+            .is_shared());
+        assert!(!engine
+            .resolve_variant(FILE, Region::NorthAmerica)
+            .unwrap()
+            .is_shared());
+        assert_eq!(
+            engine.clear_fallback(FILE, Region::Europe).unwrap(),
+            cleared
+        );
+    }
+}
@@ -1,12 +1,9 @@
 use binrw::{binread, binrw, BinWrite};
 use hash40::Hash40;
 
-use crate::engines::{table::*, HashKey};
+use crate::engines::{table::*, HashKey, Locale, Region, VariantKind};
 
-use std::{
-    cell::{Ref, RefMut},
-    rc::Rc,
-};
+use std::rc::Rc;
 
 use super::StreamWriter;
 
@@ -18,6 +15,8 @@ use super::StreamWriter;
 /// In the archive currently, there are only three of these.
 #[binread]
 #[br(little)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StreamFolder {
     #[br(temp)]
     name_and_count: HashKey,
@@ -42,6 +41,8 @@ pub struct StreamFolder {
 /// the number of [links](StreamLink) that they refer to.
 #[binread]
 #[br(little)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StreamPath {
     #[br(temp)]
     path_and_link: HashKey,
@@ -83,6 +84,25 @@ pub struct StreamPath {
     pub is_regional: bool,
 }
 
+/// A locale or region to pick the data of a [stream path](StreamPath) with
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StreamVariant {
+    Locale(Locale),
+    Region(Region),
+}
+
+impl From<Locale> for StreamVariant {
+    fn from(value: Locale) -> Self {
+        Self::Locale(value)
+    }
+}
+
+impl From<Region> for StreamVariant {
+    fn from(value: Region) -> Self {
+        Self::Region(value)
+    }
+}
+
 /// The link between the [stream path](StreamPath) and the
 /// [stream metadata](StreamMetadata).
 ///
@@ -98,6 +118,8 @@ pub struct StreamPath {
 /// all of the locales/regions and use this to declare fallback
 /// locales/regions.
 #[binread]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StreamLink {
     #[br(map = |index: u32| TableReference::Unresolved(index as usize))]
     metadata: TableReference<StreamMetadata>,
@@ -109,6 +131,8 @@ pub struct StreamLink {
 /// are not allowed to be compressed, so they are instead
 /// stored simply as archive offsets and data sizes.
 #[binrw]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StreamMetadata {
     /// The size of the file data, in bytes.
     #[br(map = |size: u64| size as usize)]
@@ -127,14 +151,15 @@ impl StreamFolder {
     /// Resolves this folder
     ///
     /// ### Arguments
-    /// * `paths` - The array of [`StreamPath`] cells in which this
-    /// folder can safely index
+    /// * `paths` - The table of [`StreamPath`] in which this
+    ///   folder can safely index
     ///
-    /// ### Panicking
-    /// This function panics if the range of paths references by
-    /// this folder is out-of-bounds of the provided slice
-    pub fn resolve(&mut self, paths: &[TableCell<StreamPath>]) {
-        self.paths.resolve(paths);
+    /// ### Returns
+    /// * `Ok(())` - The folder was resolved
+    /// * `Err(_)` - The range of paths referenced by this folder
+    ///   is out-of-bounds of the provided slice
+    pub fn resolve(&mut self, paths: &[StreamPath]) -> Result<(), ResolveError> {
+        self.paths.resolve(paths)
     }
 
     /// Checks if this folder is resolved
@@ -144,6 +169,10 @@ impl StreamFolder {
     pub fn is_resolved(&self) -> bool {
         self.paths.is_resolved()
     }
+
+    pub(crate) fn remap(&mut self, paths: &TableRemap<StreamPath>) {
+        self.paths.remap(paths);
+    }
 }
 
 impl StreamPath {
@@ -152,14 +181,15 @@ impl StreamPath {
     /// Resolves this path
     ///
     /// ### Arguments
-    /// * `links` - The array of [`StreamLink`] cells in which this
-    /// path can safely index.
+    /// * `links` - The table of [`StreamLink`] in which this
+    ///   path can safely index.
     ///
-    /// ### Panicking
-    /// This function panics if the range of links referenced by
-    /// this path is out-of-bounds of the provided slice.
-    pub fn resolve(&mut self, links: &[TableCell<StreamLink>]) {
-        self.links.resolve(links);
+    /// ### Returns
+    /// * `Ok(())` - The path was resolved
+    /// * `Err(_)` - The range of links referenced by this path
+    ///   is out-of-bounds of the provided slice
+    pub fn resolve(&mut self, links: &[StreamLink]) -> Result<(), ResolveError> {
+        self.links.resolve(links)
     }
 
     /// Checks if this path is resolved
@@ -169,22 +199,87 @@ impl StreamPath {
     pub fn is_resolved(&self) -> bool {
         self.links.is_resolved()
     }
+
+    pub(crate) fn remap(&mut self, links: &TableRemap<StreamLink>) {
+        self.links.remap(links);
+    }
+
+    /// Whether this path has a link for every locale, for every region, or just one
+    pub fn variants(&self) -> VariantKind {
+        if self.is_localized {
+            VariantKind::Localized
+        } else if self.is_regional {
+            VariantKind::Regional
+        } else {
+            VariantKind::None
+        }
+    }
+
+    /// Finds which of this path's links the game picks for a locale or region
+    ///
+    /// ### Arguments
+    /// * `variant` - The locale or region to pick the link for
+    ///
+    /// ### Returns
+    /// * `Some(_)` - The position of the link in [`links`](Self::links)
+    /// * `None` - The variant is invalid, or it is a region and this path is localized
+    ///
+    /// ### Notes
+    /// Paths without variants always use their only link, and regional paths
+    /// use the region of a locale.
+    pub fn variant_position(&self, variant: StreamVariant) -> Option<usize> {
+        let position = match (self.variants(), variant) {
+            (VariantKind::None, _) => return Some(0),
+            (VariantKind::Localized, StreamVariant::Locale(locale)) => locale as i32,
+            (VariantKind::Localized, StreamVariant::Region(_)) => return None,
+            (VariantKind::Regional, StreamVariant::Locale(locale)) => Region::from(locale) as i32,
+            (VariantKind::Regional, StreamVariant::Region(region)) => region as i32,
+        };
+
+        usize::try_from(position)
+            .ok()
+            .filter(|position| *position < self.links.len())
+    }
+
+    /// The locale or region of one of this path's links
+    ///
+    /// ### Returns
+    /// * `Some(_)` - The locale or region that the link is picked for
+    /// * `None` - This path has no variants
+    pub fn variant_at(&self, position: usize) -> Option<StreamVariant> {
+        match self.variants() {
+            VariantKind::None => None,
+            VariantKind::Localized => Some(Locale::from(position as i32).into()),
+            VariantKind::Regional => Some(Region::from(position as i32).into()),
+        }
+    }
 }
 
 impl StreamLink {
     pub(crate) const REPR_SIZE: usize = 0x4;
 
+    /// Creates a link to a stream metadata
+    ///
+    /// ### Arguments
+    /// * `metadata` - The metadata which this link redirects to
+    pub fn new(metadata: TableIndex<StreamMetadata>) -> Self {
+        Self {
+            metadata: TableReference::Resolved(metadata),
+        }
+    }
+
     /// Resolves this stream link
     ///
     /// ### Arguments
     /// * `metadatas` - The array of [`StreamMetadata`] in which this
-    /// link can safely index
+    ///   link can safely index
     ///
-    /// ### Panicking
-    /// This function panics if the unresolved metadata reference is
-    /// out-of-bounds of the provided slice,
-    pub fn resolve(&mut self, metadatas: &[TableCell<StreamMetadata>]) {
-        self.metadata.resolve(metadatas);
+    /// ### Returns
+    /// * `Ok(())` - The link was resolved
+    /// * `Err(_)` - The unresolved metadata reference is
+    ///   out-of-bounds of the provided slice
+    pub fn resolve(&mut self, metadatas: &[StreamMetadata]) -> Result<(), ResolveError> {
+        self.metadata.resolve(metadatas)
     }
 
     /// Checks if this link is resolved
@@ -194,6 +289,10 @@ impl StreamLink {
     pub fn is_resolved(&self) -> bool {
         self.metadata.is_resolved()
     }
+
+    pub(crate) fn remap(&mut self, metadatas: &TableRemap<StreamMetadata>) {
+        self.metadata.remap(metadatas);
+    }
 }
 
 impl StreamMetadata {
@@ -214,7 +313,7 @@ impl BinWrite for StreamFolder {
         let start = if self.paths.is_empty() {
             0
         } else {
-            args.paths.get_index(&self.paths.cells()[0])
+            args.paths.get_index(self.paths.get(0))
         };
 
         let count = self.paths.len();
@@ -234,7 +333,7 @@ impl BinWrite for StreamPath {
         options: &binrw::WriteOptions,
         args: Self::Args,
     ) -> binrw::BinResult<()> {
-        let link = args.links.get_index(&self.links.cells()[0]) as usize;
+        let link = args.links.get_index(self.links.get(0)) as usize;
 
         let flags = if self.is_localized {
             1u32
@@ -259,7 +358,7 @@ impl BinWrite for StreamLink {
         args: Self::Args,
     ) -> binrw::BinResult<()> {
         args.metadatas
-            .get_index(self.raw_metadata())
+            .get_index(self.metadata())
             .write_options(writer, options, ())
     }
 }
@@ -1,12 +1,15 @@
 use std::{
-    cell::{Ref, RefCell, RefMut},
-    collections::HashMap,
-    ops::{Deref, DerefMut, Range},
-    rc::Rc,
-    sync::atomic::{AtomicU64, Ordering},
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    ops::{Deref, DerefMut, Index, IndexMut, Range},
 };
 
-use crate::INVALID_INDEX;
+use hash40::Hash40;
+use thiserror::Error;
+
+use crate::{INVALID_INDEX, INVALID_INDEX32};
 
 macro_rules! when_resolved {
     ($self:ident, $resolved:ident, $($t:tt)*) => {{
@@ -29,10 +32,12 @@ macro_rules! multi_reference {
         }
     ) => {
         $(#[$outer])*
+        #[derive(Clone)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         $vis enum $Reference {
             $(
                 $(#[$inner $($args)*])*
-                $Variant(TableCell<$T>),
+                $Variant(TableIndex<$T>),
             )*
             Unresolved(usize),
         }
@@ -68,16 +73,16 @@ macro_rules! multi_reference {
 
                 $(
                     #[doc =
-                        "Gets the underlying `" $Variant "` cell from this reference.\n"
+                        "Gets the underlying `" $Variant "` index from this reference.\n"
                         "### Returns\n"
-                        "The underlying reference to a `" $Variant "`.\n"
+                        "The index of the referenced `" $Variant "`.\n"
                         "### Panicking\n"
                         "* The reference is unresolved\n"
                         "* This is not a reference to a `" $Variant "`"
                     ]
-                    pub fn [<$Variant:snake>](&self) -> &TableCell<$T> {
+                    pub fn [<$Variant:snake>](&self) -> TableIndex<$T> {
                         match self {
-                            Self::$Variant(cell) => cell,
+                            Self::$Variant(index) => *index,
                             Self::Unresolved(_) => panic!("Table reference is unresolved"),
                             _ => panic!("Table reference is not a {}", stringify!($Variant))
                         }
@@ -97,11 +102,13 @@ macro_rules! multi_reference {
         }
     ) => {
         $(#[$outer])*
+        #[derive(Clone)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         $vis enum $Reference {
             None,
             $(
                 $(#[$inner $($args)*])*
-                $Variant(TableCell<$T>),
+                $Variant(TableIndex<$T>),
             )*
             Unresolved(usize),
         }
@@ -141,16 +148,16 @@ macro_rules! multi_reference {
 
                 $(
                     #[doc =
-                        "Gets the underlying `" $Variant "` cell from this reference.\n"
+                        "Gets the underlying `" $Variant "` index from this reference.\n"
                         "### Returns\n"
-                        "The underlying reference to a `" $Variant "`.\n"
+                        "The index of the referenced `" $Variant "`.\n"
                         "### Panicking\n"
                         "* The reference is unresolved\n"
                         "* This is not a reference to a `" $Variant "`"
                     ]
-                    pub fn [<$Variant:snake>](&self) -> &TableCell<$T> {
+                    pub fn [<$Variant:snake>](&self) -> TableIndex<$T> {
                         match self {
-                            Self::$Variant(cell) => cell,
+                            Self::$Variant(index) => *index,
                             Self::Unresolved(_) => panic!("Table reference is unresolved"),
                             _ => panic!("Table reference is not a {}", stringify!($Variant))
                         }
@@ -169,6 +176,8 @@ macro_rules! multi_reference {
         }
     ) => {
         $(#[$outer])*
+        #[derive(Clone)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         $vis enum $Reference {
             $(
                 $(#[$inner $($args)*])*
@@ -227,7 +236,6 @@ macro_rules! multi_reference {
                         "Gets the mutable reference to the underlying `" $Variant "` set from this reference\n"
                         "### Panicking\n"
                         "* The reference is unresolved\n"
-                        "* There is already another mutable reference active\n"
                         "* This is not a reference to a `" $Variant "`"
                     ]
                     pub fn [<$Variant:snake _mut>](&mut self) -> &mut TableContiguousReference<$T> {
@@ -252,11 +260,13 @@ macro_rules! multi_reference {
         }
     ) => {
         $(#[$outer])*
+        #[derive(Clone)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         $vis enum $Reference {
             None,
             $(
                 $(#[$inner $($args)*])*
-                $Variant(TableContiguousReference<$T, Range<usize>>),
+                $Variant(TableContiguousReference<$T>),
             )*
             Unresolved(Range<usize>),
         }
@@ -296,7 +306,7 @@ macro_rules! multi_reference {
 
                 $(
                     #[doc =
-                        "Gets the underlying `" $Variant "` cell from this reference.\n"
+                        "Gets the underlying `" $Variant "` set from this reference.\n"
                         "### Returns\n"
                         "The underlying reference to a `" $Variant "`.\n"
                         "### Panicking\n"
@@ -317,7 +327,6 @@ macro_rules! multi_reference {
 
 }
 
-use binrw::{BinRead, BinWrite};
 pub(crate) use multi_reference;
 
 macro_rules! expose_reference {
@@ -325,48 +334,24 @@ macro_rules! expose_reference {
         paste::paste! {
             impl $Structure {
                 #[doc =
-                    "Gets an immutable reference to the " $RefField " for this `" $Structure "`\n"
-                    "### Returns\n"
-                    "The immutable reference to the " $RefField " for this `" $Structure "`\n"
-                    "### Panicking\n"
-                    "This function panics if the [table reference](TableReference) to the " $RefField
-                    " is not [resolved](TableReference::resolve)."
-                ]
-                pub fn $RefField(&self) -> Ref<'_, $RefType> {
-                    self.$RefField.cell().get()
-                }
-
-                #[doc =
-                    "Gets the mutable reference to the " $RefField " for this `" $Structure "`\n"
+                    "Gets the index of the " $RefField " for this `" $Structure "`\n"
                     "### Returns\n"
-                    "The mutable reference to the " $RefField " for this `" $Structure "`\n"
+                    "The index of the " $RefField " for this `" $Structure "` in its table\n"
                     "### Panicking\n"
                     "This function panics if the [table reference](TableReference) to the " $RefField
                     " is not [resolved](TableReference::resolve)."
                 ]
-                pub fn [<$RefField _mut>](&self) -> RefMut<'_, $RefType> {
-                    self.$RefField.cell().get_mut()
+                pub fn $RefField(&self) -> TableIndex<$RefType> {
+                    self.$RefField.index()
                 }
 
                 #[doc =
-                    "Sets the reference for this `" $Structure "`'s " $RefField " to the specified cell\n"
+                    "Sets the reference for this `" $Structure "`'s " $RefField " to the specified index\n"
                     "### Arguments\n"
-                    "* `cell` - The cell to set the reference to\n"
+                    "* `index` - The index to set the reference to\n"
                 ]
-                pub fn [<set_ $RefField>](&mut self, cell: TableCell<$RefType>) {
-                    self.$RefField = TableReference::Resolved(cell);
-                }
-
-                #[doc =
-                    "Gets an immutable reference to the underlying cell for this `" $Structure "`'s " $RefField "\n"
-                    "### Returns\n"
-                    "An immutable reference to the underlying cell"
-                    "### Panicking\n"
-                    "This function panics if the [table reference](TableReference) to the " $RefField
-                    " is not [resolved](TableReference::resolve)."
-                ]
-                pub fn [<raw_ $RefField>](&self) -> &TableCell<$RefType> {
-                    self.$RefField.cell()
+                pub fn [<set_ $RefField>](&mut self, index: TableIndex<$RefType>) {
+                    self.$RefField = TableReference::Resolved(index);
                 }
             }
         }
@@ -375,48 +360,24 @@ macro_rules! expose_reference {
         paste::paste! {
             impl $Structure {
                 #[doc =
-                    "Gets an immutable reference to the " $RefVariant " for this `" $Structure "`\n"
+                    "Gets the index of the " $RefVariant " for this `" $Structure "`\n"
                     "### Returns\n"
-                    "The immutable reference to the " $RefVariant " for this `" $Structure "`\n"
+                    "The index of the " $RefVariant " for this `" $Structure "` in its table\n"
                     "### Panicking\n"
                     "This function panics if the [table reference](TableReference) to the " $RefVariant
                     " is not [resolved](TableReference::resolve)."
                 ]
-                pub fn [<$RefVariant:snake>](&self) -> Ref<'_, $RefType> {
-                    self.$RefField.[<$RefVariant:snake>]().get()
-                }
-
-                #[doc =
-                    "Gets the mutable reference to the " $RefVariant " for this `" $Structure "`\n"
-                    "### Returns\n"
-                    "The mutable reference to the " $RefVariant " for this `" $Structure "`\n"
-                    "### Panicking\n"
-                    "This function panics if the [table reference](TableReference) to the " $RefVariant
-                    " is not [resolved](TableReference::resolve)."
-                ]
-                pub fn [<$RefVariant:snake _mut>](&self) -> RefMut<'_, $RefType> {
-                    self.$RefField.[<$RefVariant:snake>]().get_mut()
+                pub fn [<$RefVariant:snake>](&self) -> TableIndex<$RefType> {
+                    self.$RefField.[<$RefVariant:snake>]()
                 }
 
                 #[doc =
-                    "Sets the reference for this `" $Structure "`'s " $RefVariant " to the specified cell\n"
+                    "Sets the reference for this `" $Structure "`'s " $RefVariant " to the specified index\n"
                     "### Arguments\n"
-                    "* `cell` - The cell to set the reference to\n"
+                    "* `index` - The index to set the reference to\n"
                 ]
-                pub fn [<set_ $RefVariant:snake>](&mut self, cell: TableCell<$RefType>) {
-                    self.$RefField = $RefName::$RefVariant(cell);
-                }
-
-                #[doc =
-                    "Gets an immutable reference to the underlying cell for this `" $Structure "`'s " $RefVariant "\n"
-                    "### Returns\n"
-                    "An immutable reference to the underlying cell"
-                    "### Panicking\n"
-                    "This function panics if the [table reference](TableReference) to the " $RefVariant
-                    " is not [resolved](TableReference::resolve)."
-                ]
-                pub fn [<raw_ $RefVariant:snake>](&self) -> &TableCell<$RefType> {
-                    self.$RefField.[<$RefVariant:snake>]()
+                pub fn [<set_ $RefVariant:snake>](&mut self, index: TableIndex<$RefType>) {
+                    self.$RefField = $RefName::$RefVariant(index);
                 }
 
                 #[doc =
@@ -432,48 +393,24 @@ macro_rules! expose_reference {
         paste::paste! {
             impl $Structure {
                 #[doc =
-                    "Gets an immutable reference to the " $RefVariant " for this `" $Structure "`\n"
+                    "Gets the index of the " $RefVariant " for this `" $Structure "`\n"
                     "### Returns\n"
-                    "The immutable reference to the " $RefVariant " for this `" $Structure "`\n"
+                    "The index of the " $RefVariant " for this `" $Structure "` in its table\n"
                     "### Panicking\n"
                     "This function panics if the [table reference](TableReference) to the " $RefVariant
                     " is not [resolved](TableReference::resolve)."
                 ]
-                pub fn [<$RefVariant:snake>](&self) -> Ref<'_, $RefType> {
-                    self.$RefField.[<$RefVariant:snake>]().get()
-                }
-
-                #[doc =
-                    "Gets the mutable reference to the " $RefVariant " for this `" $Structure "`\n"
-                    "### Returns\n"
-                    "The mutable reference to the " $RefVariant " for this `" $Structure "`\n"
-                    "### Panicking\n"
-                    "This function panics if the [table reference](TableReference) to the " $RefVariant
-                    " is not [resolved](TableReference::resolve)."
-                ]
-                pub fn [<$RefVariant:snake _mut>](&self) -> RefMut<'_, $RefType> {
-                    self.$RefField.[<$RefVariant:snake>]().get_mut()
+                pub fn [<$RefVariant:snake>](&self) -> TableIndex<$RefType> {
+                    self.$RefField.[<$RefVariant:snake>]()
                 }
 
                 #[doc =
-                    "Sets the reference for this `" $Structure "`'s " $RefVariant " to the specified cell\n"
+                    "Sets the reference for this `" $Structure "`'s " $RefVariant " to the specified index\n"
                     "### Arguments\n"
-                    "* `cell` - The cell to set the reference to\n"
+                    "* `index` - The index to set the reference to\n"
                 ]
-                pub fn [<set_ $RefVariant:snake>](&mut self, cell: TableCell<$RefType>) {
-                    self.$RefField = $RefName::$RefVariant(cell);
-                }
-
-                #[doc =
-                    "Gets an immutable reference to the underlying cell for this `" $Structure "`'s " $RefVariant "\n"
-                    "### Returns\n"
-                    "An immutable reference to the underlying cell"
-                    "### Panicking\n"
-                    "This function panics if the [table reference](TableReference) to the " $RefVariant
-                    " is not [resolved](TableReference::resolve)."
-                ]
-                pub fn [<raw_ $RefVariant:snake>](&self) -> &TableCell<$RefType> {
-                    self.$RefField.[<$RefVariant:snake>]()
+                pub fn [<set_ $RefVariant:snake>](&mut self, index: TableIndex<$RefType>) {
+                    self.$RefField = $RefName::$RefVariant(index);
                 }
 
                 #[doc =
@@ -491,132 +428,268 @@ macro_rules! expose_reference {
 
 pub(crate) use expose_reference;
 
-/// A reference counting, globally unique cell of data in the archive
+/// A typed index into one of the archive's [tables](Table)
 ///
 /// The archive format has many tables which cross-reference to each other, most
-/// notably in the packaged filesystem. Using reference-counted cells with interior
-/// mutability makes accessing and modifying the data significantly easier,
-/// with the slight overhead of runtime borrow-checking.
+/// notably in the packaged filesystem. Every entry lives in a single, contiguous
+/// [`Table`] owned by its engine, and references between entries are stored as
+/// these small, copyable handles instead of individually allocated cells.
 ///
-/// Each cell comes with a global identifier, which is unique from every other cell
-/// active in any engine. Since these cells are only active for the lifetime of the
-/// archive, there is no need to use actual UUIDs and instead a static, atomic
-/// counter is used to speed up creation of cells.
-pub struct TableCell<T: Sized> {
-    cell: Rc<RefCell<T>>,
-    guid: u64,
+/// The type parameter only exists to prevent an index into one table from being
+/// used to access another one.
+#[repr(transparent)]
+pub struct TableIndex<T> {
+    index: u32,
+    _marker: PhantomData<fn() -> T>,
 }
 
-impl<T: Sized> TableCell<T> {
-    /// Constructs a new table cell from the provided data
+impl<T> TableIndex<T> {
+    /// Constructs a new index from a raw table position
     ///
     /// ### Arguments
-    /// * `data` - The data to fill the cell with
+    /// * `index` - The position of the entry in its table
     ///
     /// ### Returns
-    /// The constructed cell
-    pub fn new(data: T) -> Self {
-        // Static atomic for a counter without unsafe
-        static GUID: AtomicU64 = AtomicU64::new(0);
-
+    /// The typed index
+    ///
+    /// ### Panicking
+    /// Panics if `index` does not fit in 32 bits
+    pub const fn new(index: usize) -> Self {
+        assert!(
+            index <= u32::MAX as usize,
+            "Table index does not fit in 32 bits"
+        );
         Self {
-            cell: Rc::new(RefCell::new(data)),
-            guid: GUID.fetch_add(1, Ordering::SeqCst),
+            index: index as u32,
+            _marker: PhantomData,
         }
     }
 
-    /// Retrieves the GUID of this cell
-    ///
-    /// ### Returns
-    /// The GUID of the cell
-    pub fn guid(&self) -> u64 {
-        self.guid
+    /// Returns the [invalid index](INVALID_INDEX) for this table
+    pub const fn invalid() -> Self {
+        Self::new(INVALID_INDEX)
     }
 
-    /// Gets the number of users which are referencing this cell
+    /// Gets the raw position of the entry in its table
     ///
     /// ### Returns
-    /// The number of users referencing this cell
-    ///
-    /// ### Note
-    /// This only takes into account the [strong count](Rc::strong_count) of
-    /// the internal cell
-    pub fn rc(&self) -> usize {
-        Rc::strong_count(&self.cell)
+    /// The raw position of the entry
+    pub const fn index(self) -> usize {
+        self.index as usize
     }
 
-    /// Gets an immutable reference to the underlying data of the cell
+    /// Checks if this index is not the [invalid index](INVALID_INDEX)
     ///
     /// ### Returns
-    /// An immutable reference to the underlying data of the cell
-    ///
-    /// ### Panicking
-    /// This method panics if there is currently a mutable reference of the data
-    pub fn get(&self) -> Ref<'_, T> {
-        self.cell.borrow()
+    /// Whether or not this index can point to an entry
+    pub const fn is_valid(self) -> bool {
+        self.index != INVALID_INDEX32
     }
+}
 
-    /// Gets a mutable reference to the underlying data of the cell
+impl<T> Clone for TableIndex<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for TableIndex<T> {}
+
+impl<T> PartialEq for TableIndex<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for TableIndex<T> {}
+
+impl<T> PartialOrd for TableIndex<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for TableIndex<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.index.cmp(&other.index)
+    }
+}
+
+impl<T> Hash for TableIndex<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state)
+    }
+}
+
+impl<T> fmt::Debug for TableIndex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TableIndex({:#x})", self.index)
+    }
+}
+
+/// A contiguous table of archive structures
+///
+/// Each engine owns one of these per table in the archive, and every structure
+/// is referred to by its [`TableIndex`]. Indexing a table with a [`TableIndex`]
+/// is as cheap as indexing the underlying [`Vec`].
+///
+/// It implements [`Deref`] and [`DerefMut`] on a slice of the entries.
+#[derive(Clone)]
+pub struct Table<T>(Vec<T>);
+
+impl<T> Table<T> {
+    /// Constructs a new, empty table
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Appends an entry to the end of the table
     ///
-    /// ### Returns
-    /// A mutable reference to the underlying data of the cell
+    /// ### Arguments
+    /// * `entry` - The entry to add
     ///
-    /// ### Panicking
-    /// This method panics if there is currently a mutable reference of the data
-    pub fn get_mut(&self) -> RefMut<'_, T> {
-        self.cell.borrow_mut()
+    /// ### Returns
+    /// The index of the new entry
+    pub fn push(&mut self, entry: T) -> TableIndex<T> {
+        let index = TableIndex::new(self.0.len());
+        self.0.push(entry);
+        index
+    }
+
+    /// Gets an entry by index, if it is in the table
+    pub fn get(&self, index: TableIndex<T>) -> Option<&T> {
+        self.0.get(index.index())
+    }
+
+    /// Gets a mutable entry by index, if it is in the table
+    pub fn get_mut(&mut self, index: TableIndex<T>) -> Option<&mut T> {
+        self.0.get_mut(index.index())
+    }
+
+    /// Creates an iterator over the index of every entry in the table
+    pub fn indices(&self) -> impl Iterator<Item = TableIndex<T>> {
+        (0..self.0.len()).map(TableIndex::new)
+    }
+
+    /// Creates an iterator over every entry in the table, along with its index
+    pub fn iter_indexed(&self) -> impl Iterator<Item = (TableIndex<T>, &T)> {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| (TableIndex::new(index), entry))
+    }
+
+    /// Gets the index that the next pushed entry will have
+    pub fn next_index(&self) -> TableIndex<T> {
+        TableIndex::new(self.0.len())
+    }
+
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
     }
 }
 
-impl<T: Sized> Clone for TableCell<T> {
-    fn clone(&self) -> Self {
-        Self {
-            cell: Rc::clone(&self.cell),
-            guid: self.guid,
-        }
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<Vec<T>> for Table<T> {
+    fn from(entries: Vec<T>) -> Self {
+        Self(entries)
+    }
+}
+
+impl<T> FromIterator<T> for Table<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<T> Deref for Table<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Table<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
     }
 }
 
-impl<T: Sized + BinRead> BinRead for TableCell<T> {
-    type Args = T::Args;
+impl<T> Index<TableIndex<T>> for Table<T> {
+    type Output = T;
 
-    fn read_options<R: std::io::Read + std::io::Seek>(
-        reader: &mut R,
-        options: &binrw::ReadOptions,
-        args: Self::Args,
-    ) -> binrw::BinResult<Self> {
-        T::read_options(reader, options, args).map(TableCell::new)
+    fn index(&self, index: TableIndex<T>) -> &Self::Output {
+        &self.0[index.index()]
     }
 }
 
-impl<T: Sized + BinWrite> BinWrite for TableCell<T> {
-    type Args = T::Args;
-    fn write_options<W: std::io::Write + std::io::Seek>(
-        &self,
-        writer: &mut W,
-        options: &binrw::WriteOptions,
-        args: Self::Args,
-    ) -> binrw::BinResult<()> {
-        self.get().write_options(writer, options, args)
+impl<T> IndexMut<TableIndex<T>> for Table<T> {
+    fn index_mut(&mut self, index: TableIndex<T>) -> &mut Self::Output {
+        &mut self.0[index.index()]
     }
 }
 
+/// An error which prevented a reference from being resolved
+#[derive(Debug, Error)]
+pub enum ResolveError {
+    #[error("Table reference out of bounds: the len is {len} but the index is {index}")]
+    OutOfBounds { index: usize, len: usize },
+
+    #[error("Table reference out of bounds: the len is {len} but the range is {range:?}")]
+    RangeOutOfBounds { range: Range<usize>, len: usize },
+
+    #[error("The linked list starting at index {0} loops back on itself")]
+    LinkedListCycle(usize),
+
+    #[error("The search path {0:?} is a folder, but there is no search folder for it")]
+    MissingFolder(Hash40),
+}
+
+/// Checks that a reference into a table is in bounds before resolving it
+///
+/// The references are still plain indices after resolving, so this is what
+/// keeps them from pointing past the end of their table.
+pub(crate) fn check_bounds(index: usize, len: usize) -> Result<(), ResolveError> {
+    if index >= len {
+        return Err(ResolveError::OutOfBounds { index, len });
+    }
+
+    Ok(())
+}
+
+/// Adds an offset to an unresolved index, checking that the result is in bounds
+///
+/// ### Returns
+/// * `Ok(_)` - The index with the offset added
+/// * `Err(_)` - The index is out of bounds, or adding the offset overflows
+pub(crate) fn offset_index(index: usize, offset: usize, len: usize) -> Result<usize, ResolveError> {
+    let index = index.saturating_add(offset);
+    check_bounds(index, len)?;
+    Ok(index)
+}
+
 /// A potentially unresolved reference to an archive structure
 ///
 /// Table references are used when parsing archive structures
 /// as not everything has been loaded yet. This enables those elements
 /// to be loaded into whole tables and, upon resolving, will make
 /// accessing the structures related to one another much easier.
-pub enum TableReference<T: Sized> {
+pub enum TableReference<T> {
     /// A resolved reference, an element of one of the archive's tables.
-    Resolved(TableCell<T>),
+    Resolved(TableIndex<T>),
 
     /// An unresolved reference, which is used to index into one of the
     /// archive's tables.
     Unresolved(usize),
 }
 
-impl<T: Sized> TableReference<T> {
+impl<T> TableReference<T> {
     pub fn invalid() -> Self {
         Self::Unresolved(INVALID_INDEX)
     }
@@ -624,51 +697,41 @@ impl<T: Sized> TableReference<T> {
     /// Resolves the reference if it is currently unresolved
     ///
     /// ### Arguments
-    /// * `table` - An array of [`TableCell`] from which this reference,
-    /// if unresolved, can safely index into.
+    /// * `table` - The table from which this reference, if unresolved,
+    ///   can safely index into.
     ///
-    /// ### Panicking
-    /// This method panics if the unresolved index is out-of-bounds of
-    /// the provided slice.
-    pub fn resolve(&mut self, table: &[TableCell<T>]) {
-        // Create a temporary value which we can swap with `self`
-        // so that we can match and assign contents which cannot copy
-        // (and so that we don't clone when unnecessary)
-        let mut tmp = Self::Unresolved(INVALID_INDEX);
-        std::mem::swap(self, &mut tmp);
-        *self = match tmp {
-            Self::Unresolved(index) => Self::Resolved(table[index].clone()),
-            other => other,
-        }
+    /// ### Returns
+    /// * `Ok(())` - The reference is resolved
+    /// * `Err(_)` - The unresolved index is out-of-bounds of the provided slice
+    pub fn resolve(&mut self, table: &[T]) -> Result<(), ResolveError> {
+        self.resolve_with_offset(table, 0)
     }
 
     /// Resolves the reference if it is currently unresolved, adding
     /// the specified offset to the unresolved index
     ///
     /// ### Arguments
-    /// * `table` - An Array of [`TableCell`] from which this reference,
-    /// if unresolved, can safely index into.
+    /// * `table` - The table from which this reference, if unresolved,
+    ///   can safely index into.
     /// * `offset` - The offset to add to the unresolved index
     ///
-    /// ### Panicking
-    /// This method panics if the sum of the unresolved index and the offset
-    /// is out-of-bounds of the provided slice.
+    /// ### Returns
+    /// * `Ok(())` - The reference is resolved
+    /// * `Err(_)` - The sum of the unresolved index and the offset is
+    ///   out-of-bounds of the provided slice
     ///
     /// ### Usages
     /// Sometimes information about where the reference is pointing to is
     /// not available until after the whole table has been loaded into memory.
     /// Use this method over [`resolve`](TableReference::resolve) when that
     /// is the case.
-    pub fn resolve_with_offset(&mut self, table: &[TableCell<T>], offset: usize) {
-        // Create a temporary value which we can swap with `self`
-        // so that we can match and assign contents which cannot copy
-        // (and so that we don't clone when unnecessary)
-        let mut tmp = Self::Unresolved(INVALID_INDEX);
-        std::mem::swap(self, &mut tmp);
-        *self = match tmp {
-            Self::Unresolved(index) => Self::Resolved(table[index + offset].clone()),
-            other => other,
+    pub fn resolve_with_offset(&mut self, table: &[T], offset: usize) -> Result<(), ResolveError> {
+        if let Self::Unresolved(index) = *self {
+            let index = offset_index(index, offset, table.len())?;
+            *self = Self::Resolved(TableIndex::new(index));
         }
+
+        Ok(())
     }
 
     /// Checks if the reference is already resolved
@@ -679,16 +742,186 @@ impl<T: Sized> TableReference<T> {
         matches!(self, Self::Resolved(_))
     }
 
-    /// Gets the resolved cell from this reference, panicking if the reference
+    /// Gets the resolved index from this reference, panicking if the reference
     /// is not resolved
     ///
     /// ### Returns
-    /// An immutable reference to the resolved [`TableCell`].
+    /// The resolved [`TableIndex`].
     ///
     /// ### Panicking
     /// This function panics if the reference is not yet resolved.
-    pub fn cell(&self) -> &TableCell<T> {
-        when_resolved!(self, cell, cell)
+    pub fn index(&self) -> TableIndex<T> {
+        when_resolved!(self, index, *index)
+    }
+
+    /// Gets the resolved index from this reference, if it is resolved
+    ///
+    /// ### Returns
+    /// * `Some(_)` - The resolved [`TableIndex`]
+    /// * `None` - The reference is not resolved
+    pub fn resolved(&self) -> Option<TableIndex<T>> {
+        match self {
+            Self::Resolved(index) => Some(*index),
+            Self::Unresolved(_) => None,
+        }
+    }
+
+    /// Maps the resolved index of this reference through a [`TableRemap`]
+    ///
+    /// ### Arguments
+    /// * `remap` - The old index -> new index mapping for the table
+    pub fn remap(&mut self, remap: &TableRemap<T>) {
+        if let Self::Resolved(index) = self {
+            *index = remap.get(*index);
+        }
+    }
+}
+
+impl<T> Clone for TableReference<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Resolved(index) => Self::Resolved(*index),
+            Self::Unresolved(index) => Self::Unresolved(*index),
+        }
+    }
+}
+
+/// The resolved indices of a [`TableReferenceSet`]
+///
+/// Most of the sets in the archive point to a contiguous range of a table and are
+/// never modified, so they are stored as that range. Once an index is inserted which
+/// would break the range, the set falls back to storing every index.
+pub struct TableIndices<T> {
+    repr: TableIndicesRepr,
+    _marker: PhantomData<fn() -> T>,
+}
+
+#[derive(Clone)]
+enum TableIndicesRepr {
+    Contiguous(Range<u32>),
+    Scattered(Vec<u32>),
+}
+
+impl<T> TableIndices<T> {
+    /// Constructs a set of indices from a contiguous range of a table
+    pub fn contiguous(range: Range<usize>) -> Self {
+        let (start, end) = (
+            TableIndex::<T>::new(range.start),
+            TableIndex::<T>::new(range.end),
+        );
+        Self {
+            repr: TableIndicesRepr::Contiguous(start.index..end.index),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Gets the number of indices in this set
+    pub fn len(&self) -> usize {
+        match &self.repr {
+            TableIndicesRepr::Contiguous(range) => range.len(),
+            TableIndicesRepr::Scattered(indices) => indices.len(),
+        }
+    }
+
+    /// Checks if there are no indices in this set
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the index at the specified position in this set
+    ///
+    /// ### Panicking
+    /// Panics if `position` >= `len()`
+    pub fn get(&self, position: usize) -> TableIndex<T> {
+        match &self.repr {
+            TableIndicesRepr::Contiguous(range) => {
+                if let Err(error) = check_bounds(position, range.len()) {
+                    panic!("{}", error);
+                }
+                TableIndex::new(range.start as usize + position)
+            }
+            TableIndicesRepr::Scattered(indices) => TableIndex::new(indices[position] as usize),
+        }
+    }
+
+    /// Creates an iterator over every index in this set, in order
+    pub fn iter(&self) -> impl Iterator<Item = TableIndex<T>> + '_ {
+        let (range, scattered) = match &self.repr {
+            TableIndicesRepr::Contiguous(range) => (range.clone(), &[][..]),
+            TableIndicesRepr::Scattered(indices) => (0..0, indices.as_slice()),
+        };
+
+        range
+            .chain(scattered.iter().copied())
+            .map(|index| TableIndex::new(index as usize))
+    }
+
+    /// Checks if the set contains the specified index
+    pub fn contains(&self, index: TableIndex<T>) -> bool {
+        match &self.repr {
+            TableIndicesRepr::Contiguous(range) => range.contains(&index.index),
+            TableIndicesRepr::Scattered(indices) => indices.contains(&index.index),
+        }
+    }
+
+    /// Gets the contiguous range of this set, if it is still contiguous
+    pub fn as_range(&self) -> Option<Range<usize>> {
+        match &self.repr {
+            TableIndicesRepr::Contiguous(range) => Some(range.start as usize..range.end as usize),
+            TableIndicesRepr::Scattered(_) => None,
+        }
+    }
+
+    fn scattered(&mut self) -> &mut Vec<u32> {
+        if let TableIndicesRepr::Contiguous(range) = &self.repr {
+            self.repr = TableIndicesRepr::Scattered(range.clone().collect());
+        }
+
+        match &mut self.repr {
+            TableIndicesRepr::Scattered(indices) => indices,
+            TableIndicesRepr::Contiguous(_) => unreachable!(),
+        }
+    }
+
+    pub fn push(&mut self, index: TableIndex<T>) {
+        match &mut self.repr {
+            TableIndicesRepr::Contiguous(range) if range.start == range.end => {
+                *range = index.index..index.index + 1;
+            }
+            TableIndicesRepr::Contiguous(range) if range.end == index.index => range.end += 1,
+            _ => self.scattered().push(index.index),
+        }
+    }
+
+    pub fn insert(&mut self, position: usize, index: TableIndex<T>) {
+        if position == self.len() {
+            self.push(index);
+        } else {
+            self.scattered().insert(position, index.index);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.repr = TableIndicesRepr::Contiguous(0..0);
+    }
+}
+
+impl<T> Clone for TableIndices<T> {
+    fn clone(&self) -> Self {
+        Self {
+            repr: self.repr.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> FromIterator<TableIndex<T>> for TableIndices<T> {
+    fn from_iter<I: IntoIterator<Item = TableIndex<T>>>(iter: I) -> Self {
+        let mut indices = Self::contiguous(0..0);
+        for index in iter {
+            indices.push(index);
+        }
+        indices
     }
 }
 
@@ -700,12 +933,12 @@ impl<T: Sized> TableReference<T> {
 ///
 /// This is a generic implementation to be expanded upon
 /// by any set-like container/reference in the tables.
-pub enum TableReferenceSet<T: Sized, U: Sized> {
-    Resolved(Vec<TableCell<T>>),
+pub enum TableReferenceSet<T, U> {
+    Resolved(TableIndices<T>),
     Unresolved(U),
 }
 
-impl<T: Sized, U: Sized> TableReferenceSet<T, U> {
+impl<T, U> TableReferenceSet<T, U> {
     /// Checks if the reference is already resolved
     ///
     /// ### Returns
@@ -714,10 +947,10 @@ impl<T: Sized, U: Sized> TableReferenceSet<T, U> {
         matches!(self, Self::Resolved(_))
     }
 
-    /// Gets the number of cells in this reference set
+    /// Gets the number of entries in this reference set
     ///
     /// ### Returns
-    /// The number of cells in this reference set
+    /// The number of entries in this reference set
     ///
     /// ### Panicking
     /// This function will panic if the reference set is not yet resolved
@@ -725,10 +958,10 @@ impl<T: Sized, U: Sized> TableReferenceSet<T, U> {
         when_resolved!(self, set, set.len())
     }
 
-    /// Checks if the number of cells in this reference set is `0`
+    /// Checks if the number of entries in this reference set is `0`
     ///
     /// ### Returns
-    /// Whether or not there are no cells in this reference set
+    /// Whether or not there are no entries in this reference set
     ///
     /// ### Panicking
     /// This function will panic if the reference set is not yet resolved
@@ -736,29 +969,29 @@ impl<T: Sized, U: Sized> TableReferenceSet<T, U> {
         when_resolved!(self, set, set.is_empty())
     }
 
-    /// Adds a new cell to this reference set
+    /// Adds a new entry to this reference set
     ///
     /// ### Arguments
-    /// * `cell` - The cell to add to the set
+    /// * `index` - The index of the entry to add to the set
     ///
     /// ### Panicking
     /// This function will panic if the reference set is not yet resolved
-    pub fn push(&mut self, cell: TableCell<T>) {
-        when_resolved!(self, set, set.push(cell))
+    pub fn push(&mut self, index: TableIndex<T>) {
+        when_resolved!(self, set, set.push(index))
     }
 
-    /// Inserts a new cell to this reference set at an
-    /// arbitrary index
+    /// Inserts a new entry to this reference set at an
+    /// arbitrary position
     ///
     /// ### Arguments
-    /// * `index` - The index where `cell` should be inserted
-    /// * `cell` - The cell to insert into the set
+    /// * `position` - The position where `index` should be inserted
+    /// * `index` - The index of the entry to insert into the set
     ///
     /// ### Panicking
-    /// * Panics if `index` > `len()`
+    /// * Panics if `position` > `len()`
     /// * Panics if the reference set is not yet resolved
-    pub fn insert(&mut self, index: usize, cell: TableCell<T>) {
-        when_resolved!(self, set, set.insert(index, cell))
+    pub fn insert(&mut self, position: usize, index: TableIndex<T>) {
+        when_resolved!(self, set, set.insert(position, index))
     }
 
     /// Removes all references in this set
@@ -769,57 +1002,82 @@ impl<T: Sized, U: Sized> TableReferenceSet<T, U> {
         when_resolved!(self, set, set.clear())
     }
 
-    /// Creates an iterator of immutable references to every cell
-    /// in this reference set
+    /// Creates an iterator over the index of every entry in this reference set
     ///
     /// ### Returns
-    /// An iterator over each cell, returning an immutable reference
-    /// to the data contained within
+    /// An iterator over each index, in order
     ///
     /// ### Panicking
     /// This function will panic if the reference set is not yet resolved
-    pub fn iter(&self) -> impl Iterator<Item = Ref<'_, T>> {
-        when_resolved!(self, set, set.iter().map(TableCell::get))
+    pub fn iter(&self) -> impl Iterator<Item = TableIndex<T>> + '_ {
+        when_resolved!(self, set, set.iter())
     }
 
-    /// Creates an iterator of mutable references to every cell
-    /// in this reference set
+    /// Creates an iterator over every entry in this reference set
+    ///
+    /// ### Arguments
+    /// * `table` - The table which this reference set indexes into
     ///
     /// ### Returns
-    /// An iterator over each cell, returning a mutable reference
-    /// to the data contained within
+    /// An iterator over each entry, returning an immutable reference
+    /// to the data in the table
     ///
     /// ### Panicking
     /// This function will panic if the reference set is not yet resolved
-    pub fn iter_mut(&self) -> impl Iterator<Item = RefMut<'_, T>> {
-        when_resolved!(self, set, set.iter().map(TableCell::get_mut))
+    pub fn iter_in<'a>(&'a self, table: &'a [T]) -> impl Iterator<Item = &'a T> + 'a {
+        self.iter().map(move |index| &table[index.index()])
     }
 
-    /// Gets the underlying cells of this reference set
+    /// Gets the resolved indices of this reference set
     ///
     /// ### Returns
-    /// The slice of cells for this reference set
+    /// The indices of this reference set
     ///
     /// ### Panicking
     /// This function will panic if the reference set is not yet resolved
-    pub fn cells(&self) -> &[TableCell<T>] {
-        when_resolved!(self, set, set.as_slice())
+    pub fn indices(&self) -> &TableIndices<T> {
+        when_resolved!(self, set, set)
     }
 
-    pub fn cells_mut(&mut self) -> &mut [TableCell<T>] {
-        when_resolved!(self, set, set.as_mut_slice())
+    pub fn replace(&mut self, set: impl IntoIterator<Item = TableIndex<T>>) {
+        *self = TableReferenceSet::Resolved(set.into_iter().collect());
     }
 
-    pub fn replace(&mut self, set: Vec<TableCell<T>>) {
-        *self = TableReferenceSet::Resolved(set);
+    pub fn get(&self, position: usize) -> TableIndex<T> {
+        when_resolved!(self, set, set.get(position))
+    }
+
+    /// Creates an iterator over the index of every entry in this reference set,
+    /// which is empty if the set is not resolved
+    ///
+    /// ### Returns
+    /// An iterator over each index, in order
+    pub fn resolved(&self) -> impl Iterator<Item = TableIndex<T>> + '_ {
+        match self {
+            Self::Resolved(set) => Some(set.iter()),
+            Self::Unresolved(_) => None,
+        }
+        .into_iter()
+        .flatten()
     }
 
-    pub fn get(&self, index: usize) -> Ref<'_, T> {
-        when_resolved!(self, set, set[index].get())
+    /// Maps every resolved index of this set through a [`TableRemap`]
+    ///
+    /// ### Arguments
+    /// * `remap` - The old index -> new index mapping for the table
+    pub fn remap(&mut self, remap: &TableRemap<T>) {
+        if let Self::Resolved(set) = self {
+            *set = set.iter().map(|index| remap.get(index)).collect();
+        }
     }
+}
 
-    pub fn get_mut(&self, index: usize) -> RefMut<'_, T> {
-        when_resolved!(self, set, set[index].get_mut())
+impl<T, U: Clone> Clone for TableReferenceSet<T, U> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Resolved(set) => Self::Resolved(set.clone()),
+            Self::Unresolved(unresolved) => Self::Unresolved(unresolved.clone()),
+        }
     }
 }
 
@@ -830,11 +1088,11 @@ impl<T: Sized, U: Sized> TableReferenceSet<T, U> {
 /// collections.
 ///
 /// It implements [`Deref`] and [`DerefMut`] on [`TableReferenceSet`] for
-/// simple access to all of the cells.
+/// simple access to all of the indices.
 #[repr(transparent)]
-pub struct TableContiguousReference<T: Sized>(pub(crate) TableReferenceSet<T, Range<usize>>);
+pub struct TableContiguousReference<T>(pub(crate) TableReferenceSet<T, Range<usize>>);
 
-impl<T: Sized> TableContiguousReference<T> {
+impl<T> TableContiguousReference<T> {
     pub fn invalid() -> Self {
         Self(TableReferenceSet::Unresolved(INVALID_INDEX..INVALID_INDEX))
     }
@@ -854,61 +1112,56 @@ impl<T: Sized> TableContiguousReference<T> {
     /// Resolves the reference if it is currently unresolved
     ///
     /// ### Arguments
-    /// * `table` - An array of [`TableCell`] from which this reference,
-    /// if unresolved, can safely index into.
+    /// * `table` - The table from which this reference, if unresolved,
+    ///   can safely index into.
     ///
-    /// ### Panicking
-    /// This method panics if the unresolved range is out-of-bounds of
-    /// the provided slice.
-    pub fn resolve(&mut self, table: &[TableCell<T>]) {
-        // Create a temporary value which we can swap with `self`
-        // so that we can match and assign contents which cannot copy
-        // (and so that we don't clone when unnecessary)
-        let mut tmp = Self(TableReferenceSet::Unresolved(INVALID_INDEX..INVALID_INDEX));
-        std::mem::swap(self, &mut tmp);
-        *self = match tmp {
-            Self(TableReferenceSet::Unresolved(range)) => Self(TableReferenceSet::Resolved(
-                table[range].iter().map(TableCell::clone).collect(),
-            )),
-            other => other,
-        }
+    /// ### Returns
+    /// * `Ok(())` - The reference is resolved
+    /// * `Err(_)` - The unresolved range is out-of-bounds of the provided slice
+    pub fn resolve(&mut self, table: &[T]) -> Result<(), ResolveError> {
+        self.resolve_with_offset(table, 0)
     }
 
     /// Resolves the reference if it is currently unresolved
     ///
     /// ### Arguments
-    /// * `table` - An array of [`TableCell`] from which this reference,
-    /// if unresolved, can safely index into.
+    /// * `table` - The table from which this reference, if unresolved,
+    ///   can safely index into.
     /// * `offset` - The offset to add to the unresolved range
     ///
-    /// ### Panicking
-    /// This method panics if the sum of the unresolved range and the offset
-    /// is out-of-bounds of the provided slice.
+    /// ### Returns
+    /// * `Ok(())` - The reference is resolved
+    /// * `Err(_)` - The sum of the unresolved range and the offset is
+    ///   out-of-bounds of the provided slice
     ///
     /// ### Usages
     /// Sometimes information about where the reference is pointing to is
     /// not available until after the whole table has been loaded into memory.
     /// Use this method over [`resolve`](TableContiguousReference::resolve) when that
     /// is the case.
-    pub fn resolve_with_offset(&mut self, table: &[TableCell<T>], offset: usize) {
-        // Create a temporary value which we can swap with `self`
-        // so that we can match and assign contents which cannot copy
-        // (and so that we don't clone when unnecessary)
-        let mut tmp = Self(TableReferenceSet::Unresolved(INVALID_INDEX..INVALID_INDEX));
-        std::mem::swap(self, &mut tmp);
-        *self = match tmp {
-            Self(TableReferenceSet::Unresolved(range)) => Self(TableReferenceSet::Resolved(
-                table[(range.start + offset)..(range.end + offset)]
-                    .iter()
-                    .map(TableCell::clone)
-                    .collect(),
-            )),
-            other => other,
+    pub fn resolve_with_offset(&mut self, table: &[T], offset: usize) -> Result<(), ResolveError> {
+        if let TableReferenceSet::Unresolved(range) = &self.0 {
+            let range = range.start.saturating_add(offset)..range.end.saturating_add(offset);
+            if range.start > range.end || range.end > table.len() {
+                return Err(ResolveError::RangeOutOfBounds {
+                    range,
+                    len: table.len(),
+                });
+            }
+            self.0 = TableReferenceSet::Resolved(TableIndices::contiguous(range));
         }
+
+        Ok(())
+    }
+}
+
+impl<T> Clone for TableContiguousReference<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
     }
 }
 
-impl<T: Sized> Deref for TableContiguousReference<T> {
+impl<T> Deref for TableContiguousReference<T> {
     type Target = TableReferenceSet<T, Range<usize>>;
 
     fn deref(&self) -> &Self::Target {
@@ -916,13 +1169,13 @@ impl<T: Sized> Deref for TableContiguousReference<T> {
     }
 }
 
-impl<T: Sized> DerefMut for TableContiguousReference<T> {
+impl<T> DerefMut for TableContiguousReference<T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.0
     }
 }
 
-/// Trait for locating the next cell in a linked-list reference set
+/// Trait for locating the next entry in a linked-list reference set
 ///
 /// Some sets of data in the archive format are linked together via
 /// a linked-list like method, which requires one element to point
@@ -931,7 +1184,7 @@ impl<T: Sized> DerefMut for TableContiguousReference<T> {
 /// This trait is required to be implemented on any structure which
 /// is an element of one of these sets.
 pub trait LinkedReference: Sized {
-    fn next(&self) -> Option<TableCell<Self>>;
+    fn next(&self) -> Option<TableIndex<Self>>;
 }
 
 /// A reference to a set of archive structures which are joined via a linked-list
@@ -949,7 +1202,7 @@ pub trait LinkedReference: Sized {
 ///
 /// Note that any type which is referenced by this type must implement [`LinkedReference`]
 #[repr(transparent)]
-pub struct TableLinkedReference<T: LinkedReference>(TableReferenceSet<T, usize>);
+pub struct TableLinkedReference<T: LinkedReference>(pub(crate) TableReferenceSet<T, usize>);
 
 impl<T: LinkedReference> TableLinkedReference<T> {
     pub fn invalid() -> Self {
@@ -970,48 +1223,51 @@ impl<T: LinkedReference> TableLinkedReference<T> {
     /// Resolves the reference if it is currently unresolved
     ///
     /// ### Arguments
-    /// * `table` - An array of [`TableCell`] from which this reference,
-    /// if unresolved, can safely index into.
+    /// * `table` - The table from which this reference, if unresolved,
+    ///   can safely index into.
     ///
-    /// ### Panicking
-    /// This method panics if the unresolved index is out-of-bounds of
-    /// the provided slice.
+    /// ### Returns
+    /// * `Ok(())` - The reference is resolved
+    /// * `Err(_)` - One of the indices in the list is out-of-bounds of the
+    ///   provided slice, or the list loops back on itself
     ///
     /// ### Notes
-    /// * This function makes no checks on the data received from `table` to
-    /// see if the referenced cell(s) are already resolved.
+    /// * The entries in `table` must already have their next references
+    ///   resolved, as those are what is followed to collect the set.
     /// * There is no option for resolving with an index, as that doesn't make
-    /// sense for a linked-list based reference set.
-    pub fn resolve(&mut self, table: &[TableCell<T>]) {
-        // Create a temporary value which we can swap with `self`
-        // so that we can match and assign contents which cannot copy
-        // (and so that we don't clone when unnecessary)
-        let mut tmp = Self(TableReferenceSet::Unresolved(INVALID_INDEX));
-        std::mem::swap(self, &mut tmp);
-        *self = match tmp {
-            Self(TableReferenceSet::Unresolved(start_index)) => {
-                // Get the start of our references, this is the only one
-                // that we directly index
-                let mut current = table[start_index].clone();
-                let mut set = vec![current.clone()];
-
-                loop {
-                    // Continuously attempt to get the next element
-                    // in the set, breaking out if there is not one
-                    let next = if let Some(next) = current.get().next() {
-                        next
-                    } else {
-                        break;
-                    };
-
-                    // Push the element and then change our current element
-                    set.push(next.clone());
-                    current = next;
+    ///   sense for a linked-list based reference set.
+    pub fn resolve(&mut self, table: &[T]) -> Result<(), ResolveError> {
+        if let TableReferenceSet::Unresolved(start_index) = self.0 {
+            // Get the start of our references, this is the only one
+            // that we directly index
+            check_bounds(start_index, table.len())?;
+            let mut current = TableIndex::new(start_index);
+            let mut set = TableIndices::contiguous(start_index..start_index + 1);
+
+            // Continuously attempt to get the next element
+            // in the set, breaking out if there is not one
+            while let Some(next) = table[current.index()].next() {
+                check_bounds(next.index(), table.len())?;
+
+                // A list can't be longer than its table without visiting an entry twice
+                if set.len() >= table.len() {
+                    return Err(ResolveError::LinkedListCycle(start_index));
                 }
-                Self(TableReferenceSet::Resolved(set))
+
+                set.push(next);
+                current = next;
             }
-            other => other,
+
+            self.0 = TableReferenceSet::Resolved(set);
         }
+
+        Ok(())
+    }
+}
+
+impl<T: LinkedReference> Clone for TableLinkedReference<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
     }
 }
 
@@ -1029,94 +1285,290 @@ impl<T: LinkedReference> DerefMut for TableLinkedReference<T> {
     }
 }
 
+/// A mapping from the indices of an old table to the indices of its reorganized table
+///
+/// Produced by [`TableMaker::into_parts`], and used to fix up every reference
+/// into a table after it has been reorganized.
+pub struct TableRemap<T> {
+    new_indices: Vec<u32>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> TableRemap<T> {
+    /// Gets the new index of an entry
+    ///
+    /// ### Arguments
+    /// * `index` - The index of the entry in the old table
+    ///
+    /// ### Returns
+    /// The index of the entry in the new table, or the [invalid index](TableIndex::invalid)
+    /// if the entry was not kept in the new table
+    pub fn get(&self, index: TableIndex<T>) -> TableIndex<T> {
+        self.new_indices
+            .get(index.index())
+            .map_or(TableIndex::invalid(), |new| TableIndex::new(*new as usize))
+    }
+}
+
 /// Helper structure for reconstructing tables
 ///
 /// When reconstructing tables as intended for the archive,
 /// it is important to make sure the elements are properly
 /// set up in the proper reference chains. This structure
-/// provides an encapsulated way of building new tables using
-/// only the cells and also maintaining a lookup of their GUID
-/// to the table index for use in serializing out the structure
+/// takes ownership of an existing table and records the order
+/// in which its entries are pushed, maintaining a lookup of old
+/// index to new index for use in serializing out the structures
 /// to their raw form.
-pub struct TableMaker<T: Sized> {
-    table: Vec<TableCell<T>>,
-    guid_to_index: HashMap<u64, u32>,
+pub struct TableMaker<T> {
+    source: Table<T>,
+    order: Vec<TableIndex<T>>,
+    new_indices: Vec<u32>,
 }
 
-impl<T: Sized> TableMaker<T> {
-    /// Constructs a new [`TableMaker`] with empty containers
+impl<T> TableMaker<T> {
+    /// Constructs a new [`TableMaker`] which will reorganize the provided table
+    ///
+    /// ### Arguments
+    /// * `source` - The table to reorganize
     ///
     /// ### Returns
     /// The constructed [`TableMaker`]
-    pub fn new() -> Self {
+    pub fn new(source: Table<T>) -> Self {
+        let new_indices = vec![INVALID_INDEX32; source.len()];
         Self {
-            table: Vec::new(),
-            guid_to_index: HashMap::new(),
+            source,
+            order: Vec::new(),
+            new_indices,
         }
     }
 
-    /// Pushes a cell to the new table
+    /// Pushes an entry of the source table to the new table
     ///
     /// ### Arguments
-    /// * `cell` - The cell to add to the table
+    /// * `index` - The index of the entry in the source table
     ///
     /// ### Panicking
-    /// This function panics if the cell is already present in the table
-    pub fn push(&mut self, cell: TableCell<T>) {
-        let index = self.table.len() as u32;
-        if self.guid_to_index.insert(cell.guid(), index).is_some() {
-            panic!("Overlapping GUIDs in index lookup!");
+    /// This function panics if the entry is already present in the table
+    pub fn push(&mut self, index: TableIndex<T>) {
+        let new_index = TableIndex::<T>::new(self.order.len()).index;
+        let slot = &mut self.new_indices[index.index()];
+        if *slot != INVALID_INDEX32 {
+            panic!("Overlapping entries in index lookup!");
+        }
+        *slot = new_index;
+        self.order.push(index);
+    }
+
+    /// Pushes an entry of the source table to the new table, unless it is
+    /// already there
+    ///
+    /// ### Arguments
+    /// * `index` - The index of the entry in the source table
+    ///
+    /// ### Returns
+    /// Whether or not the entry was pushed. Indices which are out of bounds of
+    /// the source table, such as the [invalid index](TableIndex::invalid), are
+    /// never pushed.
+    pub fn push_missing(&mut self, index: TableIndex<T>) -> bool {
+        if index.index() >= self.source.len() || self.has_cell(index) {
+            return false;
         }
-        self.table.push(cell);
+
+        self.push(index);
+        true
     }
 
-    /// Checks if the table contains the cell already
+    /// Checks if the table contains the entry already
     ///
     /// ### Arguments
-    /// * `cell` - A reference to the cell to check for
+    /// * `index` - The index of the entry in the source table
     ///
     /// ### Returns
-    /// Whether or not the cell is already in the table
-    pub fn has_cell(&self, cell: &TableCell<T>) -> bool {
-        self.guid_to_index.contains_key(&cell.guid())
+    /// Whether or not the entry is already in the table
+    pub fn has_cell(&self, index: TableIndex<T>) -> bool {
+        matches!(self.new_indices.get(index.index()), Some(new) if *new != INVALID_INDEX32)
     }
 
-    /// Gets the index of the cell in the table
+    /// Gets the index of the entry in the new table
     ///
     /// ### Arguments
-    /// * `cell` - A reference to the cell to get the index for
+    /// * `index` - The index of the entry in the source table
     ///
     /// ### Returns
-    /// The index of the cell in the table
+    /// The index of the entry in the new table
     ///
     /// ### Panicking
-    /// This function panics if the cell is not in the table
-    pub fn get_index(&self, cell: &TableCell<T>) -> u32 {
-        self.guid_to_index
-            .get(&cell.guid())
+    /// This function panics if the entry is not in the table
+    pub fn get_index(&self, index: TableIndex<T>) -> u32 {
+        self.new_indices
+            .get(index.index())
             .copied()
-            .expect("Index lookup should have index for GUID")
+            .filter(|new| *new != INVALID_INDEX32)
+            .expect("Index lookup should have index for entry")
     }
 
-    /// Gets an iterator over each cell in the table
+    /// Gets an iterator over each entry in the new table, in order
     ///
     /// ### Returns
-    /// The iterator over the cells
-    pub fn iter(&self) -> impl Iterator<Item = &TableCell<T>> {
-        self.table.iter()
+    /// The iterator over the entries
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.order.iter().map(|index| &self.source[*index])
     }
 
-    pub fn into_inner(self) -> Vec<TableCell<T>> {
-        self.table
+    /// Gets the index in the source table of each entry in the new table, in order
+    pub fn pushed(&self) -> &[TableIndex<T>] {
+        &self.order
+    }
+
+    /// Gets the source table which this maker is reorganizing
+    pub fn source(&self) -> &Table<T> {
+        &self.source
+    }
+
+    /// Consumes the maker, returning the reorganized table and the mapping
+    /// from the indices of the source table
+    ///
+    /// ### Returns
+    /// The new table and the remap for any references to it
+    ///
+    /// ### Notes
+    /// Entries which were never pushed are dropped.
+    pub fn into_parts(self) -> (Table<T>, TableRemap<T>) {
+        let Self {
+            source,
+            order,
+            new_indices,
+        } = self;
+
+        let mut source: Vec<Option<T>> = source.into_inner().into_iter().map(Some).collect();
+        let table = order
+            .into_iter()
+            .map(|index| {
+                source[index.index()]
+                    .take()
+                    .expect("Entries should only be pushed once")
+            })
+            .collect();
+
+        (
+            table,
+            TableRemap {
+                new_indices,
+                _marker: PhantomData,
+            },
+        )
     }
 
     pub fn len(&self) -> usize {
-        self.table.len()
+        self.order.len()
     }
 }
 
-impl<T: Sized> Default for TableMaker<T> {
-    fn default() -> Self {
-        Self::new()
+impl<T> Index<TableIndex<T>> for TableMaker<T> {
+    type Output = T;
+
+    /// Indexes into the source table
+    fn index(&self, index: TableIndex<T>) -> &Self::Output {
+        &self.source[index]
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{ser::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::*;
+
+    impl<T> Serialize for TableIndex<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.index.serialize(serializer)
+        }
+    }
+
+    impl<'de, T> Deserialize<'de> for TableIndex<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            u32::deserialize(deserializer).map(|index| Self::new(index as usize))
+        }
+    }
+
+    impl<T: Serialize> Serialize for Table<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.0.serialize(serializer)
+        }
+    }
+
+    impl<'de, T: Deserialize<'de>> Deserialize<'de> for Table<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Vec::deserialize(deserializer).map(Self)
+        }
+    }
+
+    impl<T> Serialize for TableReference<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                Self::Resolved(index) => index.serialize(serializer),
+                Self::Unresolved(_) => Err(S::Error::custom("Table reference is unresolved")),
+            }
+        }
+    }
+
+    impl<'de, T> Deserialize<'de> for TableReference<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            TableIndex::deserialize(deserializer).map(Self::Resolved)
+        }
+    }
+
+    /// Indices are always written as a plain list, whether or not they are contiguous
+    impl<T> Serialize for TableIndices<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.iter())
+        }
+    }
+
+    /// Contiguous lists of indices are stored as a range again
+    impl<'de, T> Deserialize<'de> for TableIndices<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Vec::<TableIndex<T>>::deserialize(deserializer)
+                .map(|indices| indices.into_iter().collect())
+        }
+    }
+
+    impl<T, U> Serialize for TableReferenceSet<T, U> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                Self::Resolved(indices) => indices.serialize(serializer),
+                Self::Unresolved(_) => Err(S::Error::custom("Table reference is unresolved")),
+            }
+        }
+    }
+
+    impl<'de, T, U> Deserialize<'de> for TableReferenceSet<T, U> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            TableIndices::deserialize(deserializer).map(Self::Resolved)
+        }
+    }
+
+    impl<T> Serialize for TableContiguousReference<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.0.serialize(serializer)
+        }
+    }
+
+    impl<'de, T> Deserialize<'de> for TableContiguousReference<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            TableReferenceSet::deserialize(deserializer).map(Self)
+        }
+    }
+
+    impl<T: LinkedReference> Serialize for TableLinkedReference<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.0.serialize(serializer)
+        }
+    }
+
+    impl<'de, T: LinkedReference> Deserialize<'de> for TableLinkedReference<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            TableReferenceSet::deserialize(deserializer).map(Self)
+        }
     }
 }
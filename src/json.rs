@@ -0,0 +1,131 @@
+//! A small writer for the JSON reports that the crate produces
+
+use std::{
+    fmt::{Display, Write as _},
+    io::{self, Write},
+};
+
+/// Escapes a string to be placed inside of quotes in DOT or JSON
+///
+/// Quotes, backslashes and control characters are escaped, using `\u00XX` for
+/// the control characters which have no shorter escape.
+pub(crate) fn escape(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Builds an indented JSON document, taking care of the separators between values
+///
+/// Values are written in order, and every object and array has to be ended in the
+/// reverse order that they were begun in. Inside of objects, every value has to be
+/// preceded by a [`key`](Self::key).
+pub(crate) struct JsonWriter {
+    json: String,
+
+    /// For each object or array that is still open, whether it has no values yet
+    empty: Vec<bool>,
+
+    /// Whether a key was just written, so the value belongs on the same line
+    after_key: bool,
+}
+
+impl JsonWriter {
+    pub fn new() -> Self {
+        Self {
+            json: String::new(),
+            empty: vec![],
+            after_key: false,
+        }
+    }
+
+    /// Writes the separator and indentation before a key, or before a value in an array
+    fn separate(&mut self) {
+        if std::mem::take(&mut self.after_key) {
+            return;
+        }
+
+        if let Some(empty) = self.empty.last_mut() {
+            if !*empty {
+                self.json.push(',');
+            }
+            *empty = false;
+
+            self.json.push('\n');
+            self.indent();
+        }
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.empty.len() {
+            self.json.push_str("  ");
+        }
+    }
+
+    fn begin(&mut self, open: char) {
+        self.separate();
+        self.json.push(open);
+        self.empty.push(true);
+    }
+
+    fn end(&mut self, close: char) {
+        if !self.empty.pop().expect("No object or array to end") {
+            self.json.push('\n');
+            self.indent();
+        }
+        self.json.push(close);
+    }
+
+    pub fn begin_object(&mut self) {
+        self.begin('{');
+    }
+
+    pub fn end_object(&mut self) {
+        self.end('}');
+    }
+
+    pub fn begin_array(&mut self) {
+        self.begin('[');
+    }
+
+    pub fn end_array(&mut self) {
+        self.end(']');
+    }
+
+    /// Writes the key of the next value in an object
+    pub fn key(&mut self, key: &str) {
+        self.separate();
+        let _ = write!(self.json, "\"{}\": ", escape(key));
+        self.after_key = true;
+    }
+
+    /// Writes a string value
+    pub fn string(&mut self, value: &str) {
+        self.separate();
+        let _ = write!(self.json, "\"{}\"", escape(value));
+    }
+
+    /// Writes a number or boolean value
+    pub fn value(&mut self, value: impl Display) {
+        self.separate();
+        let _ = write!(self.json, "{}", value);
+    }
+
+    /// Writes the finished document, followed by a newline
+    pub fn finish<W: Write>(mut self, writer: &mut W) -> io::Result<()> {
+        self.json.push('\n');
+        writer.write_all(self.json.as_bytes())
+    }
+}
@@ -1,19 +1,141 @@
 #![feature(const_trait_impl)]
-use std::io::Read;
+use std::io::{self, Read};
 
 use hash40::Hash40;
 
 pub mod archive;
+pub mod cache;
+pub mod diff;
 pub mod engines;
+mod json;
+pub mod loose;
+pub mod overlay;
+pub mod region;
+pub mod stats;
+pub mod version;
 
 #[cfg(feature = "raw")]
 pub mod raw;
 
+#[cfg(feature = "serde")]
+pub mod serialization;
+
 pub trait Decompressor: Sync + Send {
     fn decompress(&self, data: &[u8]) -> std::io::Result<Vec<u8>>;
     fn decompress_with_size(&self, data: &[u8], size: usize) -> std::io::Result<Vec<u8>>;
+
+    /// Decompresses data into a buffer owned by the caller
+    ///
+    /// ### Returns
+    /// * `Ok(usize)` - The number of bytes written to the start of `output`
+    /// * `Err(_)` - The data could not be decompressed, or it does not fit in `output`
+    ///
+    /// ### Notes
+    /// The default implementation decompresses into a new `Vec` and copies it over, so
+    /// implementors should override this when they can write to `output` directly.
+    fn decompress_into(&self, data: &[u8], output: &mut [u8]) -> std::io::Result<usize> {
+        copy_into(&self.decompress_with_size(data, output.len())?, output)
+    }
+
+    /// Wraps a reader of compressed data in a reader of the decompressed data
+    ///
+    /// ### Notes
+    /// The default implementation reads and decompresses all of the data up front, so
+    /// implementors should override this when they can decompress as they go.
+    fn reader<'a>(&'a self, mut reader: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Ok(Box::new(io::Cursor::new(self.decompress(&data)?)))
+    }
+
+    /// Decompresses data which was compressed with a dictionary into a buffer owned by the caller
+    ///
+    /// ### Returns
+    /// See [`decompress_into`](Self::decompress_into)
+    ///
+    /// ### Notes
+    /// Dictionaries are optional, and the default implementation fails with
+    /// [`io::ErrorKind::Unsupported`].
+    fn decompress_into_with_dictionary(
+        &self,
+        _data: &[u8],
+        _dictionary: &[u8],
+        _output: &mut [u8],
+    ) -> io::Result<usize> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Wraps a reader of data which was compressed with a dictionary in a reader of the decompressed data
+    ///
+    /// ### Notes
+    /// Dictionaries are optional, and the default implementation fails with
+    /// [`io::ErrorKind::Unsupported`].
+    fn reader_with_dictionary<'a>(
+        &'a self,
+        _reader: Box<dyn Read + 'a>,
+        _dictionary: &'a [u8],
+    ) -> io::Result<Box<dyn Read + 'a>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+/// Copies decompressed data into a buffer owned by the caller
+fn copy_into(data: &[u8], output: &mut [u8]) -> io::Result<usize> {
+    output
+        .get_mut(..data.len())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The output buffer is too small",
+            )
+        })?
+        .copy_from_slice(data);
+    Ok(data.len())
+}
+
+/// Reads from a decompressing reader until it ends, failing if it doesn't fit in `output`
+fn read_into(mut reader: impl Read, output: &mut [u8]) -> io::Result<usize> {
+    let mut written = 0;
+    while written < output.len() {
+        match reader.read(&mut output[written..])? {
+            0 => return Ok(written),
+            count => written += count,
+        }
+    }
+
+    match reader.read(&mut [0u8])? {
+        0 => Ok(written),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The output buffer is too small",
+        )),
+    }
+}
+
+fn ruzstd_error(error: ruzstd::frame_decoder::FrameDecoderError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Creates a frame decoder which can use a dictionary
+///
+/// `ruzstd` panics on dictionaries which are too short to have a header,
+/// so those are rejected before they are decoded.
+fn ruzstd_decoder_with_dictionary(dictionary: &[u8]) -> io::Result<ruzstd::FrameDecoder> {
+    const DICTIONARY_MAGIC: [u8; 4] = [0x37, 0xA4, 0x30, 0xEC];
+
+    if dictionary.len() < 8 || dictionary[..4] != DICTIONARY_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The dictionary is not a zstd dictionary",
+        ));
+    }
+
+    let mut decoder = ruzstd::FrameDecoder::new();
+    decoder.add_dict(dictionary).map_err(ruzstd_error)?;
+    Ok(decoder)
 }
 
+/// The pure Rust decompressor, which is used unless another one is set
 pub struct DefaultDecompressor;
 
 impl Decompressor for DefaultDecompressor {
@@ -28,11 +150,163 @@ impl Decompressor for DefaultDecompressor {
         let mut data = Vec::with_capacity(size);
         decoder.read_to_end(&mut data).map(|_| data)
     }
+
+    fn decompress_into(&self, data: &[u8], output: &mut [u8]) -> io::Result<usize> {
+        let decoder = ruzstd::StreamingDecoder::new(data).map_err(ruzstd_error)?;
+        read_into(decoder, output)
+    }
+
+    fn reader<'a>(&'a self, reader: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        let decoder = ruzstd::StreamingDecoder::new(reader).map_err(ruzstd_error)?;
+        Ok(Box::new(decoder))
+    }
+
+    fn decompress_into_with_dictionary(
+        &self,
+        data: &[u8],
+        dictionary: &[u8],
+        output: &mut [u8],
+    ) -> io::Result<usize> {
+        let frame_decoder = ruzstd_decoder_with_dictionary(dictionary)?;
+        let decoder = ruzstd::StreamingDecoder::new_with_decoder(data, frame_decoder)
+            .map_err(ruzstd_error)?;
+        read_into(decoder, output)
+    }
+
+    fn reader_with_dictionary<'a>(
+        &'a self,
+        reader: Box<dyn Read + 'a>,
+        dictionary: &'a [u8],
+    ) -> io::Result<Box<dyn Read + 'a>> {
+        let frame_decoder = ruzstd_decoder_with_dictionary(dictionary)?;
+        let decoder = ruzstd::StreamingDecoder::new_with_decoder(reader, frame_decoder)
+            .map_err(ruzstd_error)?;
+        Ok(Box::new(decoder))
+    }
+}
+
+/// The decompressor backed by the reference zstd library
+#[cfg(feature = "compression")]
+pub struct ZstdDecompressor;
+
+#[cfg(feature = "compression")]
+thread_local! {
+    /// The zstd context of each thread, which [`ZstdDecompressor::decompress_into`] reuses
+    /// instead of creating one for every file
+    static ZSTD_CONTEXT: std::cell::RefCell<Option<zstd::bulk::Decompressor<'static>>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+#[cfg(feature = "compression")]
+impl Decompressor for ZstdDecompressor {
+    fn decompress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        zstd::stream::decode_all(data)
+    }
+
+    fn decompress_with_size(&self, data: &[u8], size: usize) -> io::Result<Vec<u8>> {
+        zstd::bulk::decompress(data, size)
+    }
+
+    fn decompress_into(&self, data: &[u8], output: &mut [u8]) -> io::Result<usize> {
+        ZSTD_CONTEXT.with(|context| {
+            let mut context = context.borrow_mut();
+            let context = match context.as_mut() {
+                Some(context) => context,
+                None => context.insert(zstd::bulk::Decompressor::new()?),
+            };
+            context.decompress_to_buffer(data, output)
+        })
+    }
+
+    fn reader<'a>(&'a self, reader: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(zstd::stream::read::Decoder::new(reader)?))
+    }
+
+    fn decompress_into_with_dictionary(
+        &self,
+        data: &[u8],
+        dictionary: &[u8],
+        output: &mut [u8],
+    ) -> io::Result<usize> {
+        zstd::bulk::Decompressor::with_dictionary(dictionary)?.decompress_to_buffer(data, output)
+    }
+
+    fn reader_with_dictionary<'a>(
+        &'a self,
+        reader: Box<dyn Read + 'a>,
+        dictionary: &'a [u8],
+    ) -> io::Result<Box<dyn Read + 'a>> {
+        let reader = io::BufReader::with_capacity(zstd::zstd_safe::DCtx::in_size(), reader);
+        Ok(Box::new(zstd::stream::read::Decoder::with_dictionary(
+            reader, dictionary,
+        )?))
+    }
 }
 
 static GLOBAL_DECOMPRESSOR: std::sync::RwLock<&'static dyn Decompressor> =
     std::sync::RwLock::new(&DefaultDecompressor);
 
+/// Forwards to whichever decompressor was set with [`set_decompressor`]
+///
+/// This is the decompressor of the default [`ArchiveOptions`](archive::ArchiveOptions),
+/// so archives which are opened without options keep following the global decompressor.
+pub(crate) struct GlobalDecompressor;
+
+impl GlobalDecompressor {
+    fn get() -> &'static dyn Decompressor {
+        *GLOBAL_DECOMPRESSOR.read().unwrap()
+    }
+}
+
+impl Decompressor for GlobalDecompressor {
+    fn decompress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        Self::get().decompress(data)
+    }
+
+    fn decompress_with_size(&self, data: &[u8], size: usize) -> std::io::Result<Vec<u8>> {
+        Self::get().decompress_with_size(data, size)
+    }
+
+    fn decompress_into(&self, data: &[u8], output: &mut [u8]) -> io::Result<usize> {
+        Self::get().decompress_into(data, output)
+    }
+
+    fn reader<'a>(&'a self, reader: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        Self::get().reader(reader)
+    }
+
+    fn decompress_into_with_dictionary(
+        &self,
+        data: &[u8],
+        dictionary: &[u8],
+        output: &mut [u8],
+    ) -> io::Result<usize> {
+        Self::get().decompress_into_with_dictionary(data, dictionary, output)
+    }
+
+    fn reader_with_dictionary<'a>(
+        &'a self,
+        reader: Box<dyn Read + 'a>,
+        dictionary: &'a [u8],
+    ) -> io::Result<Box<dyn Read + 'a>> {
+        Self::get().reader_with_dictionary(reader, dictionary)
+    }
+}
+
+pub trait Compressor: Sync + Send {
+    fn compress(&self, data: &[u8]) -> std::io::Result<Vec<u8>>;
+}
+
+#[cfg(feature = "compression")]
+pub struct DefaultCompressor;
+
+#[cfg(feature = "compression")]
+impl Compressor for DefaultCompressor {
+    fn compress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        zstd::encode_all(std::io::Cursor::new(data), 0)
+    }
+}
+
 /// The invalid index for any archive table
 ///
 /// Most indices found in the archive are 24-bit integers, of which the value
@@ -62,9 +336,19 @@ impl const Hashable for Hash40 {
 }
 
 pub fn load_labels(labels: impl AsRef<std::path::Path>) {
+    try_load_labels(labels).unwrap();
+}
+
+/// Adds every label in a label file to the global label map, without panicking if the file
+/// can't be read
+///
+/// ### Returns
+/// * `Ok(())` - The labels were added
+/// * `Err(_)` - The label file could not be read
+pub fn try_load_labels(labels: impl AsRef<std::path::Path>) -> std::io::Result<()> {
     let map = Hash40::label_map();
     let mut lock = map.lock().unwrap();
-    lock.add_labels_from_path(labels).unwrap();
+    lock.add_labels_from_path(labels)
 }
 
 pub fn decompress_data(data: impl AsRef<[u8]>) -> Vec<u8> {
@@ -83,12 +367,13 @@ pub fn decompress_data_with_size(data: impl AsRef<[u8]>, decompressed_size: usiz
         .unwrap()
 }
 
+/// Sets the decompressor used by every archive which was opened without its own
+/// [`ArchiveOptions`](archive::ArchiveOptions)
 pub fn set_decompressor(decompressor: &'static dyn Decompressor) {
     *GLOBAL_DECOMPRESSOR.write().unwrap() = decompressor;
 }
 
 #[cfg(feature = "compression")]
 pub fn compress_data(data: impl AsRef<[u8]>) -> Vec<u8> {
-    let data = data.as_ref();
-    zstd::encode_all(std::io::Cursor::new(data), 0).unwrap()
+    DefaultCompressor.compress(data.as_ref()).unwrap()
 }
@@ -0,0 +1,588 @@
+use std::{collections::HashMap, path::Path as FsPath, str::FromStr};
+
+use semver::Version;
+use thiserror::Error;
+
+use crate::{
+    archive::{Archive, ArchiveOptions},
+    engines::{
+        packaged::{
+            types::{Metadata, Package, Path},
+            PackagedEngine,
+        },
+        search::SearchEngine,
+        stream::{types::StreamMetadata, StreamEngine},
+        table::TableIndex,
+        VariantKind,
+    },
+    region::LocaleRegionTable,
+};
+
+/// The alignment of every piece of file data that is laid out by [`Archive::from_loose_tree`]
+const DATA_ALIGNMENT: usize = 0x10;
+
+/// The data for one locale or region of a file
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LooseVariant {
+    /// The name of the locale or region, such as `us_en` or `eu`
+    pub name: String,
+
+    /// The path of the variant's data, relative to the root of the loose tree
+    pub source: String,
+}
+
+/// A file owned by a [`LoosePackage`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LooseFile {
+    /// The full path of the file in the archive
+    pub path: String,
+
+    /// The path of the file's data, relative to the root of the loose tree.
+    ///
+    /// When this is `None`, the file's path in the archive is used.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub source: Option<String>,
+
+    /// The data for each locale or region that has its own version of the file.
+    ///
+    /// If this is not empty, the file gets a variant for every locale or region of its
+    /// package, and the ones that are not listed use the file's shared data.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub variants: Vec<LooseVariant>,
+}
+
+/// A package, along with the files that it owns and shares
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoosePackage {
+    /// The full path of the package
+    pub path: String,
+
+    /// The lifetime of the package
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub lifetime: String,
+
+    /// Whether the package has a group for every locale or region
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub variants: VariantKind,
+
+    /// The files which the package owns
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub files: Vec<LooseFile>,
+
+    /// The full paths of files owned by other packages which this package also loads
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub shared_files: Vec<String>,
+
+    /// The full paths of the packages which are loaded along with this one
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub child_packages: Vec<String>,
+}
+
+/// A file in the stream filesystem
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LooseStreamFile {
+    /// The full path of the file, including the `stream:/` prefix
+    pub path: String,
+
+    /// The path of the file's data, relative to the root of the loose tree.
+    ///
+    /// When this is `None`, the file's path is used with `stream:/` replaced by `stream/`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub source: Option<String>,
+
+    /// Whether the file has a variant for every locale or region
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub variant_kind: VariantKind,
+
+    /// The data for each locale or region that has its own version of the file.
+    ///
+    /// The ones that are not listed use the file's default data.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub variants: Vec<LooseVariant>,
+}
+
+/// Describes how the files of a loose tree are put together into an archive
+///
+/// Used by [`Archive::from_loose_tree`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LooseManifest {
+    /// The version of the archive
+    pub version: Version,
+
+    /// Every package in the packaged filesystem
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub packages: Vec<LoosePackage>,
+
+    /// Every file in the stream filesystem
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stream_files: Vec<LooseStreamFile>,
+}
+
+/// An error which prevented an archive from being built from a loose tree
+#[derive(Debug, Error)]
+pub enum LooseTreeError {
+    #[error("Failed to read {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Package {0} is listed more than once")]
+    DuplicatePackage(String),
+
+    #[error("File {0} is listed more than once")]
+    DuplicateFile(String),
+
+    #[error("Package {0} does not exist")]
+    MissingPackage(String),
+
+    #[error("Shared file {0} is not owned by any package")]
+    MissingFile(String),
+
+    #[error("Path {0} should have a parent, a file name and an extension")]
+    InvalidPath(String),
+
+    #[error("File {file} has variants, but its package is neither localized nor regional")]
+    UnexpectedVariants { file: String },
+
+    #[error("File {file} has a variant for {name}, which is not a known locale or region")]
+    UnknownVariant { file: String, name: String },
+}
+
+/// An archive built from a loose tree, along with all of its file data
+///
+/// Created by [`Archive::from_loose_tree`]
+pub struct LooseArchive {
+    /// The archive, with every engine resolved
+    pub archive: Archive,
+
+    /// The data of the packaged filesystem, which starts at the archive's
+    /// [file section offset](Archive::file_section_offset). The archive offset of
+    /// each group is relative to the start of this data.
+    pub file_data: Vec<u8>,
+
+    /// The data of the stream filesystem
+    pub stream_data: Vec<u8>,
+
+    /// The offset of [`stream_data`](Self::stream_data) in the archive, which is
+    /// included in the offset of every stream metadata
+    pub stream_offset: usize,
+}
+
+impl LooseArchive {
+    /// Places the file data at its final offsets in the archive
+    ///
+    /// ### Arguments
+    /// * `stream_offset` - The offset of [`stream_data`](Self::stream_data) in the archive
+    /// * `file_section_offset` - The offset of [`file_data`](Self::file_data) in the archive
+    ///
+    /// ### Panicking
+    /// Panics if the stream data and the file data would overlap
+    ///
+    /// ### Notes
+    /// * [`Archive::from_loose_tree`] places both at offset 0, so the offsets in the tables
+    ///   are relative to the start of their data until this is called
+    /// * The offset of every stream metadata is moved along with the stream data, and the
+    ///   archive's file section offset is set to `file_section_offset`
+    pub fn set_data_offsets(&mut self, stream_offset: usize, file_section_offset: usize) {
+        assert!(
+            stream_offset + self.stream_data.len() <= file_section_offset
+                || file_section_offset + self.file_data.len() <= stream_offset,
+            "Stream data overlaps the file data"
+        );
+
+        for metadata in self.archive.stream_fs.metadatas.iter_mut() {
+            metadata.offset = metadata.offset - self.stream_offset + stream_offset;
+        }
+
+        self.stream_offset = stream_offset;
+        self.archive.file_section_offset = file_section_offset;
+    }
+}
+
+/// Finds the index of a variant by the name of its locale or region
+fn variant_index(variants: VariantKind, file: &str, name: &str) -> Result<usize, LooseTreeError> {
+    (0..variants.variant_count())
+        .find(|index| variants.variant_name(*index) == name)
+        .ok_or_else(|| LooseTreeError::UnknownVariant {
+            file: file.to_string(),
+            name: name.to_string(),
+        })
+}
+
+/// Reads a file from the loose tree
+fn read_source(root: &FsPath, source: &str) -> Result<Vec<u8>, LooseTreeError> {
+    std::fs::read(root.join(source)).map_err(|source_error| LooseTreeError::Io {
+        path: source.to_string(),
+        source: source_error,
+    })
+}
+
+/// Pads data with zeroes until its length is a multiple of [`DATA_ALIGNMENT`]
+fn align(data: &mut Vec<u8>) {
+    let padding = (DATA_ALIGNMENT - data.len() % DATA_ALIGNMENT) % DATA_ALIGNMENT;
+    data.resize(data.len() + padding, 0);
+}
+
+/// Fills in a metadata for some file data, returning the data as it is stored in the archive
+fn store_data(metadata: &mut Metadata, data: Vec<u8>) -> Vec<u8> {
+    metadata.decompressed_size = data.len();
+
+    #[cfg(feature = "compression")]
+    let data = {
+        let compressed = crate::compress_data(&data);
+        if compressed.len() < data.len() {
+            metadata.is_compressed = true;
+            metadata.is_standard_zstd = true;
+            compressed
+        } else {
+            data
+        }
+    };
+
+    metadata.compressed_size = data.len();
+    data
+}
+
+impl Archive {
+    /// Builds an archive out of a directory of loose files and a manifest describing them
+    ///
+    /// ### Arguments
+    /// * `root` - The directory which the sources in the manifest are relative to
+    /// * `manifest` - The packages, files and stream files to put in the archive
+    ///
+    /// ### Returns
+    /// * `Ok(_)` - The resolved archive and its file data
+    /// * `Err(_)` - The manifest is invalid, or one of its files could not be read
+    ///
+    /// ### Notes
+    /// * Packages are added first, then every owned file, then shared files and finally child packages,
+    ///   so the manifest can refer to packages and files in any order
+    /// * Files are added to the search filesystem along with the packaged filesystem
+    /// * The data of each group is laid out one after another, with every file aligned to 0x10 bytes.
+    ///   With the `compression` feature, file data is compressed when that makes it smaller.
+    /// * The archive has no info groups, version groups or patches, and uses the
+    ///   [retail](LocaleRegionTable::retail) region lookup table
+    /// * The file data and stream data are both placed at offset 0, see
+    ///   [`LooseArchive::set_data_offsets`] to give the tables their final offsets
+    pub fn from_loose_tree(
+        root: impl AsRef<FsPath>,
+        manifest: &LooseManifest,
+    ) -> Result<LooseArchive, LooseTreeError> {
+        let root = root.as_ref();
+
+        let mut archive = Archive {
+            file_section_offset: 0,
+            packaged_fs: PackagedEngine::new(manifest.version.clone()),
+            search_fs: SearchEngine::new(),
+            stream_fs: StreamEngine::new(),
+            version: manifest.version.clone(),
+            region_lookup_table: LocaleRegionTable::retail(),
+            options: ArchiveOptions::default(),
+        };
+
+        for package in manifest.packages.iter() {
+            if Package::from_str(&package.path).is_err() {
+                return Err(LooseTreeError::InvalidPath(package.path.clone()));
+            }
+
+            if archive.packaged_fs.has_package(package.path.as_str()) {
+                return Err(LooseTreeError::DuplicatePackage(package.path.clone()));
+            }
+
+            archive.packaged_fs.add_package(
+                &package.path,
+                package.lifetime.as_str(),
+                package.variants,
+            );
+        }
+
+        let mut data: HashMap<TableIndex<Metadata>, Vec<u8>> = HashMap::new();
+
+        for package in manifest.packages.iter() {
+            for file in package.files.iter() {
+                if Path::from_str(&file.path).is_err() {
+                    return Err(LooseTreeError::InvalidPath(file.path.clone()));
+                }
+
+                if archive.packaged_fs.has_file(file.path.as_str())
+                    || archive.search_fs.get_path(file.path.as_str()).is_some()
+                {
+                    return Err(LooseTreeError::DuplicateFile(file.path.clone()));
+                }
+
+                if !file.variants.is_empty() && package.variants == VariantKind::None {
+                    return Err(LooseTreeError::UnexpectedVariants {
+                        file: file.path.clone(),
+                    });
+                }
+
+                let shared = read_source(root, file.source.as_deref().unwrap_or(&file.path))?;
+                let info = archive.add_file(&file.path, &package.path);
+
+                if file.variants.is_empty() {
+                    let descriptor = archive.packaged_fs.infos[info].descriptors.get(0);
+                    data.insert(
+                        archive.packaged_fs.descriptors[descriptor].metadata(),
+                        shared,
+                    );
+                    continue;
+                }
+
+                let mut variants = vec![None; package.variants.variant_count()];
+                for variant in file.variants.iter() {
+                    let index = variant_index(package.variants, &file.path, &variant.name)?;
+                    variants[index] = Some(read_source(root, &variant.source)?);
+                }
+
+                let metadatas = archive.packaged_fs.localize_file(info);
+                for (position, metadata) in metadatas.into_iter().enumerate() {
+                    let variant = position
+                        .checked_sub(1)
+                        .and_then(|index| variants[index].take());
+                    data.insert(metadata, variant.unwrap_or_else(|| shared.clone()));
+                }
+            }
+        }
+
+        for package in manifest.packages.iter() {
+            for file in package.shared_files.iter() {
+                if !archive.packaged_fs.has_file(file.as_str()) {
+                    return Err(LooseTreeError::MissingFile(file.clone()));
+                }
+
+                archive
+                    .packaged_fs
+                    .add_shared_file(file.as_str(), package.path.as_str());
+            }
+
+            for child in package.child_packages.iter() {
+                if !archive.packaged_fs.has_package(child.as_str()) {
+                    return Err(LooseTreeError::MissingPackage(child.clone()));
+                }
+
+                archive
+                    .packaged_fs
+                    .add_child_package(package.path.as_str(), child.as_str());
+            }
+        }
+
+        let mut file_data = vec![];
+        let engine = &mut archive.packaged_fs;
+
+        for group in engine.groups.iter_mut() {
+            group.archive_offset = file_data.len();
+            group.decompressed_size = 0;
+
+            let metadatas: Vec<_> = group.metadatas().iter().collect();
+            for metadata in metadatas {
+                let metadata_ref = &mut engine.metadatas[metadata];
+                let stored = store_data(metadata_ref, data.remove(&metadata).unwrap_or_default());
+                metadata_ref.group_offset = file_data.len() - group.archive_offset;
+                group.decompressed_size += metadata_ref.decompressed_size;
+
+                file_data.extend_from_slice(&stored);
+                align(&mut file_data);
+            }
+
+            group.compressed_size = file_data.len() - group.archive_offset;
+        }
+
+        let mut stream_data = vec![];
+
+        for file in manifest.stream_files.iter() {
+            if archive.stream_fs.get_path(file.path.as_str()).is_some() {
+                return Err(LooseTreeError::DuplicateFile(file.path.clone()));
+            }
+
+            let source = match &file.source {
+                Some(source) => source.clone(),
+                None => file.path.replacen("stream:/", "stream/", 1),
+            };
+            let default = read_source(root, &source)?;
+
+            let mut variants = vec![None; file.variant_kind.variant_count()];
+            for variant in file.variants.iter() {
+                let index = variant_index(file.variant_kind, &file.path, &variant.name)?;
+                variants[index] = Some(read_source(root, &variant.source)?);
+            }
+
+            let path = archive.stream_fs.add_file(&file.path, file.variant_kind);
+            let links: Vec<_> = archive.stream_fs.paths[path].links.iter().collect();
+
+            for (index, link) in links.into_iter().enumerate() {
+                let data = variants
+                    .get_mut(index)
+                    .and_then(Option::take)
+                    .unwrap_or_else(|| default.clone());
+
+                let metadata = archive.stream_fs.links[link].metadata();
+                archive.stream_fs.metadatas[metadata] = StreamMetadata {
+                    size: data.len(),
+                    offset: stream_data.len(),
+                };
+
+                stream_data.extend_from_slice(&data);
+                align(&mut stream_data);
+            }
+        }
+
+        Ok(LooseArchive {
+            archive,
+            file_data,
+            stream_data,
+            stream_offset: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, path::PathBuf};
+
+    use super::*;
+    use crate::engines::Locale;
+
+    const MODEL: &str = "fighter/mario/model/body/c00/model.numdlb";
+    const MESSAGE: &str = "ui/message/msg_name.msbt";
+    const BGM: &str = "stream:/sound/bgm/bgm_crs01.nus3audio";
+
+    /// Writes the loose files of the manifest into a new directory
+    fn write_tree(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("snarc-{}-{}", name, std::process::id()));
+        for (path, data) in [
+            (MODEL, b"model data ".repeat(16)),
+            (MESSAGE, b"shared".to_vec()),
+            ("msg_name_us_en.msbt", b"english".to_vec()),
+            ("stream/sound/bgm/bgm_crs01.nus3audio", b"bgm".to_vec()),
+        ] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, data).unwrap();
+        }
+        root
+    }
+
+    fn loose_file(path: &str) -> LooseFile {
+        LooseFile {
+            path: path.to_string(),
+            source: None,
+            variants: vec![],
+        }
+    }
+
+    fn build_manifest() -> LooseManifest {
+        let mut message = loose_file(MESSAGE);
+        message.variants.push(LooseVariant {
+            name: "us_en".to_string(),
+            source: "msg_name_us_en.msbt".to_string(),
+        });
+
+        LooseManifest {
+            version: Version::new(13, 0, 1),
+            packages: vec![
+                LoosePackage {
+                    path: "fighter/mario/c00".to_string(),
+                    lifetime: "fighter".to_string(),
+                    variants: VariantKind::None,
+                    files: vec![loose_file(MODEL)],
+                    shared_files: vec![MESSAGE.to_string()],
+                    child_packages: vec!["ui/message".to_string()],
+                },
+                LoosePackage {
+                    path: "ui/message".to_string(),
+                    lifetime: "ui".to_string(),
+                    variants: VariantKind::Localized,
+                    files: vec![message],
+                    shared_files: vec![],
+                    child_packages: vec![],
+                },
+            ],
+            stream_files: vec![LooseStreamFile {
+                path: BGM.to_string(),
+                source: None,
+                variant_kind: VariantKind::None,
+                variants: vec![],
+            }],
+        }
+    }
+
+    #[test]
+    fn loose_files_are_read_back() {
+        let root = write_tree("loose-read");
+        let loose = Archive::from_loose_tree(&root, &build_manifest());
+        std::fs::remove_dir_all(root).unwrap();
+        let mut loose = loose.unwrap();
+
+        let archive = &loose.archive;
+        let package = archive
+            .packaged_fs
+            .get_package("fighter/mario/c00")
+            .unwrap();
+        assert_eq!(package.infos.len(), 2);
+        assert_eq!(package.child_packages.len(), 1);
+        assert!(archive.search_fs.get_path(MESSAGE).is_some());
+
+        let view = archive.at_version(Version::new(13, 0, 1)).unwrap();
+        let mut reader = Cursor::new(&loose.file_data);
+        let mut read = |file, locale| view.read_file(&mut reader, file, locale).unwrap().unwrap();
+        assert_eq!(read(MODEL, Locale::Japanese), b"model data ".repeat(16));
+        assert_eq!(read(MESSAGE, Locale::UsEnglish), b"english");
+        assert_eq!(read(MESSAGE, Locale::Japanese), b"shared");
+
+        let stream = &archive.stream_fs;
+        let link = stream.get_path(BGM).unwrap().links.get(0);
+        let metadata = &stream.metadatas[stream.links[link].metadata()];
+        let offset = metadata.offset;
+        assert_eq!(&loose.stream_data[offset..offset + metadata.size], b"bgm");
+
+        loose.set_data_offsets(0x100, 0x1000);
+        let stream = &loose.archive.stream_fs;
+        assert_eq!(
+            stream.metadatas[stream.links[link].metadata()].offset,
+            offset + 0x100
+        );
+        assert_eq!(loose.archive.file_section_offset, 0x1000);
+    }
+
+    #[test]
+    fn invalid_manifests_are_rejected() {
+        let root = write_tree("loose-invalid");
+
+        let mut manifest = build_manifest();
+        manifest.packages[0]
+            .child_packages
+            .push("fighter/luigi/c00".to_string());
+        let missing = Archive::from_loose_tree(&root, &manifest);
+
+        let mut manifest = build_manifest();
+        manifest.packages[1].files.push(loose_file(MESSAGE));
+        let duplicate = Archive::from_loose_tree(&root, &manifest);
+
+        let mut manifest = build_manifest();
+        manifest.packages[1].files[0].variants[0].name = "us".to_string();
+        let unknown = Archive::from_loose_tree(&root, &manifest);
+
+        let mut manifest = build_manifest();
+        manifest.packages[0].files[0].variants = manifest.packages[1].files[0].variants.clone();
+        let unexpected = Archive::from_loose_tree(&root, &manifest);
+
+        std::fs::remove_dir_all(root).unwrap();
+
+        assert!(matches!(missing, Err(LooseTreeError::MissingPackage(_))));
+        assert!(matches!(duplicate, Err(LooseTreeError::DuplicateFile(_))));
+        assert!(matches!(
+            unknown,
+            Err(LooseTreeError::UnknownVariant { .. })
+        ));
+        assert!(matches!(
+            unexpected,
+            Err(LooseTreeError::UnexpectedVariants { .. })
+        ));
+    }
+}
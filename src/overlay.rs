@@ -0,0 +1,744 @@
+use std::{
+    collections::HashSet,
+    io,
+    path::{Path as FsPath, PathBuf},
+};
+
+use hash40::Hash40;
+
+use crate::{
+    archive::Archive,
+    diff::Filesystem,
+    engines::{
+        packaged::types::{DescriptorLoadArguments, Info, Link, Metadata, Package, Path},
+        stream::types::{StreamMetadata, StreamPath},
+        table::TableIndex,
+        VariantKind,
+    },
+};
+
+/// What applying an overlay file did to the archive
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OverlayAction {
+    /// The file already existed, and its data is replaced
+    Replaced,
+
+    /// The file is new, and was added to the archive
+    Added,
+}
+
+/// The file data in the archive which an overlay file's data goes to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OverlayTarget {
+    /// A variant of a file in the packaged filesystem
+    Packaged {
+        info: TableIndex<Info>,
+        metadata: TableIndex<Metadata>,
+    },
+
+    /// A variant of a file in the stream filesystem
+    Stream {
+        path: TableIndex<StreamPath>,
+        metadata: TableIndex<StreamMetadata>,
+    },
+}
+
+/// A file from the overlay which was applied to the archive
+#[derive(Debug, Clone)]
+pub struct OverlayFile {
+    /// The path of the file on disk
+    pub source: PathBuf,
+
+    /// The full path of the file in the archive
+    pub full_path: Hash40,
+
+    /// Whether the file was replaced or added
+    pub action: OverlayAction,
+
+    /// Where the file's data goes
+    pub target: OverlayTarget,
+
+    /// The package which the file was added to. Only set for added files.
+    pub package: Option<TableIndex<Package>>,
+
+    /// Whether the replaced data is also loaded through other files or packages, which will
+    /// see the new data as well. Only set when [`OverlayConfig::allow_shared`] is enabled.
+    pub is_shared: bool,
+}
+
+impl OverlayFile {
+    /// The filesystem that the file belongs to
+    pub fn filesystem(&self) -> Filesystem {
+        match self.target {
+            OverlayTarget::Packaged { .. } => Filesystem::Packaged,
+            OverlayTarget::Stream { .. } => Filesystem::Stream,
+        }
+    }
+}
+
+/// Why a file from the overlay could not be applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OverlayConflictKind {
+    /// The path is not valid UTF-8, or does not have a parent, a name and an extension
+    InvalidPath,
+
+    /// The path is a folder in the search filesystem
+    FolderExists,
+
+    /// The file is new, and no package could be found for it
+    NoPackage,
+
+    /// The file is new to the stream filesystem, which only supports replacing files
+    NewStreamFile,
+
+    /// The file name has a variant suffix for a locale or region that the file does not have
+    UnknownVariant(String),
+
+    /// The file's data could not be found in the archive
+    MissingData,
+
+    /// The file's data is also loaded through other files or packages, and
+    /// [`OverlayConfig::allow_shared`] is not enabled
+    SharedData,
+
+    /// More than one file in the overlay targets the same data. The first one is applied.
+    Duplicate,
+}
+
+/// A file from the overlay which could not be applied
+#[derive(Debug, Clone)]
+pub struct OverlayConflict {
+    /// The path of the file on disk
+    pub source: PathBuf,
+
+    /// The full path of the file in the archive, as far as it could be worked out
+    pub path: String,
+
+    /// Why the file could not be applied
+    pub kind: OverlayConflictKind,
+}
+
+/// Everything that was done while applying an overlay
+///
+/// Created by [`Archive::apply_overlay`]
+#[derive(Debug, Default, Clone)]
+pub struct OverlayReport {
+    /// Every file which was applied, in the order they were applied
+    pub files: Vec<OverlayFile>,
+
+    /// Every file which could not be applied
+    pub conflicts: Vec<OverlayConflict>,
+}
+
+impl OverlayReport {
+    /// The files of the report which were replaced or added
+    pub fn files_with_action(&self, action: OverlayAction) -> impl Iterator<Item = &OverlayFile> {
+        self.files.iter().filter(move |file| file.action == action)
+    }
+
+    /// Checks if every file in the overlay was applied
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Controls which packages new files are added to when applying an overlay
+#[derive(Debug, Default, Clone)]
+pub struct OverlayConfig {
+    /// The package for files under a path prefix, such as `("ui/message", "ui/message")`.
+    /// The longest matching prefix is used.
+    pub packages: Vec<(String, String)>,
+
+    /// The package for new files which have no configured or inferred package
+    pub default_package: Option<String>,
+
+    /// Whether to replace data which is also loaded through other files or packages.
+    /// When this is not set, those files are reported as conflicts.
+    pub allow_shared: bool,
+}
+
+impl OverlayConfig {
+    /// Creates a config where new files go to their inferred package, or are reported as conflicts
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds new files under a path prefix to a package
+    pub fn package_for(mut self, prefix: impl Into<String>, package: impl Into<String>) -> Self {
+        self.packages.push((prefix.into(), package.into()));
+        self
+    }
+
+    /// Adds new files to a package when no other package is found for them
+    pub fn default_package(mut self, package: impl Into<String>) -> Self {
+        self.default_package = Some(package.into());
+        self
+    }
+
+    /// Sets whether to replace data which is also loaded through other files or packages
+    pub fn allow_shared(mut self, allow_shared: bool) -> Self {
+        self.allow_shared = allow_shared;
+        self
+    }
+}
+
+/// Collects every file under a directory, sorted by path
+fn collect_files(dir: &FsPath, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            collect_files(&entry, files)?;
+        } else {
+            files.push(entry);
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits a `+locale` or `+region` suffix off of the name of a file
+///
+/// For example, `model+us_en.numdlb` is the `us_en` variant of `model.numdlb`.
+/// A suffix which is not the name of a locale or region is kept as part of the name.
+fn split_variant(path: &str) -> (String, Option<&str>) {
+    let (parent, name) = match path.rsplit_once('/') {
+        Some((parent, name)) => (Some(parent), name),
+        None => (None, path),
+    };
+
+    let (stem, extension) = match name.split_once('.') {
+        Some((stem, extension)) => (stem, Some(extension)),
+        None => (name, None),
+    };
+
+    // Only known locales and regions count, so other names with a `+` are left alone
+    let (stem, variant) = match stem.rsplit_once('+') {
+        Some((stem, variant))
+            if [VariantKind::Localized, VariantKind::Regional]
+                .iter()
+                .any(|kind| variant_position(*kind, Some(variant), 0).is_some()) =>
+        {
+            (stem, Some(variant))
+        }
+        _ => return (path.to_string(), None),
+    };
+
+    let mut path = String::new();
+    if let Some(parent) = parent {
+        path.push_str(parent);
+        path.push('/');
+    }
+    path.push_str(stem);
+    if let Some(extension) = extension {
+        path.push('.');
+        path.push_str(extension);
+    }
+
+    (path, variant)
+}
+
+/// Finds the position of a variant out of a file's descriptors or stream links
+///
+/// ### Returns
+/// * `Some(0)` - There is no variant name, so the shared data (or the first link) is used
+/// * `Some(_)` - The position of the named variant
+/// * `None` - The file does not have a variant with that name
+fn variant_position(variants: VariantKind, name: Option<&str>, offset: usize) -> Option<usize> {
+    match name {
+        None => Some(0),
+        Some(name) => (0..variants.variant_count())
+            .find(|index| variants.variant_name(*index) == name)
+            .map(|index| index + offset),
+    }
+}
+
+struct OverlayState<'a> {
+    config: &'a OverlayConfig,
+    report: OverlayReport,
+    shared_links: Option<HashSet<TableIndex<Link>>>,
+    seen_packaged: HashSet<TableIndex<Metadata>>,
+    seen_stream: HashSet<TableIndex<StreamMetadata>>,
+}
+
+impl OverlayState<'_> {
+    fn conflict(&mut self, source: &FsPath, path: &str, kind: OverlayConflictKind) {
+        self.report.conflicts.push(OverlayConflict {
+            source: source.to_path_buf(),
+            path: path.to_string(),
+            kind,
+        });
+    }
+}
+
+impl Archive {
+    /// Finds the links of every file whose data is also loaded through another info
+    fn shared_links(&self) -> HashSet<TableIndex<Link>> {
+        let engine = &self.packaged_fs;
+        engine
+            .descriptors
+            .iter()
+            .filter_map(|descriptor| match &descriptor.load_args {
+                DescriptorLoadArguments::Unowned { link }
+                | DescriptorLoadArguments::SharedButOwned { link } => Some(link.index()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Finds the package that a new file should be added to
+    ///
+    /// The configured prefixes are checked first, then the package of any other file in the
+    /// same folder, then a package at the path of any of the file's parent folders, and
+    /// finally the configured default package.
+    fn overlay_package(&self, path: &str, config: &OverlayConfig) -> Option<TableIndex<Package>> {
+        let engine = &self.packaged_fs;
+
+        let configured = config
+            .packages
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.trim_end_matches('/')))
+            .max_by_key(|(prefix, _)| prefix.len());
+        if let Some((_, package)) = configured {
+            return engine.package_lookup.get(&Hash40::new(package)).copied();
+        }
+
+        let parent = path
+            .rsplit_once('/')
+            .map(|(parent, _)| parent)
+            .unwrap_or("");
+
+        let sibling = self.search_fs.read_dir(parent).and_then(|mut entries| {
+            entries.find_map(|entry| {
+                let file = engine.get_file(entry.full_path())?;
+                let link = &engine.links[file.link()];
+                link.is_owner_package().then(|| link.package())
+            })
+        });
+        if sibling.is_some() {
+            return sibling;
+        }
+
+        let mut current = parent;
+        while !current.is_empty() {
+            if let Some(package) = engine.package_lookup.get(&Hash40::new(current)) {
+                return Some(*package);
+            }
+
+            current = current
+                .rsplit_once('/')
+                .map(|(parent, _)| parent)
+                .unwrap_or("");
+        }
+
+        config
+            .default_package
+            .as_ref()
+            .and_then(|package| engine.package_lookup.get(&Hash40::new(package)).copied())
+    }
+
+    /// Finds the data of an existing file that an overlay file replaces
+    ///
+    /// ### Returns
+    /// * `Ok(_)` - The metadata of the data, and whether it is shared
+    /// * `Err(_)` - Why the file can't be applied
+    fn overlay_replaced_data(
+        &self,
+        state: &mut OverlayState,
+        info: TableIndex<Info>,
+        variant: Option<&str>,
+    ) -> Result<(TableIndex<Metadata>, bool), OverlayConflictKind> {
+        let engine = &self.packaged_fs;
+        let info_ref = &engine.infos[info];
+        let position = variant_position(info_ref.variants(), variant, 1)
+            .filter(|position| *position < info_ref.descriptors.len())
+            .ok_or_else(|| {
+                OverlayConflictKind::UnknownVariant(variant.unwrap_or_default().into())
+            })?;
+
+        let descriptor = info_ref.descriptors.get(position);
+        let (data_descriptor, metadata) = engine
+            .descriptor_data(descriptor, position)
+            .ok_or(OverlayConflictKind::MissingData)?;
+
+        let is_shared = data_descriptor != descriptor || {
+            let shared_links = state
+                .shared_links
+                .get_or_insert_with(|| self.shared_links());
+            shared_links.contains(&info_ref.link())
+        };
+
+        if is_shared && !state.config.allow_shared {
+            return Err(OverlayConflictKind::SharedData);
+        }
+
+        if state.seen_packaged.contains(&metadata) {
+            return Err(OverlayConflictKind::Duplicate);
+        }
+
+        Ok((metadata, is_shared))
+    }
+
+    fn apply_packaged_file(&mut self, state: &mut OverlayState, source: &FsPath, path: &str) {
+        let (path, variant) = split_variant(path);
+        let hash = Hash40::new(&path);
+
+        if self.search_fs.get_folder(hash).is_some() {
+            return state.conflict(source, &path, OverlayConflictKind::FolderExists);
+        }
+
+        let engine = &self.packaged_fs;
+        let (info, action, package, metadata, is_shared) = match engine.get_file(hash) {
+            Some(file) => {
+                let info = engine.links[file.link()].info();
+                match self.overlay_replaced_data(state, info, variant) {
+                    Ok((metadata, is_shared)) => {
+                        (info, OverlayAction::Replaced, None, metadata, is_shared)
+                    }
+                    Err(kind) => return state.conflict(source, &path, kind),
+                }
+            }
+            None => {
+                if variant.is_some() {
+                    return state.conflict(
+                        source,
+                        &path,
+                        OverlayConflictKind::UnknownVariant(variant.unwrap_or_default().into()),
+                    );
+                }
+
+                if path.parse::<Path>().is_err() {
+                    return state.conflict(source, &path, OverlayConflictKind::InvalidPath);
+                }
+
+                let package = match self.overlay_package(&path, state.config) {
+                    Some(package) => package,
+                    None => return state.conflict(source, &path, OverlayConflictKind::NoPackage),
+                };
+
+                // Every check is done by now, so a conflict never leaves a partly added file.
+                // The new file owns its data, which nothing else can share or target yet.
+                if self.search_fs.get_path(hash).is_none() {
+                    self.search_fs.add_file(&path);
+                }
+
+                let engine = &mut self.packaged_fs;
+                let full_path = engine.packages[package].full_path;
+                let info = engine.add_file(&path, full_path);
+                let descriptor = engine.infos[info].descriptors.get(0);
+                let metadata = engine.descriptors[descriptor].metadata();
+                (info, OverlayAction::Added, Some(package), metadata, false)
+            }
+        };
+
+        state.seen_packaged.insert(metadata);
+        state.report.files.push(OverlayFile {
+            source: source.to_path_buf(),
+            full_path: hash,
+            action,
+            target: OverlayTarget::Packaged { info, metadata },
+            package,
+            is_shared,
+        });
+    }
+
+    fn apply_stream_file(&mut self, state: &mut OverlayState, source: &FsPath, path: &str) {
+        let (path, variant) = split_variant(path);
+        let hash = Hash40::new(&path);
+
+        let engine = &self.stream_fs;
+        let index = match engine.path_lookup.get(&hash) {
+            Some(index) => *index,
+            None => return state.conflict(source, &path, OverlayConflictKind::NewStreamFile),
+        };
+
+        let stream_path = &engine.paths[index];
+        // Stream files have no shared data, so their variants start at the first link
+        let position = match variant_position(stream_path.variants(), variant, 0) {
+            Some(position) if position < stream_path.links.len() => position,
+            _ => {
+                let kind = OverlayConflictKind::UnknownVariant(variant.unwrap_or_default().into());
+                return state.conflict(source, &path, kind);
+            }
+        };
+
+        let metadata = engine.links[stream_path.links.get(position)].metadata();
+        let is_shared = engine
+            .links
+            .iter()
+            .filter(|link| link.metadata() == metadata)
+            .count()
+            > 1;
+
+        if is_shared && !state.config.allow_shared {
+            return state.conflict(source, &path, OverlayConflictKind::SharedData);
+        }
+
+        if !state.seen_stream.insert(metadata) {
+            return state.conflict(source, &path, OverlayConflictKind::Duplicate);
+        }
+
+        state.report.files.push(OverlayFile {
+            source: source.to_path_buf(),
+            full_path: hash,
+            action: OverlayAction::Replaced,
+            target: OverlayTarget::Stream {
+                path: index,
+                metadata,
+            },
+            package: None,
+            is_shared,
+        });
+    }
+
+    /// Applies a directory of loose files onto the archive
+    ///
+    /// This is the same as [`apply_overlay_with`](Self::apply_overlay_with), using the default
+    /// [`OverlayConfig`]
+    pub fn apply_overlay(&mut self, dir: impl AsRef<FsPath>) -> io::Result<OverlayReport> {
+        self.apply_overlay_with(dir, &OverlayConfig::new())
+    }
+
+    /// Applies a directory of loose files onto the archive
+    ///
+    /// ### Arguments
+    /// * `dir` - The directory of files, laid out by their path in the archive. Stream files
+    ///   go under a `stream;` folder, which stands in for the `stream:` prefix.
+    /// * `config` - Which packages new files are added to
+    ///
+    /// ### Returns
+    /// * `Ok(_)` - What was applied, and every file which could not be applied
+    /// * `Err(_)` - The directory could not be read
+    ///
+    /// ### Panicking
+    /// Panics if the archive has not been [resolved](Self::resolve)
+    ///
+    /// ### Notes
+    /// * Files which already exist have their data replaced, and new files are added to the
+    ///   packaged and search filesystems. See [`OverlayConfig`] for how their package is picked.
+    /// * A `+locale` or `+region` suffix on a file name, such as `model+us_en.numdlb`, replaces
+    ///   just that variant of the file
+    /// * Data which is also loaded through other files or packages is only replaced when
+    ///   [`OverlayConfig::allow_shared`] is enabled, and is reported as a conflict otherwise
+    /// * The archive does not hold file data, so the metadata of every target is left untouched.
+    ///   The caller writes the data at each [`OverlayTarget`] and updates its metadata: the sizes,
+    ///   the offset and the compression flags.
+    pub fn apply_overlay_with(
+        &mut self,
+        dir: impl AsRef<FsPath>,
+        config: &OverlayConfig,
+    ) -> io::Result<OverlayReport> {
+        let dir = dir.as_ref();
+
+        let mut sources = vec![];
+        collect_files(dir, &mut sources)?;
+
+        let mut state = OverlayState {
+            config,
+            report: OverlayReport::default(),
+            shared_links: None,
+            seen_packaged: HashSet::new(),
+            seen_stream: HashSet::new(),
+        };
+
+        for source in sources {
+            let relative = source.strip_prefix(dir).unwrap_or(&source);
+            let path = match relative.to_str() {
+                Some(path) => path.replace('\\', "/"),
+                None => {
+                    let path = relative.to_string_lossy();
+                    state.conflict(&source, &path, OverlayConflictKind::InvalidPath);
+                    continue;
+                }
+            };
+
+            match path.strip_prefix("stream;/") {
+                Some(stream) => {
+                    self.apply_stream_file(&mut state, &source, &format!("stream:/{}", stream))
+                }
+                None => self.apply_packaged_file(&mut state, &source, &path),
+            }
+        }
+
+        Ok(state.report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{archive::tests::build_archive, Hashable};
+
+    /// Writes an empty file for every path under a new overlay directory
+    fn write_overlay(name: &str, paths: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("snarc-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for path in paths {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, []).unwrap();
+        }
+        dir
+    }
+
+    fn conflict_kinds(report: &OverlayReport) -> Vec<(&str, &OverlayConflictKind)> {
+        report
+            .conflicts
+            .iter()
+            .map(|conflict| (conflict.path.as_str(), &conflict.kind))
+            .collect()
+    }
+
+    #[test]
+    fn files_are_replaced_added_or_reported() {
+        let mut archive = build_archive();
+        let dir = write_overlay(
+            "overlay-apply",
+            &[
+                "fighter/mario/model/body/c00/model.numdlb",
+                "fighter/mario/model/body/c00/overlay_new.nutexb",
+                "fighter/mario/motion/body/c00/a00wait1+us_en.nuanmb",
+                "overlay_orphan/overlay_new.nus3bank",
+                "stream;/sound/bgm/bgm_crs01.nus3audio",
+                "stream;/sound/bgm/overlay_new.nus3audio",
+            ],
+        );
+
+        let report = archive.apply_overlay(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let applied = report
+            .files
+            .iter()
+            .map(|file| (file.full_path, file.action, file.filesystem()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            applied,
+            [
+                (
+                    "fighter/mario/model/body/c00/model.numdlb".to_hash(),
+                    OverlayAction::Replaced,
+                    Filesystem::Packaged
+                ),
+                (
+                    "fighter/mario/model/body/c00/overlay_new.nutexb".to_hash(),
+                    OverlayAction::Added,
+                    Filesystem::Packaged
+                ),
+                (
+                    "stream:/sound/bgm/bgm_crs01.nus3audio".to_hash(),
+                    OverlayAction::Replaced,
+                    Filesystem::Stream
+                ),
+            ]
+        );
+
+        let package = archive
+            .packaged_fs
+            .package_lookup
+            .get(&"fighter/mario/c00".to_hash());
+        assert_eq!(report.files[1].package.as_ref(), package);
+        assert!(report.files.iter().all(|file| !file.is_shared));
+
+        assert_eq!(
+            conflict_kinds(&report),
+            [
+                (
+                    "fighter/mario/motion/body/c00/a00wait1.nuanmb",
+                    &OverlayConflictKind::UnknownVariant("us_en".into())
+                ),
+                (
+                    "overlay_orphan/overlay_new.nus3bank",
+                    &OverlayConflictKind::NoPackage
+                ),
+                (
+                    "stream:/sound/bgm/overlay_new.nus3audio",
+                    &OverlayConflictKind::NewStreamFile
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn conflicts_leave_the_archive_untouched() {
+        let mut archive = build_archive();
+        let dir = write_overlay(
+            "overlay-untouched",
+            &[
+                "fighter/mario/model/body/c00/overlay_untouched+us_en.nutexb",
+                "fighter/mario/motion/body",
+                "overlay_untouched/overlay_new.nus3bank",
+            ],
+        );
+
+        let report = archive.apply_overlay(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(report.files.is_empty());
+        assert_eq!(
+            conflict_kinds(&report),
+            [
+                (
+                    "fighter/mario/model/body/c00/overlay_untouched.nutexb",
+                    &OverlayConflictKind::UnknownVariant("us_en".into())
+                ),
+                (
+                    "fighter/mario/motion/body",
+                    &OverlayConflictKind::FolderExists
+                ),
+                (
+                    "overlay_untouched/overlay_new.nus3bank",
+                    &OverlayConflictKind::NoPackage
+                ),
+            ]
+        );
+
+        for path in [
+            "fighter/mario/model/body/c00/overlay_untouched.nutexb",
+            "overlay_untouched/overlay_new.nus3bank",
+        ] {
+            assert!(archive.packaged_fs.get_file(path.to_hash()).is_none());
+            assert!(archive.search_fs.get_path(path.to_hash()).is_none());
+        }
+    }
+
+    #[test]
+    fn shared_data_is_only_replaced_when_allowed() {
+        let mut archive = build_archive();
+        archive
+            .packaged_fs
+            .add_package("fighter/luigi/c00", "fighter", VariantKind::None);
+        archive.packaged_fs.add_shared_file(
+            "fighter/mario/model/body/c00/model.numdlb",
+            "fighter/luigi/c00",
+        );
+
+        let dir = write_overlay(
+            "overlay-shared",
+            &["fighter/mario/model/body/c00/model.numdlb"],
+        );
+
+        let report = archive.apply_overlay(&dir).unwrap();
+        assert!(report.files.is_empty());
+        assert_eq!(
+            conflict_kinds(&report),
+            [(
+                "fighter/mario/model/body/c00/model.numdlb",
+                &OverlayConflictKind::SharedData
+            )]
+        );
+
+        let report = archive
+            .apply_overlay_with(&dir, &OverlayConfig::new().allow_shared(true))
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(report.is_clean());
+        assert_eq!(report.files.len(), 1);
+        assert!(report.files[0].is_shared);
+        assert_eq!(report.files[0].action, OverlayAction::Replaced);
+    }
+}
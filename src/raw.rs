@@ -1,5 +1,34 @@
-use binrw::binrw;
+//! Zero-copy views over the decompressed archive tables
+//!
+//! Every type in this module has the exact layout of its table entry in the archive,
+//! so the tables can be cast directly from the decompressed table sections
+//! without parsing or resolving anything.
+//!
+//! This is meant for read-only tools which only need to scan the tables, such as
+//! hash indexers. Use [`Archive`](crate::archive::Archive) to modify the archive.
+
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    ops::Range,
+};
+
+use binrw::{BinRead, BinResult, ReadOptions};
+use bytemuck::{Pod, Zeroable};
 use hash40::Hash40;
+use semver::Version;
+
+use crate::{
+    archive::{
+        ArchiveHeader, ArchiveOptions, ArchiveTablesHeader, CompressedTables, PackagedFsHeader,
+        SearchFsHeader, StreamFsHeader,
+    },
+    engines::{
+        packaged::types::{ChildPackage, Descriptor, Group, Info, Link, Metadata, Package, Path},
+        search::types::{SearchFolder, SearchPath},
+        stream::types::{StreamFolder, StreamLink, StreamMetadata, StreamPath},
+    },
+    Decompressor, Hashable,
+};
 
 bitflags::bitflags! {
     pub struct PackageFlags : u32 {
@@ -25,16 +54,27 @@ bitflags::bitflags! {
         const IS_VERSIONED_REGIONAL_DATA = 1 << 2;
         const IS_VERSIONED_LOCALIZED_DATA = 1 << 3;
     }
+
+    pub struct StreamPathFlags : u32 {
+        const IS_LOCALIZED = 1 << 0;
+        const IS_REGIONAL = 1 << 1;
+    }
 }
 
+/// A 40-bit hash packed together with a 24-bit index
+///
+/// The low byte of `len_and_index` is the length of the hashed string,
+/// and the upper three bytes are the index.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Pod, Zeroable)]
 pub struct RawHashKey {
-    hash: u32,
-    len_and_index: u32,
+    pub hash: u32,
+    pub len_and_index: u32,
 }
 
 impl RawHashKey {
     pub fn hash40(self) -> Hash40 {
-        Hash40(self.len_and_index as u64 | ((self.len_and_index & 0x0000_00FF) as u64) << 32)
+        Hash40(self.hash as u64 | ((self.len_and_index & 0x0000_00FF) as u64) << 32)
     }
 
     pub fn index(self) -> usize {
@@ -42,6 +82,23 @@ impl RawHashKey {
     }
 }
 
+/// A range of entries in a hash lookup table which share a bucket
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Pod, Zeroable)]
+pub struct RawBucket {
+    pub start: u32,
+    pub count: u32,
+}
+
+impl RawBucket {
+    pub fn range(self) -> Range<usize> {
+        let start = self.start as usize;
+        start..start + self.count as usize
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct RawPackage {
     pub path_and_group_index: RawHashKey,
     pub name: RawHashKey,
@@ -51,9 +108,23 @@ pub struct RawPackage {
     pub info_count: u32,
     pub child_start: u32,
     pub child_count: u32,
-    pub flags: PackageFlags,
+    pub flags: u32,
+}
+
+impl RawPackage {
+    pub fn flags(&self) -> PackageFlags {
+        PackageFlags::from_bits_truncate(self.flags)
+    }
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct RawChildPackage {
+    pub path_and_package_index: RawHashKey,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct RawGroup {
     archive_offset_1: u32,
     archive_offset_2: u32,
@@ -70,48 +141,582 @@ impl RawGroup {
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct RawPath {
     pub path_and_link_index: RawHashKey,
     pub extension_and_versioned_file_index: RawHashKey,
-    pub parent: Hash40,
-    pub file_name: Hash40,
+    pub parent: RawHashKey,
+    pub file_name: RawHashKey,
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct RawLink {
     pub owner: u32,
     pub info: u32,
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct RawInfo {
     pub path: u32,
     pub link: u32,
     pub descriptor: u32,
-    pub flags: InfoFlags,
+    pub flags: u32,
+}
+
+impl RawInfo {
+    pub fn flags(&self) -> InfoFlags {
+        InfoFlags::from_bits_truncate(self.flags)
+    }
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct RawDescriptor {
     pub group: u32,
     pub metadata: u32,
     pub load_args: u32,
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct RawMetadata {
     pub group_offset: u32,
     pub compressed_size: u32,
     pub decompressed_size: u32,
-    pub flags: MetadataFlags,
+    pub flags: u32,
+}
+
+impl RawMetadata {
+    pub fn flags(&self) -> MetadataFlags {
+        MetadataFlags::from_bits_truncate(self.flags)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct RawPatch {
+    pub version: u32,
+    pub file_count: u32,
+    pub group: u32,
+    pub file_start: u32,
+    pub lookup_size_in_bytes: u32,
+    pub info_count: u32,
+    pub descriptor_count: u32,
+    pub num_changed_this_patch: u32,
 }
 
+impl RawPatch {
+    pub fn version(&self) -> Version {
+        Version::new(
+            (self.version >> 16) as u64,
+            ((self.version >> 8) & 0xFF) as u64,
+            (self.version & 0xFF) as u64,
+        )
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct RawVersionedFile {
+    pub path_and_changed: RawHashKey,
+    pub info: u32,
+    pub group_index_start: u32,
+    pub link: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct RawSearchFolder {
     pub path_and_folder_count: RawHashKey,
     pub parent_and_file_count: RawHashKey,
-    pub name: Hash40,
+    pub name: RawHashKey,
     pub first_child_index: u32,
+    unused: u32,
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct RawSearchPath {
     pub path_and_next_index: RawHashKey,
     pub parent_and_is_folder: RawHashKey,
     pub name: RawHashKey,
     pub extension: RawHashKey,
 }
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct RawSearchPathKey {
+    pub key: RawHashKey,
+    pub path: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct RawStreamFolder {
+    pub name_and_count: RawHashKey,
+    pub start: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct RawStreamPath {
+    pub path_and_link: RawHashKey,
+    pub flags: u32,
+}
+
+impl RawStreamPath {
+    pub fn flags(&self) -> StreamPathFlags {
+        StreamPathFlags::from_bits_truncate(self.flags)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct RawStreamLink {
+    pub metadata: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+pub struct RawStreamMetadata {
+    size_1: u32,
+    size_2: u32,
+    offset_1: u32,
+    offset_2: u32,
+}
+
+impl RawStreamMetadata {
+    pub fn size(self) -> usize {
+        self.size_1 as usize | (self.size_2 as usize) << 32
+    }
+
+    pub fn offset(self) -> usize {
+        self.offset_1 as usize | (self.offset_2 as usize) << 32
+    }
+}
+
+// The raw views must line up exactly with the entries that the engines read
+const _: () = {
+    use std::mem::size_of;
+
+    assert!(size_of::<RawPackage>() == Package::REPR_SIZE);
+    assert!(size_of::<RawChildPackage>() == ChildPackage::REPR_SIZE);
+    assert!(size_of::<RawGroup>() == Group::REPR_SIZE);
+    assert!(size_of::<RawPath>() == Path::REPR_SIZE);
+    assert!(size_of::<RawLink>() == Link::REPR_SIZE);
+    assert!(size_of::<RawInfo>() == Info::REPR_SIZE);
+    assert!(size_of::<RawDescriptor>() == Descriptor::REPR_SIZE);
+    assert!(size_of::<RawMetadata>() == Metadata::REPR_SIZE);
+    assert!(size_of::<RawPatch>() == 0x20);
+    assert!(size_of::<RawVersionedFile>() == 0x14);
+    assert!(size_of::<RawSearchFolder>() == SearchFolder::REPR_SIZE);
+    assert!(size_of::<RawSearchPath>() == SearchPath::REPR_SIZE);
+    assert!(size_of::<RawSearchPathKey>() == 0xC);
+    assert!(size_of::<RawStreamFolder>() == StreamFolder::REPR_SIZE);
+    assert!(size_of::<RawStreamPath>() == StreamPath::REPR_SIZE);
+    assert!(size_of::<RawStreamLink>() == StreamLink::REPR_SIZE);
+    assert!(size_of::<RawStreamMetadata>() == StreamMetadata::REPR_SIZE);
+};
+
+/// A decompressed table section, stored as words so that every table inside
+/// of it is aligned for the raw types
+struct RawSection {
+    words: Vec<u32>,
+    len: usize,
+}
+
+impl RawSection {
+    /// Decompresses a table section straight into an aligned buffer
+    fn decompress(
+        header: &ArchiveTablesHeader,
+        data: &[u8],
+        decompressor: &dyn Decompressor,
+    ) -> io::Result<Self> {
+        let len = header.decompressed_size;
+        let mut words = vec![0u32; len.div_ceil(4)];
+        header.decompress_into(
+            data,
+            decompressor,
+            &mut bytemuck::cast_slice_mut::<u32, u8>(&mut words)[..len],
+        )?;
+        Ok(Self { words, len })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &bytemuck::cast_slice(&self.words)[..self.len]
+    }
+
+    fn table<T: Pod>(&self, range: &Range<usize>) -> &[T] {
+        bytemuck::cast_slice(&self.bytes()[range.clone()])
+    }
+}
+
+/// Tracks the position of the next table while laying out a section
+struct SectionCursor {
+    position: usize,
+    len: usize,
+}
+
+impl SectionCursor {
+    fn new(position: u64, section: &RawSection) -> Self {
+        Self {
+            position: position as usize,
+            len: section.len,
+        }
+    }
+
+    /// Takes the byte range of the next table
+    ///
+    /// ### Returns
+    /// * `Ok(_)` - The range of the table in the section
+    /// * `Err(_)` - The table extends past the end of the section
+    fn take<T>(&mut self, count: usize) -> BinResult<Range<usize>> {
+        let start = self.position;
+        let end = count
+            .checked_mul(std::mem::size_of::<T>())
+            .and_then(|size| start.checked_add(size))
+            .filter(|end| *end <= self.len)
+            .ok_or_else(|| binrw::Error::Custom {
+                pos: start as u64,
+                err: Box::new("Table extends past the end of its table section"),
+            })?;
+
+        self.position = end;
+        Ok(start..end)
+    }
+}
+
+/// The byte ranges of a single patch's tables
+struct RawPatchTables {
+    versioned_files: Range<usize>,
+    buckets: Range<usize>,
+    keys: Range<usize>,
+}
+
+/// A read-only view over all of the tables in the archive
+///
+/// The table sections are decompressed when the archive is opened, after which
+/// every table accessor is a cast over the decompressed data.
+pub struct RawArchive {
+    pub file_section_offset: usize,
+    pub version: Version,
+
+    non_user: RawSection,
+    user: RawSection,
+
+    stream_folders: Range<usize>,
+    stream_path_keys: Range<usize>,
+    stream_paths: Range<usize>,
+    stream_links: Range<usize>,
+    stream_metadatas: Range<usize>,
+
+    file_buckets: Range<usize>,
+    file_keys: Range<usize>,
+    paths: Range<usize>,
+    links: Range<usize>,
+    package_keys: Range<usize>,
+    packages: Range<usize>,
+    groups: Range<usize>,
+    child_packages: Range<usize>,
+    infos: Range<usize>,
+    descriptors: Range<usize>,
+    metadatas: Range<usize>,
+    patches: Range<usize>,
+    patch_tables: Vec<RawPatchTables>,
+
+    search_folder_keys: Range<usize>,
+    search_folders: Range<usize>,
+    search_path_keys: Range<usize>,
+    search_paths: Range<usize>,
+}
+
+macro_rules! raw_tables {
+    ($($section:ident.$name:ident: $T:ty),* $(,)?) => {
+        $(
+            pub fn $name(&self) -> &[$T] {
+                self.$section.table(&self.$name)
+            }
+        )*
+    };
+}
+
+impl RawArchive {
+    pub fn open(path: impl AsRef<std::path::Path>) -> BinResult<Self> {
+        Self::open_with(path, ArchiveOptions::default())
+    }
+
+    /// Opens an archive, decompressing its tables with the decompressor from `options`
+    pub fn open_with(
+        path: impl AsRef<std::path::Path>,
+        options: ArchiveOptions,
+    ) -> BinResult<Self> {
+        let mut reader = std::io::BufReader::with_capacity(0x0010_0000, std::fs::File::open(path)?);
+        Self::read_args(&mut reader, options)
+    }
+
+    raw_tables!(
+        non_user.stream_folders: RawStreamFolder,
+        non_user.stream_path_keys: RawHashKey,
+        non_user.stream_paths: RawStreamPath,
+        non_user.stream_links: RawStreamLink,
+        non_user.stream_metadatas: RawStreamMetadata,
+        non_user.file_buckets: RawBucket,
+        non_user.file_keys: RawHashKey,
+        non_user.paths: RawPath,
+        non_user.links: RawLink,
+        non_user.package_keys: RawHashKey,
+        non_user.packages: RawPackage,
+        non_user.groups: RawGroup,
+        non_user.child_packages: RawChildPackage,
+        non_user.infos: RawInfo,
+        non_user.descriptors: RawDescriptor,
+        non_user.metadatas: RawMetadata,
+        non_user.patches: RawPatch,
+        user.search_folder_keys: RawHashKey,
+        user.search_folders: RawSearchFolder,
+        user.search_path_keys: RawSearchPathKey,
+        user.search_paths: RawSearchPath,
+    );
+
+    /// Gets the versioned files which were added in a patch
+    ///
+    /// ### Arguments
+    /// * `patch` - The index of the patch, in the same order as [`patches`](Self::patches)
+    ///
+    /// ### Panicking
+    /// Panics if `patch` is out of bounds
+    pub fn versioned_files(&self, patch: usize) -> &[RawVersionedFile] {
+        self.non_user
+            .table(&self.patch_tables[patch].versioned_files)
+    }
+
+    /// Gets the hash lookup buckets for the versioned files of a patch
+    pub fn versioned_file_buckets(&self, patch: usize) -> &[RawBucket] {
+        self.non_user.table(&self.patch_tables[patch].buckets)
+    }
+
+    /// Gets the hash lookup keys for the versioned files of a patch
+    pub fn versioned_file_keys(&self, patch: usize) -> &[RawHashKey] {
+        self.non_user.table(&self.patch_tables[patch].keys)
+    }
+
+    /// Gets a package by hash
+    ///
+    /// ### Arguments
+    /// * `hash` - The hash of the package
+    ///
+    /// ### Returns
+    /// * `Some(_)` - The raw package
+    /// * `None` - There is no package with that hash
+    pub fn get_package(&self, hash: impl Hashable) -> Option<&RawPackage> {
+        let hash = hash.to_hash();
+        let keys = self.package_keys();
+        let index = keys.binary_search_by_key(&hash, |key| key.hash40()).ok()?;
+        self.packages().get(keys[index].index())
+    }
+
+    /// Gets a file's path by hash
+    ///
+    /// ### Arguments
+    /// * `hash` - The hash of the file
+    ///
+    /// ### Returns
+    /// * `Some(_)` - The raw path of the file
+    /// * `None` - There is no file with that hash
+    pub fn get_file(&self, hash: impl Hashable) -> Option<&RawPath> {
+        let hash = hash.to_hash();
+        let buckets = self.file_buckets();
+        if buckets.is_empty() {
+            return None;
+        }
+
+        let bucket = buckets[hash.0 as usize % buckets.len()];
+        let keys = self.file_keys().get(bucket.range())?;
+        let index = keys.binary_search_by_key(&hash, |key| key.hash40()).ok()?;
+        self.paths().get(keys[index].index())
+    }
+}
+
+impl BinRead for RawArchive {
+    type Args = ArchiveOptions;
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        options: &ReadOptions,
+        args: Self::Args,
+    ) -> BinResult<Self> {
+        let header = ArchiveHeader::read_options(reader, options, ())?;
+        let tables = CompressedTables::read(reader, options, &header)?;
+        let decompressor = args.decompressor.as_ref();
+        let non_user =
+            RawSection::decompress(&tables.non_user_header, &tables.non_user, decompressor)?;
+        let user = RawSection::decompress(&tables.user_header, &tables.user, decompressor)?;
+
+        let mut data = io::Cursor::new(non_user.bytes());
+        let _filesystem_size = u32::read_options(&mut data, options, ())?;
+        let packaged_header = PackagedFsHeader::read_options(&mut data, options, ())?;
+        let stream_header = StreamFsHeader::read_options(&mut data, options, ())?;
+
+        let mut cursor = SectionCursor::new(data.position(), &non_user);
+        let stream_folders = cursor.take::<RawStreamFolder>(stream_header.folder_count)?;
+        let stream_path_keys = cursor.take::<RawHashKey>(stream_header.path_count)?;
+        let stream_paths = cursor.take::<RawStreamPath>(stream_header.path_count)?;
+        let stream_links = cursor.take::<RawStreamLink>(stream_header.link_count)?;
+        let stream_metadatas = cursor.take::<RawStreamMetadata>(stream_header.metadata_count)?;
+
+        data.seek(SeekFrom::Start(cursor.position as u64))?;
+        let file_key_count = u32::read_options(&mut data, options, ())? as usize;
+        let file_bucket_count = u32::read_options(&mut data, options, ())? as usize;
+
+        let mut cursor = SectionCursor::new(data.position(), &non_user);
+        let file_buckets = cursor.take::<RawBucket>(file_bucket_count)?;
+        let file_keys = cursor.take::<RawHashKey>(file_key_count)?;
+        let paths = cursor.take::<RawPath>(packaged_header.path_count)?;
+        let links = cursor.take::<RawLink>(packaged_header.link_count)?;
+        let package_keys = cursor.take::<RawHashKey>(packaged_header.package_count)?;
+        let packages = cursor.take::<RawPackage>(packaged_header.package_count)?;
+        let groups = cursor.take::<RawGroup>(packaged_header.group_count)?;
+        let child_packages = cursor.take::<RawChildPackage>(packaged_header.child_package_count)?;
+        let infos = cursor.take::<RawInfo>(packaged_header.info_count)?;
+        let descriptors = cursor.take::<RawDescriptor>(packaged_header.descriptor_count)?;
+        let metadatas = cursor.take::<RawMetadata>(packaged_header.metadata_count)?;
+
+        data.seek(SeekFrom::Start(cursor.position as u64))?;
+        let version: (u8, u8, u16) = BinRead::read_options(&mut data, options, ())?;
+        let version = Version::new(version.2 as u64, version.1 as u64, version.0 as u64);
+        let patch_count = u32::read_options(&mut data, options, ())? as usize;
+
+        let mut cursor = SectionCursor::new(data.position(), &non_user);
+        let patches = cursor.take::<RawPatch>(patch_count)?;
+
+        let mut patch_tables = Vec::with_capacity(patch_count);
+        for patch in non_user.table::<RawPatch>(&patches) {
+            let versioned_files = cursor.take::<RawVersionedFile>(patch.file_count as usize)?;
+
+            data.seek(SeekFrom::Start(cursor.position as u64))?;
+            let bucket_count = u32::read_options(&mut data, options, ())? as usize;
+            let key_count = u32::read_options(&mut data, options, ())? as usize;
+
+            cursor = SectionCursor::new(data.position(), &non_user);
+            patch_tables.push(RawPatchTables {
+                versioned_files,
+                buckets: cursor.take::<RawBucket>(bucket_count)?,
+                keys: cursor.take::<RawHashKey>(key_count)?,
+            });
+        }
+
+        let mut data = io::Cursor::new(user.bytes());
+        let _filesystem_size = u64::read_options(&mut data, options, ())?;
+        let search_header = SearchFsHeader::read_options(&mut data, options, ())?;
+
+        let mut cursor = SectionCursor::new(data.position(), &user);
+        let search_folder_keys = cursor.take::<RawHashKey>(search_header.folder_count)?;
+        let search_folders = cursor.take::<RawSearchFolder>(search_header.folder_count)?;
+        let search_path_keys = cursor.take::<RawSearchPathKey>(search_header.path_link_count)?;
+        let search_paths = cursor.take::<RawSearchPath>(search_header.path_count)?;
+
+        Ok(Self {
+            file_section_offset: header.file_data_start as usize,
+            version,
+            non_user,
+            user,
+            stream_folders,
+            stream_path_keys,
+            stream_paths,
+            stream_links,
+            stream_metadatas,
+            file_buckets,
+            file_keys,
+            paths,
+            links,
+            package_keys,
+            packages,
+            groups,
+            child_packages,
+            infos,
+            descriptors,
+            metadatas,
+            patches,
+            patch_tables,
+            search_folder_keys,
+            search_folders,
+            search_path_keys,
+            search_paths,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::archive::tests::{build_archive, write_archive};
+
+    fn read_u64(data: &[u8], offset: usize) -> usize {
+        u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap()) as usize
+    }
+
+    fn read_u32(data: &[u8], offset: usize) -> usize {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize
+    }
+
+    #[test]
+    fn written_archives_are_viewed() {
+        let data = write_archive(build_archive());
+        let raw = RawArchive::read_args(&mut Cursor::new(data), ArchiveOptions::default()).unwrap();
+
+        assert_eq!(raw.version, Version::new(13, 0, 1));
+        assert_eq!(raw.packages().len(), 1);
+        assert_eq!(raw.paths().len(), 2);
+        assert_eq!(raw.stream_paths().len(), 1);
+        assert!(raw.patches().is_empty());
+
+        let package = raw.get_package("fighter/mario/c00").unwrap();
+        assert_eq!(
+            package.path_and_group_index.hash40(),
+            Hash40::new("fighter/mario/c00")
+        );
+        assert!(raw
+            .get_file("fighter/mario/model/body/c00/model.numdlb")
+            .is_some());
+        assert!(raw
+            .get_file("fighter/mario/model/body/c01/model.numdlb")
+            .is_none());
+        assert!(raw.get_package("fighter/luigi/c00").is_none());
+    }
+
+    #[test]
+    fn tables_past_the_end_of_their_section_are_rejected() {
+        let data = write_archive(build_archive());
+        let options = ArchiveOptions::default();
+
+        let (non_user, user) = (read_u64(&data, 0x20), read_u64(&data, 0x28));
+        let decompressed_size = read_u32(&data, non_user + 0x4);
+        let compressed_size = read_u32(&data, non_user + 0x8);
+        let compressed = &data[non_user + 0x10..non_user + 0x10 + compressed_size];
+        let mut tables = options
+            .decompressor
+            .decompress_with_size(compressed, decompressed_size)
+            .unwrap();
+
+        // The stream header ends the table headers, and starts with the stream folder count
+        assert_eq!(read_u32(&tables, 0x100), 1);
+        tables[0x100..0x104].copy_from_slice(&u32::MAX.to_le_bytes());
+        let compressed = options.compressor.compress(&tables).unwrap();
+
+        let mut patched = data[..non_user].to_vec();
+        for value in [0x10, tables.len(), compressed.len(), compressed.len()] {
+            patched.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        patched.extend_from_slice(&compressed);
+        let user_start = patched.len() as u64;
+        patched.extend_from_slice(&data[user..]);
+        patched[0x28..0x30].copy_from_slice(&user_start.to_le_bytes());
+
+        assert!(RawArchive::read_args(&mut Cursor::new(patched), options).is_err());
+    }
+}
@@ -0,0 +1,461 @@
+//! The table which maps every locale of the archive to its region
+//!
+//! The game looks up the entry for a locale by its position in the table, so the
+//! entries are always kept in [`Locale`] order. The locale and region counts in the
+//! packaged filesystem header are written from this table, which keeps them consistent
+//! when locales are removed, such as for builds without the Chinese locales.
+//! The region count is kept as it was read, and every locale has to belong to one of
+//! the regions it counts.
+
+use binrw::{BinRead, BinWrite};
+use hash40::Hash40;
+use thiserror::Error;
+
+use crate::{
+    engines::{HashKey, Locale, Region},
+    Hashable,
+};
+
+/// The number of locales that the game knows about
+pub const LOCALE_COUNT: usize = 14;
+
+/// The number of regions that the game knows about
+pub const REGION_COUNT: usize = 5;
+
+/// An error which prevented the locale/region table from being edited
+#[derive(Debug, Error)]
+pub enum LocaleRegionError {
+    #[error("The table already has an entry for every locale")]
+    TableFull,
+
+    #[error("The table has no entry for {0:?}")]
+    MissingLocale(Locale),
+
+    #[error("{0:?} is not the last locale in the table, so removing it would shift every locale after it")]
+    NotLastLocale(Locale),
+
+    #[error("Locales can't be mapped to the invalid region")]
+    InvalidRegion,
+
+    #[error("{region:?} is not one of the table's {region_count} regions")]
+    RegionOutOfRange { region: Region, region_count: usize },
+}
+
+/// The region of a single locale
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LocaleRegionEntry {
+    /// The hash that is stored alongside the locale
+    pub hash: Hash40,
+
+    /// The index that is packed together with [`hash`](Self::hash)
+    pub index: usize,
+
+    /// The region that the locale belongs to
+    pub region: Region,
+}
+
+impl LocaleRegionEntry {
+    pub fn new(hash: impl Hashable, index: usize, region: Region) -> Self {
+        Self {
+            hash: hash.to_hash(),
+            index,
+            region,
+        }
+    }
+}
+
+impl BinRead for LocaleRegionEntry {
+    type Args = ();
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        options: &binrw::ReadOptions,
+        _args: Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let key = HashKey::read_options(reader, options, ())?;
+        let region = u32::read_options(reader, options, ())?;
+
+        Ok(Self {
+            hash: key.hash(),
+            index: key.index(),
+            region: Region::from(region as i32),
+        })
+    }
+}
+
+impl BinWrite for LocaleRegionEntry {
+    type Args = ();
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        options: &binrw::WriteOptions,
+        _args: Self::Args,
+    ) -> binrw::BinResult<()> {
+        HashKey::new(self.hash, self.index).write_options(writer, options, ())?;
+        (self.region as i32 as u32).write_options(writer, options, ())
+    }
+}
+
+/// The region of every locale in the archive, in [`Locale`] order
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "LocaleRegionTableRepr"))]
+pub struct LocaleRegionTable {
+    entries: Vec<LocaleRegionEntry>,
+    region_count: usize,
+}
+
+/// The unchecked form of a [`LocaleRegionTable`], which is checked after deserializing
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct LocaleRegionTableRepr {
+    entries: Vec<LocaleRegionEntry>,
+    region_count: usize,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<LocaleRegionTableRepr> for LocaleRegionTable {
+    type Error = LocaleRegionError;
+
+    fn try_from(repr: LocaleRegionTableRepr) -> Result<Self, Self::Error> {
+        Self::from_entries(repr.entries, repr.region_count)
+    }
+}
+
+impl LocaleRegionTable {
+    /// Creates a table with no locales, for every region that the game knows about
+    pub fn new() -> Self {
+        Self {
+            entries: vec![],
+            region_count: REGION_COUNT,
+        }
+    }
+
+    /// Creates the table used by retail archives, with every locale in its usual region
+    ///
+    /// ### Notes
+    /// The hash of each entry is the hash of the locale's short name, such as `us_en`,
+    /// and its index is the locale's position in the table.
+    pub fn retail() -> Self {
+        let entries = (0..LOCALE_COUNT)
+            .map(|index| {
+                let locale = Locale::from(index as i32);
+                LocaleRegionEntry::new(locale.as_str(), index, Region::from(locale))
+            })
+            .collect();
+
+        Self {
+            entries,
+            region_count: REGION_COUNT,
+        }
+    }
+
+    /// Creates a table from its entries and region count, checking that they agree
+    ///
+    /// ### Returns
+    /// * `Ok(Self)` - Every entry belongs to one of the regions
+    /// * `Err(_)` - There are too many entries, or an entry's region is invalid or past the region count
+    pub fn from_entries(
+        entries: Vec<LocaleRegionEntry>,
+        region_count: usize,
+    ) -> Result<Self, LocaleRegionError> {
+        if entries.len() > LOCALE_COUNT {
+            return Err(LocaleRegionError::TableFull);
+        }
+
+        let table = Self {
+            entries,
+            region_count,
+        };
+
+        for entry in table.entries.iter() {
+            table.check_region(entry.region)?;
+        }
+
+        Ok(table)
+    }
+
+    /// Checks that a region can be used by the locales in this table
+    fn check_region(&self, region: Region) -> Result<(), LocaleRegionError> {
+        if region == Region::Invalid {
+            return Err(LocaleRegionError::InvalidRegion);
+        }
+
+        if region as usize >= self.region_count {
+            return Err(LocaleRegionError::RegionOutOfRange {
+                region,
+                region_count: self.region_count,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The number of locales in the table, which is written as the header's locale count
+    pub fn locale_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The number of regions in the table, which is written as the header's region count
+    ///
+    /// This is the count that the table was read or created with, and only changes when
+    /// the last region is removed by [`remove_region`](Self::remove_region).
+    pub fn region_count(&self) -> usize {
+        self.region_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, locale: Locale) -> Option<&LocaleRegionEntry> {
+        usize::try_from(locale as i32)
+            .ok()
+            .and_then(|index| self.entries.get(index))
+    }
+
+    pub fn get_mut(&mut self, locale: Locale) -> Option<&mut LocaleRegionEntry> {
+        usize::try_from(locale as i32)
+            .ok()
+            .and_then(|index| self.entries.get_mut(index))
+    }
+
+    /// Gets the region of a locale, if the table has an entry for it
+    pub fn region(&self, locale: Locale) -> Option<Region> {
+        self.get(locale).map(|entry| entry.region)
+    }
+
+    /// Every locale in the table which belongs to a region
+    pub fn locales(&self, region: Region) -> impl Iterator<Item = Locale> + '_ {
+        self.iter()
+            .filter(move |(_, entry)| entry.region == region)
+            .map(|(locale, _)| locale)
+    }
+
+    /// Every entry in the table, along with the locale it is for
+    pub fn iter(&self) -> impl Iterator<Item = (Locale, &LocaleRegionEntry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| (Locale::from(index as i32), entry))
+    }
+
+    /// Adds an entry for the next locale
+    ///
+    /// ### Returns
+    /// * `Ok(Locale)` - The locale that the entry was added for
+    /// * `Err(_)` - The table already has every locale, or the entry's region is invalid
+    ///   or past the region count
+    pub fn push(&mut self, entry: LocaleRegionEntry) -> Result<Locale, LocaleRegionError> {
+        if self.entries.len() >= LOCALE_COUNT {
+            return Err(LocaleRegionError::TableFull);
+        }
+
+        self.check_region(entry.region)?;
+        self.entries.push(entry);
+        Ok(Locale::from(self.entries.len() as i32 - 1))
+    }
+
+    /// Moves a locale to a different region
+    pub fn set_region(&mut self, locale: Locale, region: Region) -> Result<(), LocaleRegionError> {
+        self.check_region(region)?;
+
+        self.get_mut(locale)
+            .map(|entry| entry.region = region)
+            .ok_or(LocaleRegionError::MissingLocale(locale))
+    }
+
+    /// Removes the entry for a locale
+    ///
+    /// ### Returns
+    /// * `Ok(LocaleRegionEntry)` - The entry which was removed
+    /// * `Err(_)` - The table has no entry for the locale, or it is not the last locale in the table
+    ///
+    /// ### Notes
+    /// Only the last locale can be removed, since every locale after it would otherwise
+    /// be looked up as the locale before it.
+    pub fn remove_locale(
+        &mut self,
+        locale: Locale,
+    ) -> Result<LocaleRegionEntry, LocaleRegionError> {
+        if self.get(locale).is_none() {
+            return Err(LocaleRegionError::MissingLocale(locale));
+        }
+
+        if locale as i32 + 1 != self.entries.len() as i32 {
+            return Err(LocaleRegionError::NotLastLocale(locale));
+        }
+
+        Ok(self.entries.pop().unwrap())
+    }
+
+    /// Removes every locale which belongs to a region
+    ///
+    /// ### Returns
+    /// * `Ok(Vec<(Locale, LocaleRegionEntry)>)` - The entries which were removed, in locale order
+    /// * `Err(_)` - One of the region's locales is followed by a locale from another region.
+    ///   The table is left unchanged.
+    ///
+    /// ### Notes
+    /// When the region is the last one in the table, the region count shrinks along with it.
+    pub fn remove_region(
+        &mut self,
+        region: Region,
+    ) -> Result<Vec<(Locale, LocaleRegionEntry)>, LocaleRegionError> {
+        let keep = self
+            .entries
+            .iter()
+            .rposition(|entry| entry.region != region)
+            .map_or(0, |index| index + 1);
+
+        if let Some((locale, _)) = self
+            .iter()
+            .take(keep)
+            .find(|(_, entry)| entry.region == region)
+        {
+            return Err(LocaleRegionError::NotLastLocale(locale));
+        }
+
+        if region != Region::Invalid && region as usize + 1 == self.region_count {
+            self.region_count -= 1;
+        }
+
+        Ok(self
+            .entries
+            .drain(keep..)
+            .enumerate()
+            .map(|(index, entry)| (Locale::from((keep + index) as i32), entry))
+            .collect())
+    }
+}
+
+impl Default for LocaleRegionTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BinRead for LocaleRegionTable {
+    /// The locale count and region count from the header
+    type Args = (usize, usize);
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        options: &binrw::ReadOptions,
+        (locale_count, region_count): Self::Args,
+    ) -> binrw::BinResult<Self> {
+        let pos = reader.stream_position()?;
+        let entries = (0..locale_count)
+            .map(|_| LocaleRegionEntry::read_options(reader, options, ()))
+            .collect::<binrw::BinResult<_>>()?;
+
+        Self::from_entries(entries, region_count).map_err(|err| binrw::Error::Custom {
+            pos,
+            err: Box::new(err),
+        })
+    }
+}
+
+impl BinWrite for LocaleRegionTable {
+    type Args = ();
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        options: &binrw::WriteOptions,
+        _args: Self::Args,
+    ) -> binrw::BinResult<()> {
+        for entry in self.entries.iter() {
+            entry.write_options(writer, options, ())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pushed_locales_are_checked() {
+        let mut table = LocaleRegionTable::from_entries(vec![], 2).unwrap();
+
+        let entry = LocaleRegionEntry::new("jp_ja", 0, Region::Japan);
+        assert_eq!(table.push(entry).unwrap(), Locale::Japanese);
+
+        let entry = LocaleRegionEntry::new("us_en", 1, Region::Invalid);
+        assert!(matches!(
+            table.push(entry),
+            Err(LocaleRegionError::InvalidRegion)
+        ));
+
+        let entry = LocaleRegionEntry::new("us_en", 1, Region::Europe);
+        assert!(matches!(
+            table.push(entry),
+            Err(LocaleRegionError::RegionOutOfRange {
+                region: Region::Europe,
+                region_count: 2
+            })
+        ));
+
+        let entry = LocaleRegionEntry::new("us_en", 1, Region::NorthAmerica);
+        assert_eq!(table.push(entry).unwrap(), Locale::UsEnglish);
+        assert_eq!(table.locale_count(), 2);
+        assert_eq!(table.region(Locale::UsEnglish), Some(Region::NorthAmerica));
+
+        let mut retail = LocaleRegionTable::retail();
+        let entry = LocaleRegionEntry::new("xx_xx", LOCALE_COUNT, Region::Japan);
+        assert!(matches!(
+            retail.push(entry),
+            Err(LocaleRegionError::TableFull)
+        ));
+        assert_eq!(retail.locale_count(), LOCALE_COUNT);
+    }
+
+    #[test]
+    fn only_the_last_locale_is_removed() {
+        let mut table = LocaleRegionTable::retail();
+
+        assert!(matches!(
+            table.remove_locale(Locale::Chinese),
+            Err(LocaleRegionError::NotLastLocale(Locale::Chinese))
+        ));
+
+        let removed = table.remove_locale(Locale::Taiwanese).unwrap();
+        assert_eq!(removed.index, Locale::Taiwanese as usize);
+        assert_eq!(table.locale_count(), LOCALE_COUNT - 1);
+        assert!(matches!(
+            table.remove_locale(Locale::Taiwanese),
+            Err(LocaleRegionError::MissingLocale(Locale::Taiwanese))
+        ));
+
+        assert!(table.remove_locale(Locale::Chinese).is_ok());
+        assert_eq!(table.region_count(), REGION_COUNT);
+    }
+
+    #[test]
+    fn regions_are_removed_from_the_end() {
+        let mut table = LocaleRegionTable::retail();
+
+        assert!(matches!(
+            table.remove_region(Region::Europe),
+            Err(LocaleRegionError::NotLastLocale(Locale::EuEnglish))
+        ));
+        assert_eq!(table, LocaleRegionTable::retail());
+
+        let removed = table.remove_region(Region::China).unwrap();
+        let locales: Vec<_> = removed.iter().map(|(locale, _)| *locale).collect();
+        assert_eq!(locales, [Locale::Chinese, Locale::Taiwanese]);
+        assert_eq!(table.locale_count(), LOCALE_COUNT - 2);
+        assert_eq!(table.region_count(), REGION_COUNT - 1);
+
+        let entry = LocaleRegionEntry::new("zh_cn", Locale::Chinese as usize, Region::China);
+        assert!(matches!(
+            table.push(entry),
+            Err(LocaleRegionError::RegionOutOfRange { .. })
+        ));
+    }
+}
@@ -0,0 +1,340 @@
+//! Serde support for the engines and the archive
+//!
+//! Every table is serialized in order, and references between tables are serialized as
+//! the index of the entry they point to, so the representation is stable across formats
+//! such as JSON, RON and MessagePack. Hashes are written as their labels when they are in
+//! the [`Hash40`] label map, and as hex strings (`0x0123456789`) when they aren't.
+//!
+//! Only resolved engines can be serialized. Deserializing produces resolved engines with
+//! their lookups rebuilt, so they can be used straight away. Every deserialized reference
+//! is checked against the length of the table it points into.
+
+use std::num::NonZeroUsize;
+
+use hash40::Hash40;
+use semver::Version;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::engines::{
+    packaged::{bucket_map::BucketMap, types::*, PackagedEngine, PackagedReferences},
+    search::{
+        types::{SearchFolder, SearchPath},
+        SearchEngine,
+    },
+    stream::{
+        types::{StreamFolder, StreamLink, StreamMetadata, StreamPath},
+        StreamEngine,
+    },
+    table::{LinkedReference, Table, TableIndex},
+};
+
+/// Checks that every index points into a table with `len` entries
+fn check_bounds<T, E: serde::de::Error>(
+    table: &str,
+    indices: impl IntoIterator<Item = TableIndex<T>>,
+    len: usize,
+) -> Result<(), E> {
+    match indices.into_iter().find(|index| index.index() >= len) {
+        Some(index) => Err(E::custom(format_args!(
+            "Index {} is out of bounds of the {} table, which has {} entries",
+            index.index(),
+            table,
+            len
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[derive(Serialize)]
+struct PackagedEngineRef<'a> {
+    version: &'a Version,
+    bucket_count: usize,
+    packages: &'a Table<Package>,
+    child_packages: &'a Table<ChildPackage>,
+    groups: &'a Table<Group>,
+    paths: &'a Table<Path>,
+    links: &'a Table<Link>,
+    infos: &'a Table<Info>,
+    descriptors: &'a Table<Descriptor>,
+    metadatas: &'a Table<Metadata>,
+    patches: &'a Table<Patch>,
+    versioned_files: &'a Table<VersionedFile>,
+}
+
+#[derive(Deserialize)]
+struct PackagedEngineOwned {
+    version: Version,
+    bucket_count: usize,
+    packages: Table<Package>,
+    child_packages: Table<ChildPackage>,
+    groups: Table<Group>,
+    paths: Table<Path>,
+    links: Table<Link>,
+    infos: Table<Info>,
+    descriptors: Table<Descriptor>,
+    metadatas: Table<Metadata>,
+    patches: Table<Patch>,
+    versioned_files: Table<VersionedFile>,
+}
+
+impl Serialize for PackagedEngine {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        PackagedEngineRef {
+            version: &self.version,
+            bucket_count: self.file_lookup.bucket_count(),
+            packages: &self.packages,
+            child_packages: &self.child_packages,
+            groups: &self.groups,
+            paths: &self.paths,
+            links: &self.links,
+            infos: &self.infos,
+            descriptors: &self.descriptors,
+            metadatas: &self.metadatas,
+            patches: &self.patches,
+            versioned_files: &self.versioned_files,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PackagedEngine {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let engine = PackagedEngineOwned::deserialize(deserializer)?;
+
+        let mut refs = PackagedReferences::default();
+        for package in engine.packages.iter() {
+            package.references(&mut refs);
+        }
+        for child in engine.child_packages.iter() {
+            child.references(&mut refs);
+        }
+        for group in engine.groups.iter() {
+            group.references(&mut refs);
+        }
+        for path in engine.paths.iter() {
+            path.references(&mut refs);
+        }
+        for link in engine.links.iter() {
+            link.references(&mut refs);
+        }
+        for info in engine.infos.iter() {
+            info.references(&mut refs);
+        }
+        for descriptor in engine.descriptors.iter() {
+            descriptor.references(&mut refs);
+        }
+        for patch in engine.patches.iter() {
+            patch.references(&mut refs);
+        }
+        for file in engine.versioned_files.iter() {
+            file.references(&mut refs);
+        }
+
+        check_bounds("package", refs.packages, engine.packages.len())?;
+        check_bounds(
+            "child package",
+            refs.child_packages,
+            engine.child_packages.len(),
+        )?;
+        check_bounds("group", refs.groups, engine.groups.len())?;
+        check_bounds("path", refs.paths, engine.paths.len())?;
+        check_bounds("link", refs.links, engine.links.len())?;
+        check_bounds("info", refs.infos, engine.infos.len())?;
+        check_bounds("descriptor", refs.descriptors, engine.descriptors.len())?;
+        check_bounds("metadata", refs.metadatas, engine.metadatas.len())?;
+        check_bounds("patch", refs.patches, engine.patches.len())?;
+        check_bounds(
+            "versioned file",
+            refs.versioned_files,
+            engine.versioned_files.len(),
+        )?;
+
+        let bucket_count = NonZeroUsize::new(engine.bucket_count)
+            .ok_or_else(|| D::Error::custom("Bucket count should be non-zero"))?;
+
+        let package_lookup = engine
+            .packages
+            .iter_indexed()
+            .map(|(index, package)| (package.full_path, index))
+            .collect();
+
+        let mut file_lookup = BucketMap::new(bucket_count);
+        for (index, path) in engine.paths.iter_indexed() {
+            file_lookup.insert(path.full_path, index);
+        }
+
+        Ok(Self {
+            version: engine.version,
+            package_lookup,
+            file_lookup,
+
+            packages: engine.packages,
+            child_packages: engine.child_packages,
+            groups: engine.groups,
+
+            paths: engine.paths,
+            links: engine.links,
+            infos: engine.infos,
+            descriptors: engine.descriptors,
+            metadatas: engine.metadatas,
+
+            patches: engine.patches,
+            versioned_files: engine.versioned_files,
+
+            resolve_offsets: None,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct SearchEngineRef<'a> {
+    folders: &'a Table<SearchFolder>,
+    paths: &'a Table<SearchPath>,
+}
+
+#[derive(Deserialize)]
+struct SearchEngineOwned {
+    folders: Table<SearchFolder>,
+    paths: Table<SearchPath>,
+}
+
+impl Serialize for SearchEngine {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SearchEngineRef {
+            folders: &self.folders,
+            paths: &self.paths,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SearchEngine {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let engine = SearchEngineOwned::deserialize(deserializer)?;
+
+        for folder in engine.folders.iter() {
+            check_bounds(
+                "search path",
+                folder.children.resolved(),
+                engine.paths.len(),
+            )?;
+        }
+
+        for path in engine.paths.iter() {
+            let folder = path.is_folder().then(|| path.folder());
+            check_bounds("search folder", folder, engine.folders.len())?;
+            check_bounds("search path", path.next(), engine.paths.len())?;
+        }
+
+        let folder_lookup = engine
+            .folders
+            .iter_indexed()
+            .map(|(index, folder)| (folder.full_path, index))
+            .collect();
+
+        let path_lookup = engine
+            .paths
+            .iter_indexed()
+            .filter(|(_, path)| path.full_path != Hash40::new(""))
+            .map(|(index, path)| (path.full_path, index))
+            .collect();
+
+        Ok(Self {
+            folder_lookup,
+            path_lookup,
+            folders: engine.folders,
+            paths: engine.paths,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct StreamEngineRef<'a> {
+    folders: &'a Table<StreamFolder>,
+    paths: &'a Table<StreamPath>,
+    links: &'a Table<StreamLink>,
+    metadatas: &'a Table<StreamMetadata>,
+}
+
+#[derive(Deserialize)]
+struct StreamEngineOwned {
+    folders: Table<StreamFolder>,
+    paths: Table<StreamPath>,
+    links: Table<StreamLink>,
+    metadatas: Table<StreamMetadata>,
+}
+
+impl Serialize for StreamEngine {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StreamEngineRef {
+            folders: &self.folders,
+            paths: &self.paths,
+            links: &self.links,
+            metadatas: &self.metadatas,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for StreamEngine {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let engine = StreamEngineOwned::deserialize(deserializer)?;
+
+        for folder in engine.folders.iter() {
+            check_bounds("stream path", folder.paths.resolved(), engine.paths.len())?;
+        }
+
+        for path in engine.paths.iter() {
+            check_bounds("stream link", path.links.resolved(), engine.links.len())?;
+        }
+
+        for link in engine.links.iter() {
+            check_bounds("stream metadata", [link.metadata()], engine.metadatas.len())?;
+        }
+
+        let path_lookup = engine
+            .paths
+            .iter_indexed()
+            .map(|(index, path)| (path.full_path, index))
+            .collect();
+
+        Ok(Self {
+            path_lookup,
+            folders: engine.folders,
+            paths: engine.paths,
+            links: engine.links,
+            metadatas: engine.metadatas,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "compression")]
+    use crate::archive::{tests::write_tables, Archive};
+    use crate::{
+        archive::tests::build_archive,
+        engines::{packaged::PackagedEngine, stream::StreamEngine},
+    };
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn round_trip_writes_the_same_tables() {
+        let archive = build_archive();
+        let json = serde_json::to_string(&archive).unwrap();
+        let deserialized: Archive = serde_json::from_str(&json).unwrap();
+        assert_eq!(write_tables(archive), write_tables(deserialized));
+    }
+
+    #[test]
+    fn out_of_bounds_references_are_rejected() {
+        let archive = build_archive();
+        let mut json = serde_json::to_value(&archive.stream_fs).unwrap();
+        json["links"][0]["metadata"] = 100.into();
+        assert!(serde_json::from_value::<StreamEngine>(json).is_err());
+
+        let mut json = serde_json::to_value(&archive.packaged_fs).unwrap();
+        json["packages"][0]["infos"][0] = 100.into();
+        assert!(serde_json::from_value::<PackagedEngine>(json).is_err());
+    }
+}
@@ -0,0 +1,289 @@
+use hash40::Hash40;
+
+use crate::{
+    archive::Archive,
+    engines::{
+        lookup::HashLookup,
+        packaged::types::{DescriptorLoadArguments, Info, Metadata, Package},
+        search::SearchEngine,
+        table::TableIndex,
+    },
+    Hashable,
+};
+
+/// The number of files and the amount of data in some part of the archive
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct SizeTotals {
+    /// The number of files (or localized/regional variants of files) with data
+    pub file_count: usize,
+
+    /// The size of the data in the archive
+    pub compressed_size: usize,
+
+    /// The size of the data once decompressed
+    pub decompressed_size: usize,
+}
+
+impl SizeTotals {
+    /// Adds a file to the totals
+    ///
+    /// The sizes saturate at `usize::MAX`, which is what [`Metadata::new`] uses for sizes
+    /// that haven't been set yet, so a single unset size makes the total `usize::MAX`
+    fn add(&mut self, compressed_size: usize, decompressed_size: usize) {
+        self.file_count += 1;
+        self.compressed_size = self.compressed_size.saturating_add(compressed_size);
+        self.decompressed_size = self.decompressed_size.saturating_add(decompressed_size);
+    }
+
+    fn add_metadata(&mut self, metadata: &Metadata) {
+        self.add(metadata.compressed_size, metadata.decompressed_size);
+    }
+}
+
+/// Size and composition statistics for an archive
+///
+/// Created by [`Archive::stats`]. Sizes in the packaged filesystem come from the
+/// [`Metadata`] of each file, and every piece of file data is only counted once.
+#[derive(Default)]
+pub struct ArchiveStats {
+    /// The totals for all of the file data in the packaged filesystem
+    pub packaged: SizeTotals,
+
+    /// The totals for all of the file data in the stream filesystem.
+    ///
+    /// Stream files are never compressed, so both sizes are the same.
+    pub stream: SizeTotals,
+
+    /// The combined size of every group in the packaged filesystem
+    pub group_totals: SizeTotals,
+
+    /// The file data owned by each package, by the package's full path
+    pub by_package: HashLookup<SizeTotals>,
+
+    /// The file data of each extension, by the extension's hash
+    pub by_extension: HashLookup<SizeTotals>,
+
+    /// The file data under each top level folder, by the folder's full path
+    pub by_top_level_folder: HashLookup<SizeTotals>,
+
+    /// The file data owned by the packages with each lifetime, by the lifetime's hash
+    pub by_lifetime: HashLookup<SizeTotals>,
+
+    /// The number of files in the packaged filesystem
+    pub file_count: usize,
+
+    /// The number of files which are marked as shared
+    pub shared_file_count: usize,
+
+    /// The number of files which have localized variants
+    pub localized_file_count: usize,
+
+    /// The number of files which have regional variants
+    pub regional_file_count: usize,
+
+    /// The number of files which are graphics archives
+    pub graphics_archive_file_count: usize,
+
+    /// The data which would have been needed if every shared file had its own copy
+    pub dedup_savings: SizeTotals,
+}
+
+fn add_to(lookup: &mut HashLookup<SizeTotals>, hash: Hash40, metadata: &Metadata) {
+    match lookup.get_mut(&hash) {
+        Some(totals) => totals.add_metadata(metadata),
+        None => {
+            let mut totals = SizeTotals::default();
+            totals.add_metadata(metadata);
+            lookup.insert(hash, totals);
+        }
+    }
+}
+
+/// Finds the top level folder of a path by walking up its parents in the search filesystem
+///
+/// If a parent cannot be found, the last parent that was found is used instead
+fn top_level_folder(search: &SearchEngine, parent: Hash40) -> Hash40 {
+    let root = "/".to_hash();
+    let empty = "".to_hash();
+    let mut current = parent;
+
+    while current != root {
+        match search.get_folder(current) {
+            Some(folder)
+                if folder.parent != root && folder.parent != empty && folder.parent != current =>
+            {
+                current = folder.parent;
+            }
+            _ => break,
+        }
+    }
+
+    current
+}
+
+struct StatsBuilder<'a> {
+    archive: &'a Archive,
+    stats: ArchiveStats,
+    seen_metadatas: Vec<bool>,
+}
+
+impl<'a> StatsBuilder<'a> {
+    fn add_info(&mut self, info: TableIndex<Info>, package: Option<&Package>) {
+        let engine = &self.archive.packaged_fs;
+        let info = &engine.infos[info];
+        let path = &engine.paths[info.path()];
+
+        for (position, index) in info.descriptors.iter().enumerate() {
+            let descriptor = &engine.descriptors[index];
+
+            if let DescriptorLoadArguments::Owned { .. } = &descriptor.load_args {
+                if !descriptor.has_metadata() {
+                    continue;
+                }
+
+                let metadata = descriptor.metadata();
+                if std::mem::replace(&mut self.seen_metadatas[metadata.index()], true) {
+                    continue;
+                }
+
+                let metadata = &engine.metadatas[metadata];
+                let stats = &mut self.stats;
+                stats.packaged.add_metadata(metadata);
+                add_to(&mut stats.by_extension, path.extension, metadata);
+                add_to(
+                    &mut stats.by_top_level_folder,
+                    top_level_folder(&self.archive.search_fs, path.parent),
+                    metadata,
+                );
+
+                if let Some(package) = package {
+                    add_to(&mut stats.by_package, package.full_path, metadata);
+                    add_to(&mut stats.by_lifetime, package.lifetime, metadata);
+                }
+            } else if let Some((_, metadata)) = engine.descriptor_data(index, position) {
+                self.stats
+                    .dedup_savings
+                    .add_metadata(&engine.metadatas[metadata]);
+            }
+        }
+    }
+}
+
+impl Archive {
+    /// Collects size and composition statistics for the archive
+    ///
+    /// ### Panicking
+    /// Panics if the archive has not been [resolved](Self::resolve)
+    ///
+    /// ### Notes
+    /// * File data is attributed to the package or group whose info owns it. Data owned
+    ///   by an info group is counted in every total except `by_package` and `by_lifetime`.
+    /// * Descriptors which share data owned by another info are counted in `dedup_savings`
+    ///   instead of the other totals.
+    /// * Top level folders are found through the search filesystem. Files whose parents are
+    ///   missing from it are grouped under the highest parent that could be found.
+    pub fn stats(&self) -> ArchiveStats {
+        let engine = &self.packaged_fs;
+
+        let mut builder = StatsBuilder {
+            archive: self,
+            stats: ArchiveStats::default(),
+            seen_metadatas: vec![false; engine.metadatas.len()],
+        };
+
+        for package in engine.packages.iter() {
+            for info in package.infos.iter() {
+                builder.add_info(info, Some(package));
+            }
+        }
+
+        for group in engine.groups.iter() {
+            builder
+                .stats
+                .group_totals
+                .add(group.compressed_size, group.decompressed_size);
+
+            if group.is_info_group() {
+                for info in group.infos().iter() {
+                    builder.add_info(info, None);
+                }
+            }
+        }
+
+        let mut stats = builder.stats;
+
+        for path in engine.paths.iter() {
+            let info = &engine.infos[engine.links[path.link()].info()];
+            stats.file_count += 1;
+            stats.shared_file_count += info.is_shared as usize;
+            stats.localized_file_count += info.is_localized as usize;
+            stats.regional_file_count += info.is_regional as usize;
+            stats.graphics_archive_file_count += info.is_graphics_archive as usize;
+        }
+
+        for metadata in self.stream_fs.metadatas.iter() {
+            stats.stream.add(metadata.size, metadata.size);
+        }
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{archive::tests::build_archive, engines::VariantKind};
+
+    #[test]
+    fn data_is_bucketed_once() {
+        let mut archive = build_archive();
+        archive
+            .packaged_fs
+            .add_package("fighter/luigi/c00", "fighter", VariantKind::None);
+        archive.add_file(
+            "fighter/luigi/model/body/c00/model.numdlb",
+            "fighter/luigi/c00",
+        );
+        archive.add_file("ui/message/msg_name.msbt", "fighter/luigi/c00");
+        archive.packaged_fs.add_shared_file(
+            "fighter/mario/model/body/c00/model.numdlb",
+            "fighter/luigi/c00",
+        );
+        for metadata in archive.packaged_fs.metadatas.iter_mut() {
+            metadata.compressed_size = 0x10;
+            metadata.decompressed_size = 0x20;
+        }
+
+        let stats = archive.stats();
+        assert_eq!(stats.file_count, 4);
+        assert_eq!(stats.packaged.file_count, 4);
+        assert_eq!(stats.packaged.compressed_size, 0x40);
+        assert_eq!(stats.packaged.decompressed_size, 0x80);
+        assert_eq!(stats.dedup_savings.file_count, 1);
+        assert_eq!(stats.dedup_savings.compressed_size, 0x10);
+
+        let fighter = stats.by_top_level_folder.get(&"fighter".to_hash()).unwrap();
+        assert_eq!(fighter.file_count, 3);
+        let ui = stats.by_top_level_folder.get(&"ui".to_hash()).unwrap();
+        assert_eq!(ui.file_count, 1);
+        assert_eq!(stats.by_top_level_folder.len(), 2);
+
+        let luigi = stats
+            .by_package
+            .get(&"fighter/luigi/c00".to_hash())
+            .unwrap();
+        assert_eq!(luigi.file_count, 2);
+        let models = stats.by_extension.get(&"numdlb".to_hash()).unwrap();
+        assert_eq!(models.file_count, 2);
+
+        assert_eq!(stats.stream.file_count, 1);
+    }
+
+    #[test]
+    fn unset_sizes_saturate() {
+        let stats = build_archive().stats();
+        assert_eq!(stats.packaged.file_count, 2);
+        assert_eq!(stats.packaged.compressed_size, usize::MAX);
+        assert_eq!(stats.packaged.decompressed_size, usize::MAX);
+    }
+}
@@ -0,0 +1,419 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use semver::Version;
+
+use crate::{
+    archive::Archive,
+    engines::{
+        lookup::HashLookup,
+        packaged::types::{Info, Patch},
+        table::TableIndex,
+        Locale,
+    },
+    Hashable,
+};
+
+/// Where the data for a file is in the archive
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FileLocation {
+    /// The absolute offset of the data in the archive
+    pub offset: usize,
+
+    /// The size of the data in the archive
+    pub compressed_size: usize,
+
+    /// The size of the data once decompressed
+    pub decompressed_size: usize,
+
+    /// Whether the data is compressed
+    pub is_compressed: bool,
+}
+
+/// A read-only view of an archive as it was at an earlier game version
+///
+/// Created by [`Archive::at_version`]. Files which were versioned by the version's patch
+/// resolve to the infos in the patch's version group, and every other file resolves to
+/// its current info.
+pub struct ArchiveAtVersion<'a> {
+    archive: &'a Archive,
+    version: Version,
+    patch: Option<TableIndex<Patch>>,
+    infos: HashLookup<TableIndex<Info>>,
+}
+
+impl<'a> ArchiveAtVersion<'a> {
+    /// The archive that is being viewed
+    pub fn archive(&self) -> &'a Archive {
+        self.archive
+    }
+
+    /// The game version of the view
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    /// The patch that the view resolves versioned files with.
+    ///
+    /// This is `None` when the view is of the archive's own version.
+    pub fn patch(&self) -> Option<TableIndex<Patch>> {
+        self.patch
+    }
+
+    /// Checks if a file was versioned by the view's patch
+    pub fn is_versioned(&self, hash: impl Hashable) -> bool {
+        self.infos.contains_key(&hash.to_hash())
+    }
+
+    /// Gets the info that describes a file at the view's version
+    ///
+    /// ### Returns
+    /// The info from the patch's version group if the file was versioned by it,
+    /// otherwise the file's current info. `None` if the file does not exist.
+    pub fn get_info(&self, hash: impl Hashable) -> Option<TableIndex<Info>> {
+        let hash = hash.to_hash();
+        if let Some(info) = self.infos.get(&hash) {
+            return Some(*info);
+        }
+
+        let engine = &self.archive.packaged_fs;
+        let path = engine.get_file(hash)?;
+        Some(engine.links[path.link()].info())
+    }
+
+    /// Finds where the data for a file is at the view's version
+    ///
+    /// ### Arguments
+    /// * `hash` - The hash of the file
+    /// * `locale` - The locale to pick localized and regional data with
+    ///
+    /// ### Returns
+    /// The location of the data, or `None` if the file does not exist or has no data
+    pub fn file_location(&self, hash: impl Hashable, locale: Locale) -> Option<FileLocation> {
+        let engine = &self.archive.packaged_fs;
+        let (descriptor, metadata) = engine.find_file_data(self.get_info(hash)?, locale)?;
+        let group = &engine.groups[engine.descriptors[descriptor].group()];
+        let metadata = &engine.metadatas[metadata];
+
+        Some(FileLocation {
+            offset: self.archive.file_section_offset + group.archive_offset + metadata.group_offset,
+            compressed_size: metadata.compressed_size,
+            decompressed_size: metadata.decompressed_size,
+            is_compressed: metadata.is_compressed,
+        })
+    }
+
+    /// Reads the data for a file at the view's version
+    ///
+    /// ### Arguments
+    /// * `reader` - The reader for the archive file that the archive was read from
+    /// * `hash` - The hash of the file
+    /// * `locale` - The locale to pick localized and regional data with
+    ///
+    /// ### Returns
+    /// The decompressed data, or `None` if the file does not exist or has no data
+    pub fn read_file<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        hash: impl Hashable,
+        locale: Locale,
+    ) -> io::Result<Option<Vec<u8>>> {
+        let mut data = Vec::new();
+        let found = self.read_file_into(reader, hash, locale, &mut data, &mut Vec::new())?;
+        Ok(found.then_some(data))
+    }
+
+    /// Reads the data for a file at the view's version into a buffer owned by the caller
+    ///
+    /// ### Arguments
+    /// * `reader` - The reader for the archive file that the archive was read from
+    /// * `hash` - The hash of the file
+    /// * `locale` - The locale to pick localized and regional data with
+    /// * `buffer` - The buffer to read the decompressed data into, which is resized to fit it
+    /// * `scratch` - The buffer to read compressed data into before it is decompressed
+    ///
+    /// ### Returns
+    /// Whether the file exists and has data. If it doesn't, `buffer` is left untouched.
+    ///
+    /// ### Notes
+    /// Compressed data is decompressed from `scratch` straight into `buffer` with the archive's
+    /// [`Decompressor::decompress_into`](crate::Decompressor::decompress_into), so reusing
+    /// the same two buffers for many files avoids allocating for each of them.
+    pub fn read_file_into<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        hash: impl Hashable,
+        locale: Locale,
+        buffer: &mut Vec<u8>,
+        scratch: &mut Vec<u8>,
+    ) -> io::Result<bool> {
+        let location = match self.file_location(hash, locale) {
+            Some(location) => location,
+            None => return Ok(false),
+        };
+
+        reader.seek(SeekFrom::Start(location.offset as u64))?;
+
+        buffer.clear();
+        if location.is_compressed {
+            scratch.clear();
+            scratch.resize(location.compressed_size, 0);
+            reader.read_exact(scratch)?;
+
+            buffer.resize(location.decompressed_size, 0);
+            let size = self
+                .archive
+                .options
+                .decompressor
+                .decompress_into(scratch, buffer)?;
+
+            if size != location.decompressed_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "The decompressed data is smaller than the file's decompressed size",
+                ));
+            }
+        } else {
+            buffer.resize(location.compressed_size, 0);
+            reader.read_exact(buffer)?;
+        }
+
+        Ok(true)
+    }
+}
+
+impl Archive {
+    /// Views the archive as it was at an earlier game version
+    ///
+    /// ### Arguments
+    /// * `version` - The game version to view. This must be the archive's own version
+    ///   or the version of one of its patches.
+    ///
+    /// ### Returns
+    /// The view, or `None` if the archive has no patch for the version
+    ///
+    /// ### Panicking
+    /// Panics if the archive has not been [resolved](Self::resolve)
+    ///
+    /// ### Notes
+    /// Versioned files which the patch has no info for are viewed with their current info
+    pub fn at_version(&self, version: Version) -> Option<ArchiveAtVersion<'_>> {
+        let engine = &self.packaged_fs;
+
+        let patch = match engine.get_patch(&version) {
+            Some(patch) => Some(patch),
+            None if version == self.version => None,
+            None => return None,
+        };
+
+        let mut infos = HashLookup::new();
+        if let Some(patch) = patch {
+            for (versioned_file, info) in engine.patch_entries(patch) {
+                infos.insert(engine.versioned_files[versioned_file].path, info);
+            }
+        }
+
+        Some(ArchiveAtVersion {
+            archive: self,
+            version,
+            patch,
+            infos,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        archive::tests::build_archive,
+        engines::{
+            packaged::tests::add_patch, packaged::types::Metadata, table::TableContiguousReference,
+        },
+    };
+
+    const MODEL: &str = "fighter/mario/model/body/c00/model.numdlb";
+    const MOTION: &str = "fighter/mario/motion/body/c00/a00wait1.nuanmb";
+
+    fn current_info(archive: &Archive, file: &str) -> TableIndex<Info> {
+        let engine = &archive.packaged_fs;
+        engine.links[engine.get_file(file).unwrap().link()].info()
+    }
+
+    /// Points an info at new uncompressed data in its group
+    fn set_data(archive: &mut Archive, info: TableIndex<Info>, offset: usize, size: usize) {
+        let engine = &mut archive.packaged_fs;
+
+        let mut metadata = Metadata::new();
+        metadata.group_offset = offset;
+        metadata.compressed_size = size;
+        metadata.decompressed_size = size;
+        let metadata = engine.metadatas.push(metadata);
+
+        let mut descriptor = engine.descriptors[engine.infos[info].descriptors.get(0)].clone();
+        descriptor.set_metadata(metadata);
+        let descriptor = engine.descriptors.push(descriptor);
+        engine.infos[info].descriptors.replace([descriptor]);
+    }
+
+    /// Builds an archive at 13.0.1 where the model was different in 13.0.0
+    ///
+    /// The file data is `"current" "old"`
+    fn build_versioned_archive() -> Archive {
+        let mut archive = build_archive();
+        let info = current_info(&archive, MODEL);
+        set_data(&mut archive, info, 0, 7);
+
+        let patch = add_patch(
+            &mut archive.packaged_fs,
+            Version::new(13, 0, 0),
+            &[(MODEL, true)],
+        );
+        let info = archive.packaged_fs.patches[patch].infos.get(0);
+        set_data(&mut archive, info, 7, 3);
+        archive
+    }
+
+    #[test]
+    fn versioned_files_read_their_old_data() {
+        let archive = build_versioned_archive();
+        let mut reader = Cursor::new(b"currentold".to_vec());
+
+        let current = archive.at_version(Version::new(13, 0, 1)).unwrap();
+        assert!(current.patch().is_none());
+        assert!(!current.is_versioned(MODEL));
+        assert_eq!(
+            current
+                .read_file(&mut reader, MODEL, Locale::Japanese)
+                .unwrap(),
+            Some(b"current".to_vec())
+        );
+
+        let old = archive.at_version(Version::new(13, 0, 0)).unwrap();
+        assert!(old.is_versioned(MODEL));
+        assert!(!old.is_versioned(MOTION));
+
+        let (mut buffer, mut scratch) = (vec![0xFF; 0x20], vec![]);
+        assert!(old
+            .read_file_into(
+                &mut reader,
+                MODEL,
+                Locale::Japanese,
+                &mut buffer,
+                &mut scratch
+            )
+            .unwrap());
+        assert_eq!(buffer, b"old");
+
+        assert!(!old
+            .read_file_into(
+                &mut reader,
+                "fighter/luigi/model.numdlb",
+                Locale::Japanese,
+                &mut buffer,
+                &mut scratch
+            )
+            .unwrap());
+        assert_eq!(buffer, b"old");
+
+        assert!(archive.at_version(Version::new(12, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn files_without_a_patch_info_use_their_current_info() {
+        let mut archive = build_versioned_archive();
+        let engine = &mut archive.packaged_fs;
+        let patch = engine.get_patch(&Version::new(13, 0, 0)).unwrap();
+
+        let mut infos = TableContiguousReference::new_from_count(0, 0);
+        infos.resolve(&engine.infos).unwrap();
+        engine.patches[patch].infos = infos;
+
+        let old = archive.at_version(Version::new(13, 0, 0)).unwrap();
+        assert!(!old.is_versioned(MODEL));
+        assert_eq!(
+            old.read_file(
+                &mut Cursor::new(b"currentold".to_vec()),
+                MODEL,
+                Locale::Japanese
+            )
+            .unwrap(),
+            Some(b"current".to_vec())
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compressed_files_are_decompressed_into_the_buffer() {
+        use std::sync::Arc;
+
+        use crate::{archive::ArchiveOptions, Decompressor, DefaultDecompressor, ZstdDecompressor};
+
+        let mut archive = build_archive();
+        let data = b"model data ".repeat(16);
+        let compressed = zstd::bulk::compress(&data, 0).unwrap();
+
+        let info = current_info(&archive, MODEL);
+        set_data(&mut archive, info, 0, compressed.len());
+        let engine = &mut archive.packaged_fs;
+        let descriptor = engine.infos[info].descriptors.get(0);
+        let metadata = &mut engine.metadatas[engine.descriptors[descriptor].metadata()];
+        metadata.decompressed_size = data.len();
+        metadata.is_compressed = true;
+
+        let info = current_info(&archive, MOTION);
+        set_data(&mut archive, info, compressed.len(), 6);
+
+        let mut file = compressed.clone();
+        file.extend_from_slice(b"motion");
+        let mut reader = Cursor::new(file);
+
+        let decompressors: [Arc<dyn Decompressor>; 2] =
+            [Arc::new(DefaultDecompressor), Arc::new(ZstdDecompressor)];
+        for decompressor in decompressors {
+            archive.options = ArchiveOptions::new().decompressor(decompressor);
+            let view = archive.at_version(Version::new(13, 0, 1)).unwrap();
+
+            let (mut buffer, mut scratch) = (vec![], vec![]);
+            for _ in 0..2 {
+                assert!(view
+                    .read_file_into(
+                        &mut reader,
+                        MODEL,
+                        Locale::Japanese,
+                        &mut buffer,
+                        &mut scratch
+                    )
+                    .unwrap());
+                assert_eq!(buffer, data);
+                assert_eq!(scratch, compressed);
+            }
+
+            assert!(view
+                .read_file_into(
+                    &mut reader,
+                    MOTION,
+                    Locale::Japanese,
+                    &mut buffer,
+                    &mut scratch
+                )
+                .unwrap());
+            assert_eq!(buffer, b"motion");
+            assert_eq!(scratch, compressed);
+        }
+
+        // A failed decompression doesn't break the context that the next file reuses
+        let mut output = vec![0; data.len()];
+        assert!(ZstdDecompressor
+            .decompress_into(b"not zstd data", &mut output)
+            .is_err());
+        assert_eq!(
+            ZstdDecompressor
+                .decompress_into(&compressed, &mut output)
+                .unwrap(),
+            data.len()
+        );
+        assert_eq!(output, data);
+    }
+}